@@ -27,6 +27,12 @@ use crate::CommandError;
 pub struct BuildOptions {
     pub target_dir: PathBuf,
     pub force_loop_unroll: bool,
+    /// Prepare `tc_action` programs for the `tc` command of iproute2. The
+    /// `tc` command does not support relocations of data other than maps and
+    /// only understands the legacy layout of BTF sections. Enabled by
+    /// default. Disable it when the programs are loaded by redbpf so that
+    /// they keep `.rodata` and `.BTF.ext`.
+    pub tc_legacy: bool,
 }
 
 impl Default for BuildOptions {
@@ -34,6 +40,7 @@ impl Default for BuildOptions {
         BuildOptions {
             target_dir: env::current_dir().unwrap().join("target"),
             force_loop_unroll: false,
+            tc_legacy: true,
         }
     }
 }
@@ -58,6 +65,7 @@ impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Error::IOError(e) => Some(e),
+            Error::PatternError(e) => Some(e),
             _ => None,
         }
     }
@@ -78,7 +86,7 @@ impl Display for Error {
             Compile(p, Some(msg)) => write!(f, "failed to compile the `{}' program: {}", p, msg),
             Compile(p, None) => write!(f, "failed to compile the `{}' program", p),
             MissingBitcode(p) => write!(f, "failed to generate bitcode for the `{}' program", p),
-            Link(p) => write!(f, "failed to generate bitcode for the `{}' program", p),
+            Link(p) => write!(f, "failed to link the `{}' program", p),
             NoOPT => write!(f, "no usable opt executable found, expecting version 9"),
             NoLLC => write!(f, "no usable llc executable found, expecting version 9"),
             IOError(e) => write!(f, "{}", e),
@@ -128,6 +136,7 @@ fn build_probe(
     target_dir: &Path,
     probe: &str,
     features: &Vec<String>,
+    tc_legacy: bool,
 ) -> Result<(), Error> {
     fs::create_dir_all(&target_dir)?;
     let target_dir = target_dir.canonicalize().unwrap().join("bpf");
@@ -234,13 +243,14 @@ fn build_probe(
 
     // stripping .debug sections, .text section and BTF sections is optional
     // process. So don't care about its failure.
-    let contains_tc = unsafe {
-        llvm::get_function_section_names(&bc_file)
-            .map_or_else(|_| vec![], |names| names)
-            .iter()
-            .find(|name| name.starts_with("tc_action/"))
-            .is_some()
-    };
+    let contains_tc = tc_legacy
+        && unsafe {
+            llvm::get_function_section_names(&bc_file)
+                .map_or_else(|_| vec![], |names| names)
+                .iter()
+                .find(|name| name.starts_with("tc_action/"))
+                .is_some()
+        };
     if contains_tc {
         let elf_bytes = fs::read(&target_tmp).map_err(|e| Error::IOError(e))?;
         let binary = Elf::parse(&elf_bytes).map_err(|_| -> Error {
@@ -250,7 +260,18 @@ fn build_probe(
         let fixed = btf::tc_legacy_fix_btf_section(elf_bytes.as_slice()).map_err(|_| Error::BTF)?;
         fs::write(&target_tmp, fixed).map_err(|e| Error::IOError(e))?;
     }
-    let _ = llvm::strip_unnecessary(&target_tmp, contains_tc);
+    // .text is kept only if it contains functions that programs call
+    let empty_text = fs::read(&target_tmp)
+        .ok()
+        .and_then(|elf_bytes| {
+            let binary = Elf::parse(&elf_bytes).ok()?;
+            let empty = binary.section_headers.iter().all(|shdr| {
+                shdr.sh_size == 0 || binary.shdr_strtab.get_at(shdr.sh_name) != Some(".text")
+            });
+            Some(empty)
+        })
+        .unwrap_or(true);
+    let _ = llvm::strip_unnecessary(&target_tmp, contains_tc, empty_text);
     let target = artifacts_dir.join(format!("{}.elf", probe));
     fs::rename(&target_tmp, &target).map_err(|e| Error::IOError(e))?;
     Ok(())
@@ -349,7 +370,14 @@ pub fn build_with_features(
     }
 
     for probe in probes {
-        build_probe(cargo, package, &buildopt.target_dir, &probe, &features)?;
+        build_probe(
+            cargo,
+            package,
+            &buildopt.target_dir,
+            &probe,
+            &features,
+            buildopt.tc_legacy,
+        )?;
     }
 
     Ok(())
@@ -33,8 +33,17 @@ mod new;
 #[cfg(feature = "command-line")]
 mod new_program;
 
+#[derive(Debug)]
 pub struct CommandError(pub String);
 
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
 impl std::convert::From<std::io::Error> for CommandError {
     fn from(e: std::io::Error) -> CommandError {
         CommandError(format!("{}", e))
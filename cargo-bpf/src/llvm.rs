@@ -432,9 +432,14 @@ fn find_available_command<'a>(candidates: &[&'a str]) -> Option<&'a str> {
 /// cf) `llvm_sys::debuginfo::LLVMStripModuleDebugInfo` removes BTF sections so
 /// do not call it.
 ///
-/// .text section is also removed.
+/// .text section is also removed if `delete_text` is true. It should be kept
+/// when programs call functions in it.
 ///
-pub(crate) fn strip_unnecessary(target: &impl AsRef<Path>, delete_btf: bool) -> Result<()> {
+pub(crate) fn strip_unnecessary(
+    target: &impl AsRef<Path>,
+    delete_btf: bool,
+    delete_text: bool,
+) -> Result<()> {
     let cmd = find_available_command(&[
         "llvm-strip",
         "llvm-strip-13",
@@ -458,12 +463,14 @@ pub(crate) fn strip_unnecessary(target: &impl AsRef<Path>, delete_btf: bool) ->
     // section is created with zero size as a result of compilation. So it is
     // needed to remove it explictly. The .text section can cause a problem if
     // the resulting ELF relocatable file is passed to tc command.
-    cmd.args("--remove-section .text".split(' '))
-        .arg("--no-strip-all")
+    if delete_text {
+        cmd.args("--remove-section .text".split(' '));
+    }
+    cmd.arg("--no-strip-all")
         .arg(target.as_ref())
         .status()
         .map(|_| ())
-        .or_else(|e| Err(anyhow!("llvm-strip --remove-section .text failed: {}", e)))
+        .or_else(|e| Err(anyhow!("llvm-strip --remove-section failed: {}", e)))
 }
 
 pub unsafe fn process_ir(context: LLVMContextRef, module: LLVMModuleRef) -> Result<()> {
@@ -475,6 +482,8 @@ pub unsafe fn process_ir(context: LLVMContextRef, module: LLVMModuleRef) -> Resu
     let always_inline_kind =
         LLVMGetEnumAttributeKindForName(always_inline.as_ptr(), "alwaysinline".len());
     let always_inline_attr = LLVMCreateEnumAttribute(context, always_inline_kind, 0);
+    let cold = CString::new("cold").unwrap();
+    let cold_kind = LLVMGetEnumAttributeKindForName(cold.as_ptr(), "cold".len());
 
     let mut func = LLVMGetFirstFunction(module);
     while !func.is_null() {
@@ -482,9 +491,15 @@ pub unsafe fn process_ir(context: LLVMContextRef, module: LLVMModuleRef) -> Resu
         let name = CStr::from_ptr(LLVMGetValueName2(func, &mut size as *mut _))
             .to_str()
             .unwrap();
-        if !name.starts_with("llvm.") {
-            // make sure everything gets inlined as BPF can't do calls to
-            // things other than helpers
+        let has_attr =
+            |kind| !LLVMGetEnumAttributeAtIndex(func, LLVMAttributeFunctionIndex, kind).is_null();
+        // functions marked with `#[inline(never)]` are kept in .text and
+        // called with BPF-to-BPF calls. Cold functions like the panic
+        // machinery of core are still inlined.
+        let subprogram = has_attr(no_inline_kind) && !has_attr(cold_kind);
+        if !name.starts_with("llvm.") && !subprogram {
+            // make sure everything else gets inlined so that only helpers and
+            // explicit subprograms are called
             LLVMRemoveEnumAttributeAtIndex(func, LLVMAttributeFunctionIndex, no_inline_kind);
             LLVMAddAttributeAtIndex(func, LLVMAttributeFunctionIndex, always_inline_attr);
 
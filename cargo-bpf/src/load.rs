@@ -27,7 +27,8 @@ pub fn load(
         .unwrap();
     rt.block_on(async {
         // Load all the programs and maps included in the program
-        let mut loader = Loader::load_file(&program).expect("error loading file");
+        let mut loader = Loader::load_file(&program)
+            .map_err(|e| CommandError(format!("error loading file: {}", e)))?;
 
         // attach the programs
         for program in loader.module.programs.iter_mut() {
@@ -60,7 +61,7 @@ pub fn load(
             };
             if let Err(e) = ret {
                 return Err(CommandError(format!(
-                    "failed to attach program {}: {:?}",
+                    "failed to attach program {}: {}",
                     name, e
                 )));
             }
@@ -189,6 +189,9 @@ fn main() {
                             .arg(Arg::with_name("FORCE_LOOP_UNROLL").long("force-loop-unroll").help(
                                 "Ensure every loop is unrolled"
                             ))
+                            .arg(Arg::with_name("NO_TC_LEGACY").long("no-tc-legacy").help(
+                                "Do not prepare tc_action programs for the tc command. Use this when redbpf loads them"
+                            ))
                             .arg(Arg::with_name("NAME").required(false).multiple(true).help(
                                 "The names of the programs to compile. When no names are specified, all the programs are built",
                             ))
@@ -243,6 +246,7 @@ fn main() {
             buildopt.target_dir = PathBuf::from(v);
         }
         buildopt.force_loop_unroll = m.is_present("FORCE_LOOP_UNROLL");
+        buildopt.tc_legacy = !m.is_present("NO_TC_LEGACY");
         let programs = m
             .values_of("NAME")
             .map(|i| i.map(String::from).collect())
@@ -13,6 +13,7 @@ use tracing::{error, Level};
 use tracing_subscriber::FmtSubscriber;
 
 use redbpf::load::{Loaded, Loader};
+use redbpf::symbolizer::UserSymbolizer;
 use redbpf::{BpfStackFrames, StackTrace};
 
 use probes::mallocstacks::MallocEvent;
@@ -100,17 +101,15 @@ fn main() {
     });
     println!("");
 
+    let mut symbolizer = UserSymbolizer::new(pid).expect("error reading memory maps");
     let acc = acc.lock().unwrap();
     for alloc_size in acc.values() {
         println!(
             "{} bytes allocated, malloc called {} times at:",
             alloc_size.size, alloc_size.count
         );
-        for ip in alloc_size.frames.ip.iter() {
-            if *ip == 0x0 {
-                break;
-            }
-            println!("{:#x}", ip);
+        for frame in symbolizer.symbolize_frames(&alloc_size.frames) {
+            println!("\t{}", frame);
         }
     }
 }
@@ -12,7 +12,8 @@
 /// The instruction that the sharemap2 loads a map from the pin file which was
 /// created by sharemap1 is defined at example-probes/src/sharemap2/main.rs. It
 /// utilizes `redbpf::Map::from_pin_file` and
-/// `redbpf::ModuleBuilder::replace_map`.
+/// `redbpf::ModuleBuilder::replace_map`. Alternatively, maps defined with
+/// `#[map(pinning = "by_name")]` are pinned and reused automatically.
 use redbpf::{Array, Map, ModuleBuilder};
 use std::process;
 use std::time::Duration;
@@ -14,16 +14,22 @@
 use futures::stream::StreamExt;
 use std::env;
 use std::process;
-use std::ptr;
 use tokio::signal::ctrl_c;
 use tracing::{error, Level};
 use tracing_subscriber::FmtSubscriber;
 
-use redbpf::load::Loader;
+use redbpf::load::{Loader, PlainData};
 use redbpf::HashMap;
 
 use probes::tcp_lifetime::{SocketAddr, TCPLifetime};
 
+/// `TCPLifetime` is defined in the probes crate, so it is wrapped to be
+/// marked as plain data.
+#[repr(transparent)]
+struct Event(TCPLifetime);
+
+unsafe impl PlainData for Event {}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
     let subscriber = FmtSubscriber::builder()
@@ -43,32 +49,25 @@ async fn main() {
     println!("Attaching socket to interface {}", iface);
     let mut raw_fds = Vec::new();
     let mut loaded = Loader::load(probe_code()).expect("error loading BPF program");
+    // take the events before attaching the socket filter so that none of
+    // them is left in `loaded.events`
+    let mut events = loaded
+        .events_for::<Event>("tcp_lifetime")
+        .expect("error taking tcp_lifetime events");
     for sf in loaded.socket_filters_mut() {
         if let Ok(sock_raw_fd) = sf.attach_socket_filter(iface) {
             raw_fds.push(sock_raw_fd);
         }
     }
-
     let event_fut = async {
         println!("{:^21}  →  {:^21} | {:^11}", "src", "dst", "duration");
-        while let Some((name, events)) = loaded.events.next().await {
-            match name.as_str() {
-                "tcp_lifetime" => {
-                    for event in events {
-                        let tcp_lifetime =
-                            unsafe { ptr::read(event.as_ptr() as *const TCPLifetime) };
-                        println!(
-                            "{:21}  →  {:21} | {:>8} ms",
-                            tcp_lifetime.src.to_string(),
-                            tcp_lifetime.dst.to_string(),
-                            tcp_lifetime.duration / 1000 / 1000
-                        );
-                    }
-                }
-                _ => {
-                    error!("unknown event = {}", name);
-                }
-            }
+        while let Some(Event(tcp_lifetime)) = events.next().await {
+            println!(
+                "{:21}  →  {:21} | {:>8} ms",
+                tcp_lifetime.src.to_string(),
+                tcp_lifetime.dst.to_string(),
+                tcp_lifetime.duration / 1000 / 1000
+            );
         }
     };
     let ctrlc_fut = async {
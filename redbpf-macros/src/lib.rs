@@ -173,9 +173,23 @@ pub fn impl_network_buffer_array(_: TokenStream) -> TokenStream {
 /// // ...
 /// }
 /// ```
+///
+/// Maps can be shared through BPF FS with `#[map(pinning = "by_name")]`.
+/// When the module is loaded, a map of the same name pinned at
+/// `/sys/fs/bpf/<map name>` is reused if there is, otherwise the map is
+/// created and pinned there. Set `pin_path` to use another directory.
+///
+/// ```no_run
+/// # use redbpf_probes::kprobe::prelude::*;
+/// // Shared with other modules through /sys/fs/bpf/myagent/sharedmap
+/// #[map(pinning = "by_name", pin_path = "/sys/fs/bpf/myagent")]
+/// static mut sharedmap: HashMap<u32, u64> = HashMap::with_max_entries(1024);
+/// ```
 #[proc_macro_attribute]
 pub fn map(attrs: TokenStream, item: TokenStream) -> TokenStream {
     let mut link_section: Option<String> = None;
+    let mut pinning: Option<String> = None;
+    let mut pin_path: Option<String> = None;
     for attr in parse_macro_input!(attrs as AttributeArgs) {
         let mut allowed = false;
         match attr {
@@ -195,7 +209,32 @@ pub fn map(attrs: TokenStream, item: TokenStream) -> TokenStream {
                                     allowed = true;
                                 }
                             }
-                            _ => panic!("expected `link_section' as metadata of #[map]"),
+                            "pinning" => {
+                                if let Lit::Str(value) = mnv.lit {
+                                    if pinning.is_some() {
+                                        panic!("#[map(pinning = \"...\")] is used more than once");
+                                    }
+                                    match value.value().as_str() {
+                                        "by_name" | "none" => pinning = Some(value.value()),
+                                        _ => panic!(
+                                            "expected `by_name' or `none' as the value of `pinning'"
+                                        ),
+                                    }
+                                    allowed = true;
+                                }
+                            }
+                            "pin_path" => {
+                                if let Lit::Str(value) = mnv.lit {
+                                    if pin_path.is_some() {
+                                        panic!("#[map(pin_path = \"...\")] is used more than once");
+                                    }
+                                    pin_path = Some(value.value());
+                                    allowed = true;
+                                }
+                            }
+                            _ => panic!(
+                                "expected `link_section', `pinning' or `pin_path' as metadata of #[map]"
+                            ),
                         }
                     }
                 }
@@ -209,9 +248,13 @@ pub fn map(attrs: TokenStream, item: TokenStream) -> TokenStream {
         }
 
         if !allowed {
-            panic!("expected #[map(link_section = \"...\")]");
+            panic!("expected #[map(link_section = \"...\")], #[map(pinning = \"...\")] or #[map(pin_path = \"...\")]");
         }
     }
+    let pinned = pinning.as_deref() == Some("by_name");
+    if pin_path.is_some() && !pinned {
+        panic!("#[map(pin_path = \"...\")] requires #[map(pinning = \"by_name\")]");
+    }
     let static_item = {
         let item = item.clone();
         parse_macro_input!(item as ItemStatic)
@@ -263,6 +306,23 @@ pub fn map(attrs: TokenStream, item: TokenStream) -> TokenStream {
             static #map_btf_ident: #btf_map_type = unsafe { mem::transmute::<[u8; N], #btf_map_type>([0u8; N]) };
         }
     });
+    if pinned {
+        // CAUTION: redbpf finds the directory to pin the map at by the name
+        // MAP_PIN_XXXX in the `maps.pin` section
+        let map_pin_name = format!("MAP_PIN_{}", static_item.ident.to_string());
+        let map_pin_ident = syn::Ident::new(&map_pin_name, static_item.ident.span());
+        let mut path = pin_path
+            .unwrap_or_else(|| "/sys/fs/bpf".to_string())
+            .into_bytes();
+        path.push(0);
+        let path_len = path.len();
+        let path = syn::LitByteStr::new(&path, static_item.ident.span());
+        tokens.extend(quote! {
+            #[no_mangle]
+            #[link_section = "maps.pin"]
+            static #map_pin_ident: [u8; #path_len] = *#path;
+        });
+    }
     tokens.into()
 }
 
@@ -683,6 +743,49 @@ pub fn stream_verdict(attrs: TokenStream, item: TokenStream) -> TokenStream {
     probe_impl("streamverdict", attrs, wrapper, name)
 }
 
+/// Attribute macro for defining BPF programs of `sk_msg`s. An `sk_msg`
+/// program is attached to a `sockmap` or a `sockhash` and runs on every
+/// message sent through the sockets stored in the map. The program decides
+/// whether the message is passed, dropped or redirected to another socket.
+///
+/// # Example
+/// ```no_run
+/// use redbpf_probes::sockmap::prelude::*;
+///
+/// #[map(link_section = "maps/sockhash")]
+/// static mut SOCKHASH: SockHash<u32> = SockHash::with_max_entries(65535);
+///
+/// #[sk_msg]
+/// fn redirect(msg: SkMsg) -> SkAction {
+///     match msg.msg_redirect_hash(unsafe { &mut SOCKHASH }, &msg.local_port(), 0) {
+///         Ok(_) => SkAction::Pass,
+///         Err(_) => SkAction::Drop,
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn sk_msg(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemFn);
+    let name = item.sig.ident.to_string();
+    let ident = item.sig.ident.clone();
+    let outer_ident = Ident::new(&format!("outer_{}", ident), Span::call_site());
+    let wrapper = parse_quote! {
+        fn #outer_ident(msg: *mut ::redbpf_probes::bindings::sk_msg_md) -> i32 {
+            let msg = ::redbpf_probes::sockmap::SkMsg { msg };
+            use ::redbpf_probes::socket::SkAction;
+
+            return match unsafe { #ident(msg) } {
+                SkAction::Pass => ::redbpf_probes::bindings::sk_action_SK_PASS,
+                SkAction::Drop => ::redbpf_probes::bindings::sk_action_SK_DROP,
+            } as i32;
+
+            #item
+        }
+    };
+
+    probe_impl("sk_msg", attrs, wrapper, name)
+}
+
 /// Define [tc action BPF programs](https://man7.org/linux/man-pages/man8/tc-bpf.8.html)
 #[proc_macro_attribute]
 pub fn tc_action(attrs: TokenStream, item: TokenStream) -> TokenStream {
@@ -708,14 +811,117 @@ pub fn tc_action(attrs: TokenStream, item: TokenStream) -> TokenStream {
 /// Attribute macro for defining a BPF iterator of `task`
 #[proc_macro_attribute]
 pub fn task_iter(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    bpf_iter_impl(
+        "task_iter",
+        "bpf_iter__task",
+        "TaskIterContext",
+        attrs,
+        item,
+    )
+}
+
+/// Attribute macro for defining a BPF iterator of `task_file`
+///
+/// The iterator is called for every open file of every task.
+#[proc_macro_attribute]
+pub fn task_file_iter(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    bpf_iter_impl(
+        "task_file_iter",
+        "bpf_iter__task_file",
+        "TaskFileIterContext",
+        attrs,
+        item,
+    )
+}
+
+/// Attribute macro for defining a BPF iterator of `tcp` sockets
+#[proc_macro_attribute]
+pub fn tcp_iter(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    bpf_iter_impl("tcp_iter", "bpf_iter__tcp", "TcpIterContext", attrs, item)
+}
+
+/// Attribute macro for defining a BPF iterator of `udp` sockets
+#[proc_macro_attribute]
+pub fn udp_iter(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    bpf_iter_impl("udp_iter", "bpf_iter__udp", "UdpIterContext", attrs, item)
+}
+
+/// Attribute macro for defining a BPF iterator of `bpf_map`s loaded into the
+/// kernel
+#[proc_macro_attribute]
+pub fn bpf_map_iter(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    bpf_iter_impl(
+        "bpf_map_iter",
+        "bpf_iter__bpf_map",
+        "BpfMapIterContext",
+        attrs,
+        item,
+    )
+}
+
+/// Attribute macro for defining a BPF iterator of the elements of a BPF map
+///
+/// The map is chosen by userspace when the iterator is attached.
+///
+/// # Example
+/// ```no_run
+/// use core::mem;
+/// use redbpf_probes::bpf_iter::prelude::*;
+///
+/// #[bpf_map_elem_iter]
+/// unsafe fn dump_counts(ctx: BpfMapElemIterContext) -> BPFIterAction {
+///     let ctx = ctx.ctx;
+///     let seq = (*(*ctx).__bindgen_anon_1.meta).__bindgen_anon_1.seq;
+///     let value = (*ctx).__bindgen_anon_4.value as *const u64;
+///     if value.is_null() {
+///         return BPFIterAction::Ok;
+///     }
+///     bpf_seq_write(seq, value as *const _, mem::size_of::<u64>() as u32);
+///     BPFIterAction::Ok
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn bpf_map_elem_iter(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    bpf_iter_impl(
+        "bpf_map_elem_iter",
+        "bpf_iter__bpf_map_elem",
+        "BpfMapElemIterContext",
+        attrs,
+        item,
+    )
+}
+
+/// Attribute macro for defining a BPF iterator of `bpf_prog`s loaded into the
+/// kernel
+#[proc_macro_attribute]
+pub fn bpf_prog_iter(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    bpf_iter_impl(
+        "bpf_prog_iter",
+        "bpf_iter__bpf_prog",
+        "BpfProgIterContext",
+        attrs,
+        item,
+    )
+}
+
+fn bpf_iter_impl(
+    kind: &str,
+    ctx_type: &str,
+    ctx_wrapper: &str,
+    attrs: TokenStream,
+    item: TokenStream,
+) -> TokenStream {
     let item = parse_macro_input!(item as ItemFn);
     let name = item.sig.ident.to_string();
     let ident = item.sig.ident.clone();
     let outer_ident = Ident::new(&format!("outer_{}", ident), Span::call_site());
+    let ctx_type = Ident::new(ctx_type, Span::call_site());
+    let ctx_wrapper = Ident::new(ctx_wrapper, Span::call_site());
     let wrapper = parse_quote! {
-        fn #outer_ident(ctx: *mut bpf_iter__task) -> i32 {
-            let task_ctx = ::redbpf_probes::bpf_iter::context::TaskIterContext { ctx };
-            return match unsafe { #ident(task_ctx) } {
+        fn #outer_ident(ctx: *mut ::redbpf_probes::bindings::#ctx_type) -> i32 {
+            let iter_ctx = ::redbpf_probes::bpf_iter::context::#ctx_wrapper { ctx };
+            use ::redbpf_probes::bpf_iter::BPFIterAction;
+            return match unsafe { #ident(iter_ctx) } {
                 BPFIterAction::Ok => 0,
                 BPFIterAction::Retry => 1,
             };
@@ -724,7 +930,371 @@ pub fn task_iter(attrs: TokenStream, item: TokenStream) -> TokenStream {
         }
     };
 
-    probe_impl("task_iter", attrs, wrapper, name)
+    probe_impl(kind, attrs, wrapper, name)
+}
+
+/// Attribute macro that must be used to define `raw_tracepoint` BPF programs.
+///
+/// The attribute argument is the name of the tracepoint without its category,
+/// e.g. `sched_switch`.
+///
+/// # Example
+/// ```no_run
+/// use redbpf_probes::raw_tracepoint::prelude::*;
+///
+/// #[raw_tracepoint("sched_switch")]
+/// fn sched_switch(ctx: RawTracePointContext) {
+///     let preempt = ctx.arg(0);
+///     // ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn raw_tracepoint(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemFn);
+    let name = item.sig.ident.to_string();
+    let ident = item.sig.ident.clone();
+    let outer_ident = Ident::new(&format!("outer_{}", ident), Span::call_site());
+    let wrapper = parse_quote! {
+        fn #outer_ident(ctx: *mut ::redbpf_probes::bindings::bpf_raw_tracepoint_args) -> i32 {
+            let ctx = ::redbpf_probes::raw_tracepoint::RawTracePointContext { ctx };
+            let _ = unsafe { #ident(ctx) };
+            return 0;
+
+            #item
+        }
+    };
+
+    probe_impl("raw_tracepoint", attrs, wrapper, name)
+}
+
+/// Attribute macro that must be used to define BTF-enabled tracepoint
+/// (`tp_btf`) BPF programs.
+///
+/// # Example
+/// ```no_run
+/// use redbpf_probes::raw_tracepoint::prelude::*;
+///
+/// #[tp_btf("sched_switch")]
+/// fn sched_switch(ctx: BtfTracePointContext) {
+///     let next = ctx.arg_ptr::<task_struct>(2);
+///     let pid = unsafe { (*next).pid };
+///     // ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn tp_btf(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemFn);
+    let name = item.sig.ident.to_string();
+    let ident = item.sig.ident.clone();
+    let outer_ident = Ident::new(&format!("outer_{}", ident), Span::call_site());
+    let wrapper = parse_quote! {
+        fn #outer_ident(ctx: *mut u64) -> i32 {
+            let ctx = ::redbpf_probes::raw_tracepoint::BtfTracePointContext { ctx };
+            let _ = unsafe { #ident(ctx) };
+            return 0;
+
+            #item
+        }
+    };
+
+    probe_impl("tp_btf", attrs, wrapper, name)
+}
+
+/// Attribute macro that must be used to define `fentry` BPF programs.
+///
+/// The attribute argument is the name of the kernel function to trace. If it
+/// is omitted, the name of the Rust function is used instead.
+///
+/// # Example
+/// ```no_run
+/// use redbpf_probes::trampoline::prelude::*;
+///
+/// #[fentry("tcp_connect")]
+/// fn tcp_connect_enter(ctx: FEntryContext) {
+///     let sk = ctx.arg(0);
+///     // ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn fentry(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemFn);
+    let name = item.sig.ident.to_string();
+    let ident = item.sig.ident.clone();
+    let outer_ident = Ident::new(&format!("outer_{}", ident), Span::call_site());
+    let wrapper = parse_quote! {
+        fn #outer_ident(ctx: *mut u64) -> i32 {
+            let ctx = ::redbpf_probes::trampoline::FEntryContext { ctx };
+            let _ = unsafe { #ident(ctx) };
+            return 0;
+
+            #item
+        }
+    };
+
+    probe_impl("fentry", attrs, wrapper, name)
+}
+
+/// Attribute macro that must be used to define `fexit` BPF programs.
+///
+/// Unlike `kretprobe`, `fexit` programs have access to
+/// both the arguments and the return value of the traced function so no
+/// additional map is needed to pass the arguments from an entry probe.
+///
+/// # Example
+/// ```no_run
+/// use redbpf_probes::trampoline::prelude::*;
+///
+/// #[fexit("tcp_connect")]
+/// fn tcp_connect_exit(ctx: FExitContext) {
+///     let sk = ctx.arg(0);
+///     let ret = ctx.ret(1);
+///     // ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn fexit(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemFn);
+    let name = item.sig.ident.to_string();
+    let ident = item.sig.ident.clone();
+    let outer_ident = Ident::new(&format!("outer_{}", ident), Span::call_site());
+    let wrapper = parse_quote! {
+        fn #outer_ident(ctx: *mut u64) -> i32 {
+            let ctx = ::redbpf_probes::trampoline::FExitContext { ctx };
+            let _ = unsafe { #ident(ctx) };
+            return 0;
+
+            #item
+        }
+    };
+
+    probe_impl("fexit", attrs, wrapper, name)
+}
+
+/// Attribute macro that must be used to define BPF LSM programs.
+///
+/// The attribute argument is the name of the LSM hook, e.g. `file_open`. The
+/// program must return an [`LsmAction`] that allows or denies the operation.
+///
+/// # Example
+/// ```no_run
+/// use redbpf_probes::lsm::prelude::*;
+///
+/// #[lsm("file_open")]
+/// fn file_open(ctx: LsmContext) -> LsmAction {
+///     LsmAction::Allow
+/// }
+/// ```
+///
+/// [`LsmAction`]: ../redbpf_probes/lsm/enum.LsmAction.html
+#[proc_macro_attribute]
+pub fn lsm(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemFn);
+    let name = item.sig.ident.to_string();
+    let ident = item.sig.ident.clone();
+    let outer_ident = Ident::new(&format!("outer_{}", ident), Span::call_site());
+    let wrapper = parse_quote! {
+        fn #outer_ident(ctx: *mut u64) -> i32 {
+            let ctx = ::redbpf_probes::lsm::LsmContext { ctx };
+            return unsafe { #ident(ctx) }.to_ret();
+
+            #item
+        }
+    };
+
+    probe_impl("lsm", attrs, wrapper, name)
+}
+
+fn cgroup_impl(kind: &str, attrs: TokenStream, item: ItemFn, name: String) -> TokenStream {
+    if attrs.is_empty() {
+        panic!(
+            "expected the attach point of #[{}] as a string literal, e.g. #[{}(\"...\")]",
+            kind, kind
+        );
+    }
+    let attach_point = match parse_macro_input!(attrs as Expr) {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(s), ..
+        }) => s.value(),
+        _ => panic!("expected string literal"),
+    };
+
+    probe_impl(
+        &format!("{}/{}", kind, attach_point),
+        TokenStream::new(),
+        item,
+        name,
+    )
+}
+
+/// Attribute macro that must be used to define `cgroup_skb` BPF programs.
+///
+/// The attribute argument is the direction of the packets the program
+/// filters: `ingress` or `egress`.
+///
+/// # Example
+/// ```no_run
+/// use redbpf_probes::cgroup::prelude::*;
+///
+/// #[cgroup_skb("egress")]
+/// fn egress_policy(skb: SkBuff) -> CGroupAction {
+///     CGroupAction::Allow
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn cgroup_skb(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemFn);
+    let name = item.sig.ident.to_string();
+    let ident = item.sig.ident.clone();
+    let outer_ident = Ident::new(&format!("outer_{}", ident), Span::call_site());
+    let wrapper = parse_quote! {
+        fn #outer_ident(skb: *const ::redbpf_probes::bindings::__sk_buff) -> i32 {
+            let skb = ::redbpf_probes::socket::SkBuff { skb };
+            return unsafe { #ident(skb) } as i32;
+
+            #item
+        }
+    };
+
+    cgroup_impl("cgroup_skb", attrs, wrapper, name)
+}
+
+/// Attribute macro that must be used to define `cgroup_sock` BPF programs.
+///
+/// The attribute argument is the hook the program runs at: `sock_create`,
+/// `sock_release`, `post_bind4` or `post_bind6`.
+///
+/// # Example
+/// ```no_run
+/// use redbpf_probes::cgroup::prelude::*;
+///
+/// #[cgroup_sock("sock_create")]
+/// fn mark_socket(ctx: SockContext) -> CGroupAction {
+///     ctx.set_mark(0x1234);
+///     CGroupAction::Allow
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn cgroup_sock(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemFn);
+    let name = item.sig.ident.to_string();
+    let ident = item.sig.ident.clone();
+    let outer_ident = Ident::new(&format!("outer_{}", ident), Span::call_site());
+    let wrapper = parse_quote! {
+        fn #outer_ident(ctx: *mut ::redbpf_probes::bindings::bpf_sock) -> i32 {
+            let ctx = ::redbpf_probes::cgroup::SockContext { ctx };
+            return unsafe { #ident(ctx) } as i32;
+
+            #item
+        }
+    };
+
+    cgroup_impl("cgroup_sock", attrs, wrapper, name)
+}
+
+/// Attribute macro that must be used to define `cgroup_sock_addr` BPF
+/// programs.
+///
+/// The attribute argument is the hook the program runs at: `bind4`, `bind6`,
+/// `connect4`, `connect6`, `sendmsg4`, `sendmsg6`, `recvmsg4` or `recvmsg6`.
+///
+/// # Example
+/// ```no_run
+/// use redbpf_probes::cgroup::prelude::*;
+///
+/// #[cgroup_sock_addr("connect4")]
+/// fn deny_port_25(ctx: SockAddrContext) -> CGroupAction {
+///     if ctx.user_port() == 25 {
+///         return CGroupAction::Deny;
+///     }
+///     CGroupAction::Allow
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn cgroup_sock_addr(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemFn);
+    let name = item.sig.ident.to_string();
+    let ident = item.sig.ident.clone();
+    let outer_ident = Ident::new(&format!("outer_{}", ident), Span::call_site());
+    let wrapper = parse_quote! {
+        fn #outer_ident(ctx: *mut ::redbpf_probes::bindings::bpf_sock_addr) -> i32 {
+            let ctx = ::redbpf_probes::cgroup::SockAddrContext { ctx };
+            return unsafe { #ident(ctx) } as i32;
+
+            #item
+        }
+    };
+
+    cgroup_impl("cgroup_sock_addr", attrs, wrapper, name)
+}
+
+/// Attribute macro that must be used to define `cgroup_sysctl` BPF programs.
+///
+/// # Example
+/// ```no_run
+/// use redbpf_probes::cgroup::prelude::*;
+///
+/// #[cgroup_sysctl]
+/// fn read_only_sysctl(ctx: SysctlContext) -> CGroupAction {
+///     if ctx.is_write() {
+///         return CGroupAction::Deny;
+///     }
+///     CGroupAction::Allow
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn cgroup_sysctl(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemFn);
+    let name = item.sig.ident.to_string();
+    let ident = item.sig.ident.clone();
+    let outer_ident = Ident::new(&format!("outer_{}", ident), Span::call_site());
+    let wrapper = parse_quote! {
+        fn #outer_ident(ctx: *mut ::redbpf_probes::bindings::bpf_sysctl) -> i32 {
+            let ctx = ::redbpf_probes::cgroup::SysctlContext { ctx };
+            return unsafe { #ident(ctx) } as i32;
+
+            #item
+        }
+    };
+
+    probe_impl("cgroup_sysctl/sysctl", attrs, wrapper, name)
+}
+
+/// Attribute macro that must be used to define `perf_event` BPF programs.
+///
+/// `perf_event` programs are run each time the perf event they are attached
+/// to overflows, e.g. on every tick of a sampling profiler. The program is
+/// passed a [`PerfEventContext`] that gives access to the registers of the
+/// sampled CPU.
+///
+/// # Example
+/// ```no_run
+/// use redbpf_probes::perf_event::prelude::*;
+///
+/// #[perf_event]
+/// fn sample_ip(ctx: PerfEventContext) {
+///     let ip = ctx.regs().ip();
+///     // record the sampled instruction pointer
+/// }
+/// ```
+///
+/// [`PerfEventContext`]: ../redbpf_probes/perf_event/struct.PerfEventContext.html
+#[proc_macro_attribute]
+pub fn perf_event(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemFn);
+    let name = item.sig.ident.to_string();
+    let ident = item.sig.ident.clone();
+    let outer_ident = Ident::new(&format!("outer_{}", ident), Span::call_site());
+    let wrapper = parse_quote! {
+        fn #outer_ident(ctx: *mut ::redbpf_probes::bindings::bpf_perf_event_data) -> i32 {
+            let ctx = ::redbpf_probes::perf_event::PerfEventContext { ctx };
+            let _ = unsafe { #ident(ctx) };
+            return 0;
+
+            #item
+        }
+    };
+
+    probe_impl("perf_event", attrs, wrapper, name)
 }
 
 /// Safe wrapper for bpf_trace_printk helper.
@@ -9,4 +9,71 @@ struct bpf_iter__task {
                 struct task_struct *task;
         };
 };
+#undef bpf_iter__task_file
+struct bpf_iter__task_file {
+        union {
+                struct bpf_iter_meta *meta;
+        };
+        union {
+                struct task_struct *task;
+        };
+        u32 fd __attribute__((aligned(8)));
+        union {
+                struct file *file;
+        };
+};
+#undef bpf_iter__tcp
+struct bpf_iter__tcp {
+        union {
+                struct bpf_iter_meta *meta;
+        };
+        union {
+                struct sock_common *sk_common;
+        };
+        uid_t uid __attribute__((aligned(8)));
+};
+#undef bpf_iter__udp
+struct bpf_iter__udp {
+        union {
+                struct bpf_iter_meta *meta;
+        };
+        union {
+                struct udp_sock *udp_sk;
+        };
+        uid_t uid __attribute__((aligned(8)));
+        int bucket __attribute__((aligned(8)));
+};
+#undef bpf_iter__bpf_map
+struct bpf_iter__bpf_map {
+        union {
+                struct bpf_iter_meta *meta;
+        };
+        union {
+                struct bpf_map *map;
+        };
+};
+#undef bpf_iter__bpf_map_elem
+struct bpf_iter__bpf_map_elem {
+        union {
+                struct bpf_iter_meta *meta;
+        };
+        union {
+                struct bpf_map *map;
+        };
+        union {
+                void *key;
+        };
+        union {
+                void *value;
+        };
+};
+#undef bpf_iter__bpf_prog
+struct bpf_iter__bpf_prog {
+        union {
+                struct bpf_iter_meta *meta;
+        };
+        union {
+                struct bpf_prog *prog;
+        };
+};
 #endif  // __BPF_ITER_H__
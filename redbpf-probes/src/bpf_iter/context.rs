@@ -19,3 +19,42 @@ use crate::bindings::*;
 pub struct TaskIterContext {
     pub ctx: *mut bpf_iter__task,
 }
+
+/// A structure that wraps `bpf_iter__task_file`
+///
+/// The iterator is called for every open file of every task. `fd` is the file
+/// descriptor of the file in the task.
+pub struct TaskFileIterContext {
+    pub ctx: *mut bpf_iter__task_file,
+}
+
+/// A structure that wraps `bpf_iter__tcp`
+///
+/// `sk_common` may point to a `tcp_sock`, a `tcp_timewait_sock` or a
+/// `tcp_request_sock` depending on `sk_common.skc_state`.
+pub struct TcpIterContext {
+    pub ctx: *mut bpf_iter__tcp,
+}
+
+/// A structure that wraps `bpf_iter__udp`
+pub struct UdpIterContext {
+    pub ctx: *mut bpf_iter__udp,
+}
+
+/// A structure that wraps `bpf_iter__bpf_map`
+pub struct BpfMapIterContext {
+    pub ctx: *mut bpf_iter__bpf_map,
+}
+
+/// A structure that wraps `bpf_iter__bpf_map_elem`
+///
+/// `key` and `value` point to the current element of the map the iterator is
+/// attached to. Both of them are null at the end of the iteration.
+pub struct BpfMapElemIterContext {
+    pub ctx: *mut bpf_iter__bpf_map_elem,
+}
+
+/// A structure that wraps `bpf_iter__bpf_prog`
+pub struct BpfProgIterContext {
+    pub ctx: *mut bpf_iter__bpf_prog,
+}
@@ -6,6 +6,18 @@
 // copied, modified, or distributed except according to those terms.
 /*!
 BPF iterators
+
+BPF iterators walk over kernel objects and write data to a seq file with
+`bpf_seq_write` that userspace programs read. The kind of objects is chosen by
+the attribute macro used to define the iterator:
+
+- [`task_iter`](../../redbpf_macros/attr.task_iter.html): tasks
+- [`task_file_iter`](../../redbpf_macros/attr.task_file_iter.html): open files of tasks
+- [`tcp_iter`](../../redbpf_macros/attr.tcp_iter.html): TCP sockets
+- [`udp_iter`](../../redbpf_macros/attr.udp_iter.html): UDP sockets
+- [`bpf_map_iter`](../../redbpf_macros/attr.bpf_map_iter.html): BPF maps
+- [`bpf_map_elem_iter`](../../redbpf_macros/attr.bpf_map_elem_iter.html): elements of a BPF map
+- [`bpf_prog_iter`](../../redbpf_macros/attr.bpf_prog_iter.html): BPF programs
 */
 
 /// Possible types that a BPF program of BPF iterators can return
@@ -24,5 +36,8 @@ pub mod prelude {
     pub use crate::bindings::*;
     pub use crate::helpers::*;
     pub use crate::maps::*;
-    pub use redbpf_macros::{map, printk, program, task_iter};
+    pub use redbpf_macros::{
+        bpf_map_elem_iter, bpf_map_iter, bpf_prog_iter, map, printk, program, task_file_iter,
+        task_iter, tcp_iter, udp_iter,
+    };
 }
@@ -0,0 +1,274 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*!
+cgroup v2 programs.
+
+cgroup programs are attached to a cgroup v2 directory and run for the sockets
+and the processes that belong to the cgroup. Four kinds of cgroup programs are
+supported:
+
+- `cgroup_skb` programs filter the ingress or egress packets of the cgroup.
+- `cgroup_sock` programs run when a socket is created, released or bound.
+- `cgroup_sock_addr` programs run on `bind(2)`, `connect(2)`, `sendmsg(2)` and
+  `recvmsg(2)` and can rewrite the address passed by the process.
+- `cgroup_sysctl` programs run when a process accesses `/proc/sys`.
+
+All of them return a [`CGroupAction`](enum.CGroupAction.html) that allows or
+denies the operation.
+
+# Example
+
+Redirect the IPv4 connections to port 80 to port 8080.
+
+```no_run
+#![no_std]
+#![no_main]
+use redbpf_probes::cgroup::prelude::*;
+
+program!(0xFFFFFFFE, "GPL");
+
+#[cgroup_sock_addr("connect4")]
+fn rewrite_connect(ctx: SockAddrContext) -> CGroupAction {
+    if ctx.user_port() == 80 {
+        ctx.set_user_port(8080);
+    }
+    CGroupAction::Allow
+}
+```
+ */
+pub mod prelude;
+
+use cty::c_char;
+
+use crate::bindings::*;
+use crate::helpers::*;
+
+/// The verdict of cgroup programs.
+#[repr(i32)]
+#[derive(Clone, Copy)]
+pub enum CGroupAction {
+    /// Reject the packet or the operation. Operations fail with `EPERM`.
+    Deny = 0,
+    /// Let the packet or the operation through.
+    Allow = 1,
+}
+
+/// Context object provided to `cgroup_sock` programs.
+#[derive(Clone)]
+pub struct SockContext {
+    pub ctx: *mut bpf_sock,
+}
+
+impl SockContext {
+    /// Returns the raw `bpf_sock` context passed by the kernel.
+    #[inline]
+    pub fn inner(&self) -> *mut bpf_sock {
+        self.ctx
+    }
+
+    /// Returns the address family of the socket, e.g. `AF_INET`.
+    #[inline]
+    pub fn family(&self) -> u32 {
+        unsafe { (*self.ctx).family }
+    }
+
+    /// Returns the type of the socket, e.g. `SOCK_STREAM`.
+    #[inline]
+    pub fn sock_type(&self) -> u32 {
+        unsafe { (*self.ctx).type_ }
+    }
+
+    /// Returns the protocol of the socket, e.g. `IPPROTO_TCP`.
+    #[inline]
+    pub fn protocol(&self) -> u32 {
+        unsafe { (*self.ctx).protocol }
+    }
+
+    /// Returns the local IPv4 address in network byte order. It is set only
+    /// after the socket is bound.
+    #[inline]
+    pub fn src_ip4(&self) -> u32 {
+        unsafe { (*self.ctx).src_ip4 }
+    }
+
+    /// Returns the local port in host byte order. It is set only after the
+    /// socket is bound.
+    #[inline]
+    pub fn src_port(&self) -> u32 {
+        unsafe { (*self.ctx).src_port }
+    }
+
+    /// Binds the socket to the network device with index `ifindex`.
+    #[inline]
+    pub fn set_bound_dev_if(&self, ifindex: u32) {
+        unsafe { (*self.ctx).bound_dev_if = ifindex }
+    }
+
+    /// Sets `SO_MARK` of the socket.
+    #[inline]
+    pub fn set_mark(&self, mark: u32) {
+        unsafe { (*self.ctx).mark = mark }
+    }
+
+    /// Sets `SO_PRIORITY` of the socket.
+    #[inline]
+    pub fn set_priority(&self, priority: u32) {
+        unsafe { (*self.ctx).priority = priority }
+    }
+}
+
+/// Context object provided to `cgroup_sock_addr` programs.
+///
+/// The `user_*` accessors refer to the address passed by the process to the
+/// system call. Setting them rewrites the address the system call uses.
+#[derive(Clone)]
+pub struct SockAddrContext {
+    pub ctx: *mut bpf_sock_addr,
+}
+
+impl SockAddrContext {
+    /// Returns the raw `bpf_sock_addr` context passed by the kernel.
+    #[inline]
+    pub fn inner(&self) -> *mut bpf_sock_addr {
+        self.ctx
+    }
+
+    /// Returns the address family of the address passed by the process.
+    #[inline]
+    pub fn user_family(&self) -> u32 {
+        unsafe { (*self.ctx).user_family }
+    }
+
+    /// Returns the IPv4 address passed by the process in network byte order.
+    #[inline]
+    pub fn user_ip4(&self) -> u32 {
+        unsafe { (*self.ctx).user_ip4 }
+    }
+
+    /// Rewrites the IPv4 address. `addr` is in network byte order.
+    #[inline]
+    pub fn set_user_ip4(&self, addr: u32) {
+        unsafe { (*self.ctx).user_ip4 = addr }
+    }
+
+    /// Returns the IPv6 address passed by the process in network byte order.
+    #[inline]
+    pub fn user_ip6(&self) -> [u32; 4] {
+        // the verifier allows only 4-byte accesses to user_ip6
+        unsafe {
+            [
+                (*self.ctx).user_ip6[0],
+                (*self.ctx).user_ip6[1],
+                (*self.ctx).user_ip6[2],
+                (*self.ctx).user_ip6[3],
+            ]
+        }
+    }
+
+    /// Rewrites the IPv6 address. `addr` is in network byte order.
+    #[inline]
+    pub fn set_user_ip6(&self, addr: [u32; 4]) {
+        unsafe {
+            (*self.ctx).user_ip6[0] = addr[0];
+            (*self.ctx).user_ip6[1] = addr[1];
+            (*self.ctx).user_ip6[2] = addr[2];
+            (*self.ctx).user_ip6[3] = addr[3];
+        }
+    }
+
+    /// Returns the port passed by the process in host byte order.
+    #[inline]
+    pub fn user_port(&self) -> u16 {
+        u16::from_be(unsafe { (*self.ctx).user_port } as u16)
+    }
+
+    /// Rewrites the port. `port` is in host byte order.
+    #[inline]
+    pub fn set_user_port(&self, port: u16) {
+        unsafe { (*self.ctx).user_port = port.to_be() as u32 }
+    }
+
+    /// Returns the address family of the socket.
+    #[inline]
+    pub fn family(&self) -> u32 {
+        unsafe { (*self.ctx).family }
+    }
+
+    /// Returns the type of the socket, e.g. `SOCK_STREAM`.
+    #[inline]
+    pub fn sock_type(&self) -> u32 {
+        unsafe { (*self.ctx).type_ }
+    }
+
+    /// Returns the protocol of the socket, e.g. `IPPROTO_TCP`.
+    #[inline]
+    pub fn protocol(&self) -> u32 {
+        unsafe { (*self.ctx).protocol }
+    }
+}
+
+/// Context object provided to `cgroup_sysctl` programs.
+#[derive(Clone)]
+pub struct SysctlContext {
+    pub ctx: *mut bpf_sysctl,
+}
+
+impl SysctlContext {
+    /// Returns the raw `bpf_sysctl` context passed by the kernel.
+    #[inline]
+    pub fn inner(&self) -> *mut bpf_sysctl {
+        self.ctx
+    }
+
+    /// Returns `true` if the sysctl is being written, `false` if it is being
+    /// read.
+    #[inline]
+    pub fn is_write(&self) -> bool {
+        unsafe { (*self.ctx).write != 0 }
+    }
+
+    /// Returns the file position of the access.
+    #[inline]
+    pub fn file_pos(&self) -> u32 {
+        unsafe { (*self.ctx).file_pos }
+    }
+
+    /// Copies the name of the sysctl relative to `/proc/sys`, e.g.
+    /// `net/ipv4/tcp_mem`, into `buf` as a NUL terminated string.
+    ///
+    /// Returns the length of the name without the terminating NUL, or `None`
+    /// if `buf` is too small.
+    #[inline]
+    pub fn name(&self, buf: &mut [u8]) -> Option<usize> {
+        let ret = unsafe {
+            bpf_sysctl_get_name(self.ctx, buf.as_mut_ptr() as *mut c_char, buf.len() as _, 0)
+        };
+        if ret < 0 {
+            None
+        } else {
+            Some(ret as usize)
+        }
+    }
+
+    /// Copies the new value being written to the sysctl into `buf` as a NUL
+    /// terminated string.
+    ///
+    /// Returns the length of the value without the terminating NUL, or `None`
+    /// if the sysctl is not being written or `buf` is too small.
+    #[inline]
+    pub fn new_value(&self, buf: &mut [u8]) -> Option<usize> {
+        let ret = unsafe {
+            bpf_sysctl_get_new_value(self.ctx, buf.as_mut_ptr() as *mut c_char, buf.len() as _)
+        };
+        if ret < 0 {
+            None
+        } else {
+            Some(ret as usize)
+        }
+    }
+}
@@ -0,0 +1,24 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The cgroup Prelude
+//!
+//! The purpose of this module is to alleviate imports of the common cgroup
+//! program types by adding a glob import to the top of cgroup programs:
+//!
+//! ```
+//! use redbpf_probes::cgroup::prelude::*;
+//! ```
+pub use crate::bindings::*;
+pub use crate::cgroup::*;
+pub use crate::helpers::*;
+pub use crate::maps::*;
+pub use crate::socket::{SkBuff, SocketError};
+pub use cty::*;
+pub use redbpf_macros::{
+    cgroup_skb, cgroup_sock, cgroup_sock_addr, cgroup_sysctl, map, printk, program,
+};
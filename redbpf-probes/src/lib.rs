@@ -106,10 +106,14 @@ tries to probe vmlinux from the well-known system paths and uses it
 #![no_std]
 pub mod bindings;
 pub mod bpf_iter;
+pub mod cgroup;
 pub mod helpers;
 pub mod kprobe;
+pub mod lsm;
 pub mod maps;
 pub mod net;
+pub mod perf_event;
+pub mod raw_tracepoint;
 pub mod registers;
 #[cfg(feature = "ringbuf")]
 pub mod ringbuf;
@@ -118,5 +122,7 @@ pub mod socket_filter;
 pub mod sockmap;
 pub mod tc;
 pub mod tracepoint;
+pub mod trampoline;
 pub mod uprobe;
+pub mod usdt;
 pub mod xdp;
@@ -0,0 +1,88 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*!
+BPF Linux Security Module programs.
+
+BPF LSM programs are attached to the LSM hooks of the kernel, e.g.
+`file_open` or `bprm_check_security`, and decide whether the operation is
+allowed. The arguments of the hook are passed to the program as an array of
+`u64` and they are typed by BTF, so pointer arguments can be dereferenced
+directly. The list of hooks and their arguments can be found in
+[`include/linux/lsm_hook_defs.h`](https://elixir.bootlin.com/linux/v5.10/source/include/linux/lsm_hook_defs.h).
+
+# Example
+
+Deny opening files by the process with pid 1000.
+
+```no_run
+#![no_std]
+#![no_main]
+use redbpf_probes::lsm::prelude::*;
+
+program!(0xFFFFFFFE, "GPL");
+
+#[lsm("file_open")]
+fn file_open(_ctx: LsmContext) -> LsmAction {
+    let pid = bpf_get_current_pid_tgid() >> 32;
+    if pid == 1000 {
+        LsmAction::Deny
+    } else {
+        LsmAction::Allow
+    }
+}
+```
+ */
+pub mod prelude;
+
+/// The return type of LSM programs.
+pub enum LsmAction {
+    /// Allow the operation to proceed.
+    Allow,
+    /// Deny the operation with `EPERM`.
+    Deny,
+    /// Deny the operation with the given errno, e.g. `EACCES`. An errno of 0
+    /// denies the operation with `EPERM` rather than allowing it.
+    DenyWith(i32),
+}
+
+impl LsmAction {
+    /// Returns the value the kernel expects from the LSM hook: 0 to allow or
+    /// a negative errno to deny.
+    #[inline]
+    pub fn to_ret(&self) -> i32 {
+        match self {
+            LsmAction::Allow => 0,
+            LsmAction::Deny | LsmAction::DenyWith(0) => -1, // EPERM
+            LsmAction::DenyWith(errno) => -errno.abs(),
+        }
+    }
+}
+
+/// Context object provided to LSM programs.
+#[derive(Clone)]
+pub struct LsmContext {
+    pub ctx: *mut u64,
+}
+
+impl LsmContext {
+    /// Returns the `n`th argument of the LSM hook.
+    ///
+    /// For hooks that are called after another BPF LSM program, the argument
+    /// following the hook arguments holds the return value of the previous
+    /// program.
+    #[inline]
+    pub fn arg(&self, n: usize) -> u64 {
+        unsafe { *self.ctx.add(n) }
+    }
+
+    /// Returns the `n`th argument of the LSM hook as a pointer to `T`.
+    #[inline]
+    pub fn arg_ptr<T>(&self, n: usize) -> *const T {
+        self.arg(n) as *const T
+    }
+}
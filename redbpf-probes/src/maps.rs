@@ -582,6 +582,88 @@ impl BpfMap for SockMap {
     type Value = i32;
 }
 
+/// SockHash.
+///
+/// A sockhash is a BPF map type that holds references to sock structs like
+/// [`SockMap`](struct.SockMap.html), but the sockets are indexed by arbitrary
+/// keys of type `K` instead of by array index. e.g., a connection 5-tuple can
+/// be used as a key.
+#[repr(transparent)]
+pub struct SockHash<K> {
+    def: bpf_map_def,
+    _k: PhantomData<K>,
+}
+
+impl<K> SockHash<K> {
+    pub const fn with_max_entries(max_entries: u32) -> Self {
+        Self {
+            def: bpf_map_def {
+                type_: bpf_map_type_BPF_MAP_TYPE_SOCKHASH,
+                key_size: mem::size_of::<K>() as u32,
+                value_size: mem::size_of::<i32>() as u32,
+                max_entries,
+                map_flags: 0,
+            },
+            _k: PhantomData,
+        }
+    }
+
+    /// Redirect the packet on `egress path` to the socket referenced by
+    /// sockhash at `key`.
+    pub fn redirect(&mut self, skb: *mut __sk_buff, key: &K) -> Result<(), ()> {
+        self.redirect_skb(skb, key, 0)
+    }
+
+    /// Redirect the packet on `ingress path` to the socket referenced by
+    /// sockhash at `key`.
+    pub fn redirect_ingress(&mut self, skb: *mut __sk_buff, key: &K) -> Result<(), ()> {
+        self.redirect_skb(skb, key, BPF_F_INGRESS.into())
+    }
+
+    /// Redirect the message to the socket referenced by sockhash at `key`.
+    ///
+    /// This is used by `sk_msg` programs. `flags` is either 0 for the egress
+    /// path or `BPF_F_INGRESS` for the ingress path of the target socket.
+    pub fn redirect_msg(&mut self, msg: *mut sk_msg_md, key: &K, flags: u64) -> Result<(), ()> {
+        let ret = unsafe {
+            bpf_msg_redirect_hash(
+                msg as *mut _,
+                &mut self.def as *mut _ as *mut c_void,
+                key as *const _ as *mut c_void,
+                flags,
+            ) as sk_action
+        };
+        #[allow(non_upper_case_globals)]
+        match ret {
+            sk_action_SK_PASS => Ok(()),
+            sk_action_SK_DROP => Err(()),
+            _ => panic!("invalid return value of bpf_msg_redirect_hash"),
+        }
+    }
+
+    fn redirect_skb(&mut self, skb: *mut __sk_buff, key: &K, flags: u64) -> Result<(), ()> {
+        let ret = unsafe {
+            bpf_sk_redirect_hash(
+                skb as *mut _,
+                &mut self.def as *mut _ as *mut c_void,
+                key as *const _ as *mut c_void,
+                flags,
+            ) as sk_action
+        };
+        #[allow(non_upper_case_globals)]
+        match ret {
+            sk_action_SK_PASS => Ok(()),
+            sk_action_SK_DROP => Err(()),
+            _ => panic!("invalid return value of bpf_sk_redirect_hash"),
+        }
+    }
+}
+
+impl<K> BpfMap for SockHash<K> {
+    type Key = K;
+    type Value = i32;
+}
+
 /// LPM trie map.
 ///
 /// An LPM (longest prefix match) trie map is a BPF map type that can be
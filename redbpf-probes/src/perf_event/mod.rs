@@ -0,0 +1,86 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*!
+Perf event programs.
+
+`perf_event` programs are attached to perf events such as the `cpu-clock`
+software event or the `cycles` hardware event, and they are run every time the
+event overflows. Combined with a sampling frequency this makes it possible to
+write on-CPU profilers: the program is passed the registers of the interrupted
+task, from which the instruction pointer or a stack trace can be collected.
+
+# Example
+
+Count how many times each task was sampled on CPU.
+
+```no_run
+#![no_std]
+#![no_main]
+use redbpf_probes::perf_event::prelude::*;
+
+program!(0xFFFFFFFE, "GPL");
+
+#[map]
+static mut samples: HashMap<u32, u64> = HashMap::with_max_entries(10240);
+
+#[perf_event]
+fn on_cpu(_ctx: PerfEventContext) {
+    let tgid = (bpf_get_current_pid_tgid() >> 32) as u32;
+    unsafe {
+        match samples.get_mut(&tgid) {
+            Some(count) => *count += 1,
+            None => samples.set(&tgid, &1),
+        }
+    }
+}
+```
+ */
+pub mod prelude;
+
+use crate::bindings::*;
+use crate::registers::Registers;
+
+/// Context object provided to `perf_event` programs.
+///
+/// `perf_event` programs are passed a `PerfEventContext` instance as their
+/// argument. It wraps the `bpf_perf_event_data` context passed by the kernel.
+#[derive(Clone)]
+pub struct PerfEventContext {
+    pub ctx: *mut bpf_perf_event_data,
+}
+
+impl PerfEventContext {
+    /// Returns the raw `bpf_perf_event_data` context passed by the kernel.
+    #[inline]
+    pub fn inner(&self) -> *mut bpf_perf_event_data {
+        self.ctx
+    }
+
+    /// Returns the registers of the task that was running when the event
+    /// was sampled.
+    #[inline]
+    pub fn regs(&self) -> Registers {
+        // `regs` is the first member of `bpf_perf_event_data`
+        Registers {
+            ctx: unsafe { &mut (*self.ctx).regs as *mut _ as *mut pt_regs },
+        }
+    }
+
+    /// Returns the sample period of the event.
+    #[inline]
+    pub fn sample_period(&self) -> u64 {
+        unsafe { (*self.ctx).sample_period }
+    }
+
+    /// Returns the address associated with the event, if the event provides
+    /// one.
+    #[inline]
+    pub fn addr(&self) -> u64 {
+        unsafe { (*self.ctx).addr }
+    }
+}
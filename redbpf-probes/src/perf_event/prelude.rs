@@ -0,0 +1,25 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The perf event Prelude
+//!
+//! The purpose of this module is to alleviate imports of the common
+//! `perf_event` types by adding a glob import to the top of `perf_event`
+//! programs:
+//!
+//! ```
+//! use redbpf_probes::perf_event::prelude::*;
+//! ```
+pub use crate::bindings::*;
+pub use crate::helpers::*;
+pub use crate::maps::*;
+pub use crate::perf_event::*;
+pub use crate::registers::*;
+#[cfg(feature = "ringbuf")]
+pub use crate::ringbuf::*;
+pub use cty::*;
+pub use redbpf_macros::{map, perf_event, printk, program};
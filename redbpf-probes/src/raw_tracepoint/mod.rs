@@ -0,0 +1,94 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*!
+Raw tracepoints and BTF-enabled tracepoints.
+
+Raw tracepoints (`raw_tracepoint`) receive the arguments of the tracepoint as
+they are passed to the tracepoint in the kernel, without the copying and
+formatting performed for regular tracepoints. BTF-enabled tracepoints
+(`tp_btf`) receive the same arguments but the kernel knows their types, so
+pointer arguments can be dereferenced directly instead of being read with
+`bpf_probe_read`.
+
+The arguments are those of the `TP_PROTO` of the tracepoint. For example
+`sched_switch` is defined with `TP_PROTO(bool preempt, struct task_struct
+*prev, struct task_struct *next)`.
+
+# Example
+
+```no_run
+#![no_std]
+#![no_main]
+use redbpf_probes::raw_tracepoint::prelude::*;
+
+program!(0xFFFFFFFE, "GPL");
+
+#[raw_tracepoint("sched_switch")]
+fn sched_switch(ctx: RawTracePointContext) {
+    let prev = ctx.arg_ptr::<task_struct>(1);
+    let next = ctx.arg_ptr::<task_struct>(2);
+    // ...
+}
+```
+ */
+pub mod prelude;
+
+use crate::bindings::*;
+
+/// Context object provided to `raw_tracepoint` programs.
+#[derive(Clone)]
+pub struct RawTracePointContext {
+    pub ctx: *mut bpf_raw_tracepoint_args,
+}
+
+impl RawTracePointContext {
+    /// Returns the raw `bpf_raw_tracepoint_args` context passed by the kernel.
+    #[inline]
+    pub fn inner(&self) -> *mut bpf_raw_tracepoint_args {
+        self.ctx
+    }
+
+    /// Returns the `n`th argument of the tracepoint.
+    #[inline]
+    pub fn arg(&self, n: usize) -> u64 {
+        unsafe { *(self.ctx as *const u64).add(n) }
+    }
+
+    /// Returns the `n`th argument of the tracepoint as a pointer to `T`.
+    ///
+    /// The pointer refers to kernel memory so it must be read with
+    /// `bpf_probe_read`.
+    #[inline]
+    pub fn arg_ptr<T>(&self, n: usize) -> *const T {
+        self.arg(n) as *const T
+    }
+}
+
+/// Context object provided to `tp_btf` programs.
+#[derive(Clone)]
+pub struct BtfTracePointContext {
+    pub ctx: *mut u64,
+}
+
+impl BtfTracePointContext {
+    /// Returns the `n`th argument of the tracepoint.
+    #[inline]
+    pub fn arg(&self, n: usize) -> u64 {
+        unsafe { *self.ctx.add(n) }
+    }
+
+    /// Returns the `n`th argument of the tracepoint as a pointer to `T`.
+    ///
+    /// The verifier knows the type of the argument, so the pointer can be
+    /// dereferenced directly as long as `T` matches the type declared in the
+    /// `TP_PROTO` of the tracepoint.
+    #[inline]
+    pub fn arg_ptr<T>(&self, n: usize) -> *const T {
+        self.arg(n) as *const T
+    }
+}
@@ -0,0 +1,24 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The raw tracepoint Prelude
+//!
+//! The purpose of this module is to alleviate imports of the common
+//! `raw_tracepoint` and `tp_btf` types by adding a glob import to the top of
+//! such programs:
+//!
+//! ```
+//! use redbpf_probes::raw_tracepoint::prelude::*;
+//! ```
+pub use crate::bindings::*;
+pub use crate::helpers::*;
+pub use crate::maps::*;
+pub use crate::raw_tracepoint::*;
+#[cfg(feature = "ringbuf")]
+pub use crate::ringbuf::*;
+pub use cty::*;
+pub use redbpf_macros::{map, printk, program, raw_tracepoint, tp_btf};
@@ -6,8 +6,40 @@
 // copied, modified, or distributed except according to those terms.
 /*!
 Sockmap for socket redirection with stream parser and verdict
+
+Besides `stream_parser` and `stream_verdict` programs, `sk_msg` programs can
+be attached to a [`SockMap`](../maps/struct.SockMap.html) or a
+[`SockHash`](../maps/struct.SockHash.html). They run on every `sendmsg` of the
+sockets stored in the map and decide whether the message is passed, dropped
+or redirected to another socket.
+
+# Example
+
+```no_run
+#![no_std]
+#![no_main]
+use redbpf_probes::sockmap::prelude::*;
+
+program!(0xFFFFFFFE, "GPL");
+
+#[map(link_section = "maps/sockhash")]
+static mut SOCKHASH: SockHash<u32> = SockHash::with_max_entries(65535);
+
+#[sk_msg]
+fn bypass(msg: SkMsg) -> SkAction {
+    // userspace stores the peer socket of each connection by local port
+    let key = msg.local_port();
+    match msg.msg_redirect_hash(unsafe { &mut SOCKHASH }, &key, BPF_F_INGRESS.into()) {
+        Ok(_) => SkAction::Pass,
+        Err(_) => SkAction::Drop,
+    }
+}
+```
 */
 pub mod prelude;
+use crate::bindings::*;
+use crate::helpers::{bpf_msg_apply_bytes, bpf_msg_cork_bytes};
+use crate::maps::SockHash;
 use crate::socket::SocketError;
 
 pub enum StreamParserAction {
@@ -17,3 +49,97 @@ pub enum StreamParserAction {
 }
 
 pub type StreamParserResult = Result<StreamParserAction, SocketError>;
+
+/// Context object provided to `sk_msg` programs.
+pub struct SkMsg {
+    /// The low level message metadata.
+    pub msg: *mut sk_msg_md,
+}
+
+impl SkMsg {
+    /// Returns the address family of the socket.
+    #[inline]
+    pub fn family(&self) -> u32 {
+        unsafe { (*self.msg).family }
+    }
+
+    /// Returns the remote IPv4 address in network byte order.
+    #[inline]
+    pub fn remote_ip4(&self) -> u32 {
+        unsafe { (*self.msg).remote_ip4 }
+    }
+
+    /// Returns the local IPv4 address in network byte order.
+    #[inline]
+    pub fn local_ip4(&self) -> u32 {
+        unsafe { (*self.msg).local_ip4 }
+    }
+
+    /// Returns the remote IPv6 address in network byte order.
+    #[inline]
+    pub fn remote_ip6(&self) -> [u32; 4] {
+        unsafe { (*self.msg).remote_ip6 }
+    }
+
+    /// Returns the local IPv6 address in network byte order.
+    #[inline]
+    pub fn local_ip6(&self) -> [u32; 4] {
+        unsafe { (*self.msg).local_ip6 }
+    }
+
+    /// Returns the remote port in network byte order.
+    #[inline]
+    pub fn remote_port(&self) -> u32 {
+        unsafe { (*self.msg).remote_port }
+    }
+
+    /// Returns the local port in host byte order.
+    #[inline]
+    pub fn local_port(&self) -> u32 {
+        unsafe { (*self.msg).local_port }
+    }
+
+    /// Returns the total size of the message.
+    #[inline]
+    pub fn size(&self) -> u32 {
+        unsafe { (*self.msg).size }
+    }
+
+    /// Redirect the message to the socket referenced by `map` at `key`.
+    ///
+    /// `flags` is either 0 for the egress path or `BPF_F_INGRESS` for the
+    /// ingress path of the target socket.
+    #[inline]
+    pub fn msg_redirect_hash<K>(
+        &self,
+        map: &mut SockHash<K>,
+        key: &K,
+        flags: u64,
+    ) -> Result<(), ()> {
+        map.redirect_msg(self.msg, key, flags)
+    }
+
+    /// Apply the verdict of the program to the next `bytes` bytes of the
+    /// message only. The program runs again once they are consumed.
+    #[inline]
+    pub fn msg_apply_bytes(&self, bytes: u32) -> Result<(), ()> {
+        let ret = unsafe { bpf_msg_apply_bytes(self.msg as *mut _, bytes) };
+        if ret < 0 {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Hold the message until at least `bytes` bytes are accumulated before
+    /// running the program again.
+    #[inline]
+    pub fn msg_cork_bytes(&self, bytes: u32) -> Result<(), ()> {
+        let ret = unsafe { bpf_msg_cork_bytes(self.msg as *mut _, bytes) };
+        if ret < 0 {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+}
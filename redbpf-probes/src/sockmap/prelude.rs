@@ -3,4 +3,4 @@ pub use crate::helpers::*;
 pub use crate::maps::*;
 pub use crate::socket::{SkAction, SkBuff};
 pub use crate::sockmap::*;
-pub use redbpf_macros::{map, printk, program, stream_parser, stream_verdict};
+pub use redbpf_macros::{map, printk, program, sk_msg, stream_parser, stream_verdict};
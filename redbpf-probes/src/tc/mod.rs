@@ -10,6 +10,10 @@ BPF for tc utility
 tc supports attaching BPF programs to `clsact` qdisc as a direct action. You
 can write BPF programs and BPF maps using `redBPF`.
 
+The compiled programs can be attached with the `tc` utility or directly from
+userspace with `redbpf::TcAction::attach_tc`, which creates the `clsact` qdisc
+if needed.
+
 # Example
 
 ```no_run
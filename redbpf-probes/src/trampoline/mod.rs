@@ -0,0 +1,85 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*!
+BPF trampoline programs: `fentry` and `fexit`.
+
+`fentry` and `fexit` programs are hooks on the entry and exit of a kernel
+function, like kprobes and kretprobes. Instead of relying on breakpoints they
+are called through a BPF trampoline, which makes them considerably cheaper. The
+kernel function is resolved through BTF so the running kernel must be built
+with `CONFIG_DEBUG_INFO_BTF`.
+
+Arguments of the kernel function are passed to the programs as an array of
+`u64`. `fexit` programs additionally receive the return value of the function,
+which is stored right after the arguments.
+
+# Example
+
+Log the return value of `tcp_connect`.
+
+```no_run
+#![no_std]
+#![no_main]
+use redbpf_probes::trampoline::prelude::*;
+
+program!(0xFFFFFFFE, "GPL");
+
+#[fexit("tcp_connect")]
+fn tcp_connect_exit(ctx: FExitContext) {
+    // tcp_connect takes one argument: `struct sock *sk`
+    let _sk = ctx.arg(0);
+    let ret = ctx.ret(1) as i32;
+    if ret != 0 {
+        bpf_trace_printk(b"tcp_connect failed\0");
+    }
+}
+```
+ */
+pub mod prelude;
+
+/// Context object provided to `fentry` programs.
+#[derive(Clone)]
+pub struct FEntryContext {
+    pub ctx: *mut u64,
+}
+
+impl FEntryContext {
+    /// Returns the `n`th argument of the traced function.
+    ///
+    /// Arguments that are pointers or narrower integers are widened to `u64`
+    /// and should be cast back to their original type.
+    #[inline]
+    pub fn arg(&self, n: usize) -> u64 {
+        unsafe { *self.ctx.add(n) }
+    }
+}
+
+/// Context object provided to `fexit` programs.
+#[derive(Clone)]
+pub struct FExitContext {
+    pub ctx: *mut u64,
+}
+
+impl FExitContext {
+    /// Returns the `n`th argument of the traced function.
+    ///
+    /// The arguments hold the values that the function was called with.
+    #[inline]
+    pub fn arg(&self, n: usize) -> u64 {
+        unsafe { *self.ctx.add(n) }
+    }
+
+    /// Returns the return value of the traced function.
+    ///
+    /// The return value is stored after the arguments, so `nr_args` must be
+    /// the number of arguments the traced function takes.
+    #[inline]
+    pub fn ret(&self, nr_args: usize) -> u64 {
+        unsafe { *self.ctx.add(nr_args) }
+    }
+}
@@ -0,0 +1,23 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The trampoline Prelude
+//!
+//! The purpose of this module is to alleviate imports of the common `fentry`
+//! and `fexit` types by adding a glob import to the top of such programs:
+//!
+//! ```
+//! use redbpf_probes::trampoline::prelude::*;
+//! ```
+pub use crate::bindings::*;
+pub use crate::helpers::*;
+pub use crate::maps::*;
+#[cfg(feature = "ringbuf")]
+pub use crate::ringbuf::*;
+pub use crate::trampoline::*;
+pub use cty::*;
+pub use redbpf_macros::{fentry, fexit, map, printk, program};
@@ -16,5 +16,6 @@ pub use crate::bindings::*;
 pub use crate::helpers::*;
 pub use crate::maps::*;
 pub use crate::registers::*;
+pub use crate::usdt::*;
 pub use cty::*;
 pub use redbpf_macros::{map, printk, program, uprobe, uretprobe};
@@ -0,0 +1,119 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*!
+Arguments of USDT probes.
+
+USDT probes pass their arguments in registers, in memory relative to a
+register or as constants, and the locations differ from one probe site to
+another. User space parses the locations from the ELF file and hands them to
+the BPF program as [`UsdtSpec`](struct.UsdtSpec.html)s keyed by the address
+of the site, which is what `redbpf::UProbe::attach_usdt` returns.
+
+# Example
+```no_run
+#![no_std]
+#![no_main]
+use redbpf_probes::uprobe::prelude::*;
+
+#[map]
+static mut USDT_SPECS: HashMap<u64, UsdtSpec> = HashMap::with_max_entries(1024);
+
+#[uprobe]
+fn query_start(regs: Registers) {
+    let spec = match unsafe { USDT_SPECS.get(&regs.ip()) } {
+        Some(spec) => spec,
+        None => return,
+    };
+    let query = spec.arg(&regs, 0).unwrap_or(0) as *const u8;
+    // do something with the query
+}
+```
+*/
+
+use crate::helpers::bpf_probe_read;
+use crate::registers::Registers;
+
+/// The maximum number of arguments of a USDT probe
+pub const USDT_MAX_ARGS: usize = 12;
+
+/// The argument is the constant `val_off`
+pub const USDT_ARG_CONST: u32 = 0;
+/// The argument is the value of the register at `reg_off`
+pub const USDT_ARG_REG: u32 = 1;
+/// The argument is the value at the address of the register at `reg_off`
+/// plus `val_off`
+pub const USDT_ARG_REG_DEREF: u32 = 2;
+
+/// The location of an argument of a USDT probe
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UsdtArgSpec {
+    /// The constant value, or the offset added to the register to dereference
+    pub val_off: u64,
+    /// One of `USDT_ARG_CONST`, `USDT_ARG_REG` and `USDT_ARG_REG_DEREF`
+    pub arg_type: u32,
+    /// The offset of the register in `struct pt_regs`
+    pub reg_off: u16,
+    pub arg_signed: bool,
+    /// The number of bits to shift the 64-bit value by to get the argument,
+    /// i.e. 64 minus the size of the argument in bits
+    pub arg_bitshift: u8,
+}
+
+/// The locations of the arguments of a USDT probe site
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UsdtSpec {
+    pub args: [UsdtArgSpec; USDT_MAX_ARGS],
+    pub nr_args: u16,
+}
+
+impl UsdtSpec {
+    /// The number of arguments of the probe
+    #[inline]
+    pub fn arg_count(&self) -> usize {
+        self.nr_args as usize
+    }
+
+    /// Read the `n`-th argument of the probe, counting from 0
+    ///
+    /// The argument is sign-extended to 64 bits if it is signed and
+    /// zero-extended otherwise, so cast it to the type of the argument, e.g.
+    /// `as i32` or `as *const u8`.
+    #[inline]
+    pub fn arg(&self, regs: &Registers, n: usize) -> Option<i64> {
+        if n >= USDT_MAX_ARGS || n >= self.arg_count() {
+            return None;
+        }
+        let spec = &self.args[n];
+        let mut val = match spec.arg_type {
+            USDT_ARG_CONST => spec.val_off,
+            USDT_ARG_REG => unsafe { read_reg(regs, spec.reg_off)? },
+            USDT_ARG_REG_DEREF => unsafe {
+                let addr = read_reg(regs, spec.reg_off)?.wrapping_add(spec.val_off);
+                bpf_probe_read(addr as *const u64).ok()?
+            },
+            _ => return None,
+        };
+        // drop the bits above the size of the argument and extend its sign
+        val <<= spec.arg_bitshift;
+        if spec.arg_signed {
+            Some((val as i64) >> spec.arg_bitshift)
+        } else {
+            Some((val >> spec.arg_bitshift) as i64)
+        }
+    }
+}
+
+#[inline]
+unsafe fn read_reg(regs: &Registers, reg_off: u16) -> Option<u64> {
+    // the offset is not known to the verifier so the context can not be
+    // accessed directly
+    let reg = (regs.ctx as *const u8).add(reg_off as usize) as *const u64;
+    bpf_probe_read(reg).ok()
+}
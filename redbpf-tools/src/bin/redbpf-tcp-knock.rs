@@ -6,17 +6,31 @@
 // copied, modified, or distributed except according to those terms.
 use futures::stream::StreamExt;
 use getopts::Options;
-use redbpf::{load::Loader, xdp, HashMap};
+use redbpf::{
+    load::{Loader, PlainData},
+    xdp,
+    xdp::MapData,
+    HashMap,
+};
 use std::env;
 use std::net::Ipv4Addr;
 use std::process;
-use std::ptr;
 use tokio;
 use tokio::runtime;
 use tokio::signal;
 
 use probes::knock::{Connection, KnockAttempt, PortSequence, MAX_SEQ_LEN};
 
+// the events are defined in the probes crate, so they are wrapped to be
+// marked as plain data
+#[repr(transparent)]
+struct KnockAttemptEvent(KnockAttempt);
+unsafe impl PlainData for KnockAttemptEvent {}
+
+#[repr(transparent)]
+struct ConnectionEvent(Connection);
+unsafe impl PlainData for ConnectionEvent {}
+
 fn main() {
     if unsafe { libc::geteuid() } != 0 {
         println!("redbpf-tcp-knock: You must be root to use eBPF!");
@@ -53,46 +67,47 @@ fn main() {
         let seq_map = HashMap::<u8, PortSequence>::new(loader.map("sequence").unwrap()).unwrap();
         seq_map.set(0u8, sequence);
 
+        // process perf events sent by the XDP program
+        let mut knock_attempts = loader
+            .events_for::<MapData<KnockAttemptEvent>>("knock_attempts")
+            .expect("error taking knock_attempts events");
+        tokio::spawn(async move {
+            while let Some(event) = knock_attempts.next().await {
+                let knock = &event.data().0;
+                let seq = &knock.sequence;
+                println!(
+                    "Received knock from {} sequence {}",
+                    Ipv4Addr::from(knock.source_ip),
+                    seq.ports[..seq.len]
+                        .iter()
+                        .enumerate()
+                        .map(|(i, port)| {
+                            if i == seq.len - 1 {
+                                format!("*{}", port)
+                            } else {
+                                format!("{}", port)
+                            }
+                        })
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                )
+            }
+        });
+        let mut connections = loader
+            .events_for::<MapData<ConnectionEvent>>("connections")
+            .expect("error taking connections events");
         tokio::spawn(async move {
-            // process perf events sent by the XDP program
-            while let Some((name, events)) = loader.events.next().await {
-                for event in events {
-                    match name.as_str() {
-                        "knock_attempts" => {
-                            let knock = unsafe { ptr::read(event.as_ptr() as *const KnockAttempt) };
-                            let seq = &knock.sequence;
-                            println!(
-                                "Received knock from {} sequence {}",
-                                Ipv4Addr::from(knock.source_ip),
-                                seq.ports[..seq.len]
-                                    .iter()
-                                    .enumerate()
-                                    .map(|(i, port)| {
-                                        if i == seq.len - 1 {
-                                            format!("*{}", port)
-                                        } else {
-                                            format!("{}", port)
-                                        }
-                                    })
-                                    .collect::<Vec<String>>()
-                                    .join(" ")
-                            )
-                        }
-                        "connections" => {
-                            let conn = unsafe { ptr::read(event.as_ptr() as *const Connection) };
-                            println!(
-                                "{} access from {:?}",
-                                if conn.allowed == 1 {
-                                    "Allowed"
-                                } else {
-                                    "Blocked"
-                                },
-                                Ipv4Addr::from(conn.source_ip)
-                            );
-                        }
-                        _ => panic!("unexpected event"),
-                    }
-                }
+            while let Some(event) = connections.next().await {
+                let conn = &event.data().0;
+                println!(
+                    "{} access from {:?}",
+                    if conn.allowed == 1 {
+                        "Allowed"
+                    } else {
+                        "Blocked"
+                    },
+                    Ipv4Addr::from(conn.source_ip)
+                );
             }
         });
 
@@ -20,11 +20,13 @@ use std::slice;
 use tracing::{debug, error, warn};
 
 use libbpf_sys::{
-    btf_array, btf_enum, btf_header, btf_member, btf_param, btf_type, btf_var, btf_var_secinfo,
-    BTF_INT_BOOL, BTF_INT_CHAR, BTF_INT_SIGNED, BTF_KIND_ARRAY, BTF_KIND_CONST, BTF_KIND_DATASEC,
-    BTF_KIND_ENUM, BTF_KIND_FLOAT, BTF_KIND_FUNC, BTF_KIND_FUNC_PROTO, BTF_KIND_FWD, BTF_KIND_INT,
-    BTF_KIND_PTR, BTF_KIND_RESTRICT, BTF_KIND_STRUCT, BTF_KIND_TYPEDEF, BTF_KIND_UNION,
-    BTF_KIND_UNKN, BTF_KIND_VAR, BTF_KIND_VOLATILE, BTF_MAGIC, BTF_VAR_STATIC,
+    bpf_insn, btf_array, btf_enum, btf_header, btf_member, btf_param, btf_type, btf_var,
+    btf_var_secinfo, BPF_ALU, BPF_ALU64, BPF_CALL, BPF_DW, BPF_IMM, BPF_JMP, BPF_K, BPF_LD,
+    BPF_LDX, BPF_ST, BPF_STX, BTF_INT_BOOL, BTF_INT_CHAR, BTF_INT_SIGNED, BTF_KIND_ARRAY,
+    BTF_KIND_CONST, BTF_KIND_DATASEC, BTF_KIND_ENUM, BTF_KIND_FLOAT, BTF_KIND_FUNC,
+    BTF_KIND_FUNC_PROTO, BTF_KIND_FWD, BTF_KIND_INT, BTF_KIND_PTR, BTF_KIND_RESTRICT,
+    BTF_KIND_STRUCT, BTF_KIND_TYPEDEF, BTF_KIND_UNION, BTF_KIND_UNKN, BTF_KIND_VAR,
+    BTF_KIND_VOLATILE, BTF_MAGIC, BTF_VAR_STATIC,
 };
 
 use crate::error::{Error, Result};
@@ -347,6 +349,12 @@ impl BTF {
     }
 
     fn get_type_by_id(&self, type_id: u32) -> Option<&BtfType> {
+        // type ids are sequential unless types are filtered
+        if let Some((tid, type_)) = self.types.get((type_id as usize).wrapping_sub(1)) {
+            if *tid == type_id {
+                return Some(type_);
+            }
+        }
         self.types
             .iter()
             .find_map(|(tid, type_)| if &type_id == tid { Some(type_) } else { None })
@@ -436,6 +444,25 @@ impl BTF {
         }
     }
 
+    /// Get names, offsets and sizes of the variables of data section
+    /// `sec_name`, e.g. `.rodata`
+    pub(crate) fn get_datasec_vars(&self, sec_name: &str) -> Vec<(String, u32, u32)> {
+        let mut vars = vec![];
+        for (_, type_) in self.types.iter() {
+            if let BtfType::DataSection(comm, vsis) = type_ {
+                if comm.name_raw != sec_name {
+                    continue;
+                }
+                for vsi in vsis.iter() {
+                    if let Some(BtfType::Variable(var_comm, _)) = self.get_type_by_id(vsi.type_) {
+                        vars.push((var_comm.name_raw.clone(), vsi.offset, vsi.size));
+                    }
+                }
+            }
+        }
+        vars
+    }
+
     pub(crate) fn find_type_id(&self, type_name: &str, kind: BtfKind) -> Option<u32> {
         use BtfType::*;
         self.types.iter().find_map(|(type_id, type_)| match type_ {
@@ -1343,3 +1370,1065 @@ pub fn tc_legacy_fix_btf_section(elf_bytes: &[u8]) -> Result<Vec<u8>> {
     fixed.extend(&elf_bytes[end..]);
     Ok(fixed)
 }
+
+const BTF_EXT_SECTION_NAME: &str = ".BTF.ext";
+
+/// Instruction that libbpf also uses to poison instructions whose CO-RE
+/// relocation can not be resolved. The verifier rejects the program only if
+/// the poisoned instruction is reachable.
+const CORE_POISON_HELPER_ID: i32 = 0xbad2310;
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct btf_ext_header {
+    magic: u16,
+    version: u8,
+    flags: u8,
+    hdr_len: u32,
+    func_info_off: u32,
+    func_info_len: u32,
+    line_info_off: u32,
+    line_info_len: u32,
+    // these fields exist only if `hdr_len` is large enough
+    core_relo_off: u32,
+    core_relo_len: u32,
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct bpf_core_relo {
+    insn_off: u32,
+    type_id: u32,
+    access_str_off: u32,
+    kind: u32,
+}
+
+/// Kinds of CO-RE relocations
+///
+/// The values are defined by `enum bpf_core_relo_kind` of libbpf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CoreRelocKind {
+    FieldByteOffset,
+    FieldByteSize,
+    FieldExists,
+    FieldSigned,
+    FieldLShiftU64,
+    FieldRShiftU64,
+    TypeIdLocal,
+    TypeIdTarget,
+    TypeExists,
+    TypeSize,
+    EnumValueExists,
+    EnumValueValue,
+    /// A kind that is introduced after the ones above
+    Unknown(u32),
+}
+
+/// A CO-RE relocation record of a program read from `.BTF.ext` section
+#[derive(Debug, Clone)]
+pub(crate) struct CoreReloc {
+    /// byte offset of the instruction to patch in the program section
+    insn_off: u32,
+    /// local BTF type id of the root type
+    type_id: u32,
+    /// indices of the accessed fields. e.g. `0:1:2`
+    access: Vec<u32>,
+    kind: CoreRelocKind,
+}
+
+/// The result of resolving the access string of a field relocation against
+/// a BTF.
+struct FieldSpec {
+    bit_offset: u32,
+    bitfield_size: u32,
+    type_id: u32,
+}
+
+impl CoreRelocKind {
+    fn from_raw(kind: u32) -> Self {
+        use CoreRelocKind::*;
+        match kind {
+            0 => FieldByteOffset,
+            1 => FieldByteSize,
+            2 => FieldExists,
+            3 => FieldSigned,
+            4 => FieldLShiftU64,
+            5 => FieldRShiftU64,
+            6 => TypeIdLocal,
+            7 => TypeIdTarget,
+            8 => TypeExists,
+            9 => TypeSize,
+            10 => EnumValueExists,
+            11 => EnumValueValue,
+            _ => Unknown(kind),
+        }
+    }
+}
+
+/// Parse CO-RE relocation records of `.BTF.ext` section
+///
+/// `btf` is the BTF of `object` that holds the strings referred by the
+/// records. Returns the records grouped by the name of the program section
+/// they belong to. An empty map is returned if `object` has no CO-RE
+/// relocations.
+pub(crate) fn parse_core_relocations(
+    object: &Elf,
+    bytes: &[u8],
+    btf: &BTF,
+) -> Result<RSHashMap<String, Vec<CoreReloc>>> {
+    let shdr = if let Some(shdr) = get_section_header_by_name(object, BTF_EXT_SECTION_NAME) {
+        shdr
+    } else {
+        return Ok(RSHashMap::new());
+    };
+    let ext_bytes = (shdr.sh_offset as usize)
+        .checked_add(shdr.sh_size as usize)
+        .and_then(|end| bytes.get(shdr.sh_offset as usize..end))
+        .ok_or_else(|| Error::BTF(".BTF.ext section exceeds ELF file".to_string()))?;
+    parse_core_relocations_data(ext_bytes, btf)
+}
+
+/// Parse CO-RE relocation records of the data of `.BTF.ext` section
+fn parse_core_relocations_data(
+    ext_bytes: &[u8],
+    btf: &BTF,
+) -> Result<RSHashMap<String, Vec<CoreReloc>>> {
+    let mut relocs = RSHashMap::new();
+    // the size of the header before CO-RE relocation fields are added
+    let min_hdr_len = mem::size_of::<btf_ext_header>() - 2 * mem::size_of::<u32>();
+    if ext_bytes.len() < min_hdr_len {
+        return Err(Error::BTF(
+            ".BTF.ext section data size is too small".to_string(),
+        ));
+    }
+    let mut hdr = btf_ext_header::default();
+    unsafe {
+        ptr::copy_nonoverlapping(
+            ext_bytes.as_ptr(),
+            &mut hdr as *mut _ as *mut u8,
+            ext_bytes.len().min(mem::size_of::<btf_ext_header>()),
+        );
+    }
+    if hdr.magic != BTF_MAGIC as u16 {
+        return Err(Error::BTF(
+            "illegal magic. not a valid .BTF.ext section".to_string(),
+        ));
+    }
+    if (hdr.hdr_len as usize) < mem::size_of::<btf_ext_header>() || hdr.core_relo_len == 0 {
+        return Ok(relocs);
+    }
+
+    let data = hdr
+        .hdr_len
+        .checked_add(hdr.core_relo_off)
+        .and_then(|start| Some((start, start.checked_add(hdr.core_relo_len)?)))
+        .and_then(|(start, end)| ext_bytes.get(start as usize..end as usize))
+        .ok_or_else(|| Error::BTF("CO-RE relocations exceed .BTF.ext section".to_string()))?;
+    let read_u32 = |off: usize| -> Result<u32> {
+        if off + mem::size_of::<u32>() > data.len() {
+            return Err(Error::BTF("truncated CO-RE relocation records".to_string()));
+        }
+        Ok(unsafe { ptr::read_unaligned(data.as_ptr().add(off) as *const u32) })
+    };
+    let rec_size = read_u32(0)? as usize;
+    if rec_size < mem::size_of::<bpf_core_relo>() {
+        return Err(Error::BTF(format!(
+            "invalid CO-RE relocation record size: {}",
+            rec_size
+        )));
+    }
+    let mut off = mem::size_of::<u32>();
+    while off < data.len() {
+        let sec_name = get_type_name(&btf.raw_str_enc, read_u32(off)?)?;
+        let num_info = read_u32(off + mem::size_of::<u32>())? as usize;
+        off += 2 * mem::size_of::<u32>();
+        let end = num_info
+            .checked_mul(rec_size)
+            .and_then(|len| len.checked_add(off));
+        if !matches!(end, Some(end) if end <= data.len()) {
+            return Err(Error::BTF("truncated CO-RE relocation records".to_string()));
+        }
+        let sec_relocs: &mut Vec<CoreReloc> = relocs.entry(sec_name).or_default();
+        for _ in 0..num_info {
+            let relo =
+                unsafe { ptr::read_unaligned(data.as_ptr().add(off) as *const bpf_core_relo) };
+            off += rec_size;
+            let kind = CoreRelocKind::from_raw(relo.kind);
+            let access_str = get_type_name(&btf.raw_str_enc, relo.access_str_off)?;
+            let access = parse_access_str(&access_str).ok_or_else(|| {
+                Error::BTF(format!("invalid CO-RE access string: {}", access_str))
+            })?;
+            sec_relocs.push(CoreReloc {
+                insn_off: relo.insn_off,
+                type_id: relo.type_id,
+                access,
+                kind,
+            });
+        }
+    }
+    Ok(relocs)
+}
+
+impl BTF {
+    /// Apply CO-RE relocations to the instructions of a program
+    ///
+    /// `self` is the BTF of the BPF program and `target` is the BTF of the
+    /// running kernel. Field offsets, field existence and type sizes recorded
+    /// in the instructions at compile time are replaced with the ones of the
+    /// running kernel. Instructions whose relocation can not be resolved are
+    /// poisoned instead, so the verifier rejects the program only if they are
+    /// reachable.
+    pub(crate) fn apply_core_relocations(
+        &self,
+        target: &BTF,
+        relocs: &[CoreReloc],
+        code: &mut [bpf_insn],
+    ) -> Result<()> {
+        for reloc in relocs {
+            let insn_idx = reloc.insn_off as usize / mem::size_of::<bpf_insn>();
+            if insn_idx >= code.len() {
+                error!("CO-RE relocation offset is out of program: {:?}", reloc);
+                return Err(Error::Reloc);
+            }
+            let vals = self
+                .core_reloc_value(self, reloc.type_id, reloc)
+                .and_then(|local_val| {
+                    Ok(self
+                        .resolve_core_reloc(target, reloc)?
+                        .map(|target_val| (local_val, target_val)))
+                });
+            match vals {
+                Ok(Some((local_val, target_val))) => {
+                    debug!(
+                        "CO-RE relocation {:?}: {} => {}",
+                        reloc, local_val, target_val
+                    );
+                    patch_insn(code, insn_idx, local_val, target_val)?;
+                }
+                Ok(None) => {
+                    warn!(
+                        "CO-RE relocation {:?} can not be resolved. poison instruction #{}",
+                        reloc, insn_idx
+                    );
+                    poison_insn(&mut code[insn_idx]);
+                }
+                Err(e) => {
+                    warn!(
+                        "error on CO-RE relocation {:?}: {:?}. poison instruction #{}",
+                        reloc, e, insn_idx
+                    );
+                    poison_insn(&mut code[insn_idx]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the value of `reloc` in `target` BTF
+    ///
+    /// Returns `None` if `reloc` requires an entity that `target` does not
+    /// have.
+    fn resolve_core_reloc(&self, target: &BTF, reloc: &CoreReloc) -> Result<Option<u64>> {
+        use BtfType::*;
+        use CoreRelocKind::*;
+        if reloc.kind == TypeIdLocal {
+            return Ok(Some(u64::from(reloc.type_id)));
+        }
+        let (_, local_type) = self
+            .skip_mods_and_typedefs(reloc.type_id)
+            .ok_or_else(|| Error::BTF(format!("local type id {} not found", reloc.type_id)))?;
+        let local_name = match local_type {
+            Structure(comm, _)
+            | Union(comm, _)
+            | Enumeration(comm, _)
+            | Integer(comm, _)
+            | FloatingPoint(comm)
+            | Forward(comm) => essential_name(&comm.name_raw).to_string(),
+            _ => String::new(),
+        };
+        if local_name.is_empty() {
+            error!("CO-RE relocation of anonymous type: {:?}", reloc);
+            return Err(Error::BTF(
+                "CO-RE relocation of anonymous type is not supported".to_string(),
+            ));
+        }
+        let local_kind = local_type.kind();
+        let mut resolved: Option<u64> = None;
+        for (cand_id, cand) in target.types.iter() {
+            if cand.kind() != local_kind
+                || cand.name().map(essential_name) != Some(local_name.as_str())
+            {
+                continue;
+            }
+            let val = match self.core_reloc_value(target, *cand_id, reloc) {
+                Ok(val) => val,
+                Err(_) => continue,
+            };
+            match resolved {
+                Some(prev) if prev != val => {
+                    error!(
+                        "CO-RE relocation {:?} is ambiguous: {} != {}",
+                        reloc, prev, val
+                    );
+                    return Err(Error::BTF(format!(
+                        "ambiguous CO-RE relocation of {}",
+                        local_name
+                    )));
+                }
+                _ => resolved = Some(val),
+            }
+        }
+        match (resolved, reloc.kind) {
+            (Some(val), _) => Ok(Some(val)),
+            (None, FieldExists) | (None, TypeExists) | (None, EnumValueExists) => Ok(Some(0)),
+            (None, _) => Ok(None),
+        }
+    }
+
+    /// Compute the value of `reloc` whose root type is `type_id` of `btf`.
+    ///
+    /// `self` is the BTF the access string of `reloc` is based on.
+    fn core_reloc_value(&self, btf: &BTF, type_id: u32, reloc: &CoreReloc) -> Result<u64> {
+        use BtfType::*;
+        use CoreRelocKind::*;
+        match reloc.kind {
+            TypeIdLocal => return Ok(u64::from(reloc.type_id)),
+            TypeIdTarget => return Ok(u64::from(type_id)),
+            TypeExists => return Ok(1),
+            TypeSize => {
+                return btf
+                    .resolve_size(type_id)
+                    .map(u64::from)
+                    .ok_or_else(|| Error::BTF("size of type not found".to_string()))
+            }
+            EnumValueExists => return self.enum_value(btf, type_id, reloc).map(|_| 1),
+            EnumValueValue => return self.enum_value(btf, type_id, reloc),
+            Unknown(kind) => {
+                return Err(Error::BTF(format!(
+                    "unknown CO-RE relocation kind: {}",
+                    kind
+                )))
+            }
+            _ => {}
+        }
+
+        let spec = self.resolve_field(btf, type_id, reloc)?;
+        if reloc.kind == FieldExists {
+            return Ok(1);
+        }
+        let (_, field_type) = btf
+            .skip_mods_and_typedefs(spec.type_id)
+            .ok_or_else(|| Error::BTF("type of field not found".to_string()))?;
+        let mut byte_sz = btf
+            .resolve_size(spec.type_id)
+            .filter(|sz| *sz > 0)
+            .ok_or_else(|| Error::BTF("size of field not found".to_string()))?;
+        let bit_off = spec.bit_offset;
+        let (bit_sz, byte_off) = if spec.bitfield_size == 0 {
+            (byte_sz * 8, bit_off / 8)
+        } else {
+            let bit_sz = spec.bitfield_size;
+            let mut byte_off = bit_off / 8 / byte_sz * byte_sz;
+            // find the smallest integer that the bitfield can be loaded with
+            while bit_off + bit_sz - byte_off * 8 > byte_sz * 8 {
+                if byte_sz >= 8 {
+                    return Err(Error::BTF("bitfield is too large to load".to_string()));
+                }
+                byte_sz *= 2;
+                byte_off = bit_off / 8 / byte_sz * byte_sz;
+            }
+            (bit_sz, byte_off)
+        };
+        Ok(u64::from(match reloc.kind {
+            FieldByteOffset => byte_off,
+            FieldByteSize => byte_sz,
+            FieldSigned => match field_type {
+                Enumeration(..) => 1,
+                Integer(_, int) => (btf_int_encoding(*int) & BTF_INT_SIGNED != 0) as u32,
+                _ => 0,
+            },
+            FieldLShiftU64 => {
+                if cfg!(target_endian = "little") {
+                    64 - (bit_off + bit_sz - byte_off * 8)
+                } else {
+                    (8 - byte_sz) * 8 + (bit_off - byte_off * 8)
+                }
+            }
+            FieldRShiftU64 => 64 - bit_sz,
+            _ => unreachable!(),
+        }))
+    }
+
+    /// Find the value of the enumerator of `reloc` in the enum of `type_id`
+    /// of `btf`
+    ///
+    /// Enumerators are matched by name.
+    fn enum_value(&self, btf: &BTF, type_id: u32, reloc: &CoreReloc) -> Result<u64> {
+        use BtfType::*;
+        let not_found = || Error::BTF("enumerator not found".to_string());
+        let local_enu = match self.skip_mods_and_typedefs(reloc.type_id) {
+            Some((_, Enumeration(_, enus))) => {
+                enus.get(*reloc.access.first().ok_or_else(not_found)? as usize)
+            }
+            _ => None,
+        }
+        .ok_or_else(not_found)?;
+        let local_name = get_type_name(&self.raw_str_enc, local_enu.name_off)?;
+        let enus = match btf.skip_mods_and_typedefs(type_id) {
+            Some((_, Enumeration(_, enus))) => enus,
+            _ => return Err(not_found()),
+        };
+        for enu in enus.iter() {
+            let name = get_type_name(&btf.raw_str_enc, enu.name_off)?;
+            if essential_name(&name) == essential_name(&local_name) {
+                return Ok(enu.val as i64 as u64);
+            }
+        }
+        Err(not_found())
+    }
+
+    /// Follow the access indices of `reloc` in `btf` starting from `type_id`
+    ///
+    /// The access indices refer to the types of `self`. Members of composite
+    /// types are matched by name, so the field layout of `btf` can differ
+    /// from the one of `self`.
+    fn resolve_field(&self, btf: &BTF, type_id: u32, reloc: &CoreReloc) -> Result<FieldSpec> {
+        use BtfType::*;
+        let not_found = || Error::BTF("field not found".to_string());
+        let (first, rest) = reloc.access.split_first().ok_or_else(not_found)?;
+        let root_size = btf.resolve_size(type_id).ok_or_else(not_found)?;
+        let mut spec = FieldSpec {
+            bit_offset: first * root_size * 8,
+            bitfield_size: 0,
+            type_id,
+        };
+        let mut local = self
+            .skip_mods_and_typedefs(reloc.type_id)
+            .ok_or_else(not_found)?
+            .1;
+        for idx in rest {
+            let (target_id, target) = btf
+                .skip_mods_and_typedefs(spec.type_id)
+                .ok_or_else(not_found)?;
+            let local_next = match (local, target) {
+                (Structure(_, membs), Structure(..) | Union(..))
+                | (Union(_, membs), Structure(..) | Union(..)) => {
+                    let memb = membs.get(*idx as usize).ok_or_else(not_found)?;
+                    let (bit_offset, bitfield_size, memb_type_id) = btf
+                        .find_member(target_id, &memb.name)
+                        .ok_or_else(not_found)?;
+                    spec.bit_offset += bit_offset;
+                    spec.bitfield_size = bitfield_size;
+                    spec.type_id = memb_type_id;
+                    memb.type_id()
+                }
+                (Array(_, local_arr), Array(_, target_arr)) => {
+                    let elem_size = btf.resolve_size(target_arr.type_).ok_or_else(not_found)?;
+                    spec.bit_offset += idx * elem_size * 8;
+                    spec.bitfield_size = 0;
+                    spec.type_id = target_arr.type_;
+                    local_arr.type_
+                }
+                _ => return Err(not_found()),
+            };
+            local = self
+                .skip_mods_and_typedefs(local_next)
+                .ok_or_else(not_found)?
+                .1;
+        }
+        Ok(spec)
+    }
+
+    /// Find a member named `name` of a struct or union of `type_id`
+    ///
+    /// Members of anonymous struct or union members are searched too. Returns
+    /// bit offset, bitfield size and type id of the member.
+    fn find_member(&self, type_id: u32, name: &str) -> Option<(u32, u32, u32)> {
+        use BtfType::*;
+        let (comm, membs) = match self.skip_mods_and_typedefs(type_id)?.1 {
+            Structure(comm, membs) | Union(comm, membs) => (comm, membs),
+            _ => return None,
+        };
+        let bitfield_size = |memb: &BtfMember| {
+            if comm.kind_flag() {
+                memb.bitfield_size()
+            } else {
+                0
+            }
+        };
+        let bit_offset = |memb: &BtfMember| {
+            if comm.kind_flag() {
+                memb.bit_offset()
+            } else {
+                memb.member.offset
+            }
+        };
+        for memb in membs.iter() {
+            if memb.name == name {
+                return Some((bit_offset(memb), bitfield_size(memb), memb.type_id()));
+            }
+        }
+        for memb in membs.iter().filter(|memb| memb.name.is_empty()) {
+            if let Some((off, bsz, tid)) = self.find_member(memb.type_id(), name) {
+                return Some((bit_offset(memb) + off, bsz, tid));
+            }
+        }
+        None
+    }
+
+    fn skip_mods_and_typedefs(&self, mut type_id: u32) -> Option<(u32, &BtfType)> {
+        use BtfType::*;
+        loop {
+            let type_ = self.get_type_by_id(type_id)?;
+            match type_ {
+                TypeDef(comm) | Volatile(comm) | Constant(comm) | Restrict(comm) => {
+                    type_id = comm.type_id();
+                }
+                _ => return Some((type_id, type_)),
+            }
+        }
+    }
+
+    fn resolve_size(&self, type_id: u32) -> Option<u32> {
+        use BtfType::*;
+        let (_, type_) = self.skip_mods_and_typedefs(type_id)?;
+        match type_ {
+            // BPF is a 64-bit architecture
+            Pointer(_) => Some(mem::size_of::<u64>() as u32),
+            Array(_, arr) => Some(arr.nelems * self.resolve_size(arr.type_)?),
+            _ => type_.size(),
+        }
+    }
+}
+
+impl BtfType {
+    fn kind(&self) -> BtfKind {
+        use BtfType::*;
+        match self {
+            Integer(comm, _)
+            | Pointer(comm)
+            | Array(comm, _)
+            | Structure(comm, _)
+            | Union(comm, _)
+            | Enumeration(comm, _)
+            | Forward(comm)
+            | TypeDef(comm)
+            | Volatile(comm)
+            | Constant(comm)
+            | Restrict(comm)
+            | Function(comm)
+            | FunctionProtocol(comm, _)
+            | Variable(comm, _)
+            | DataSection(comm, _)
+            | FloatingPoint(comm) => comm.kind(),
+        }
+    }
+
+    fn name(&self) -> Option<&str> {
+        use BtfType::*;
+        match self {
+            Structure(comm, _)
+            | Union(comm, _)
+            | Enumeration(comm, _)
+            | Integer(comm, _)
+            | FloatingPoint(comm)
+            | Forward(comm)
+            | TypeDef(comm) => Some(&comm.name_raw),
+            _ => None,
+        }
+    }
+}
+
+/// Patch the instruction at `insn_idx` with the relocated value
+///
+/// `orig` is the value computed with the local BTF. It should equal to the
+/// value that the compiler has written in the instruction.
+fn patch_insn(code: &mut [bpf_insn], insn_idx: usize, orig: u64, new: u64) -> Result<()> {
+    let opcode = code[insn_idx].code as u32;
+    match opcode & 0x07 {
+        BPF_ALU | BPF_ALU64 => {
+            if opcode & 0x08 != BPF_K {
+                error!("CO-RE relocation of ALU instruction without immediate value");
+                return Err(Error::Reloc);
+            }
+            if code[insn_idx].imm as i64 as u64 != orig {
+                warn!(
+                    "unexpected immediate value of instruction #{}: {} != {}",
+                    insn_idx, code[insn_idx].imm, orig
+                );
+            }
+            code[insn_idx].imm = new as i32;
+        }
+        BPF_LDX | BPF_ST | BPF_STX => {
+            if new > i16::MAX as u64 {
+                error!("relocated offset {} is too large", new);
+                return Err(Error::Reloc);
+            }
+            code[insn_idx].off = new as i16;
+        }
+        BPF_LD if opcode == BPF_LD | BPF_IMM | BPF_DW => {
+            if insn_idx + 1 >= code.len() {
+                error!("incomplete ld_imm64 instruction");
+                return Err(Error::Reloc);
+            }
+            code[insn_idx].imm = new as u32 as i32;
+            code[insn_idx + 1].imm = (new >> 32) as u32 as i32;
+        }
+        _ => {
+            error!(
+                "CO-RE relocation of unsupported instruction #{}: code={:#x}",
+                insn_idx, opcode
+            );
+            return Err(Error::Reloc);
+        }
+    }
+    Ok(())
+}
+
+fn poison_insn(insn: &mut bpf_insn) {
+    insn.code = (BPF_JMP | BPF_CALL) as u8;
+    insn.set_dst_reg(0);
+    insn.set_src_reg(0);
+    insn.off = 0;
+    insn.imm = CORE_POISON_HELPER_ID;
+}
+
+/// Strip the flavor suffix that starts with `___` from a type name
+///
+/// e.g., `task_struct___5_10` and `task_struct` are the same type.
+fn essential_name(name: &str) -> &str {
+    match name.find("___") {
+        Some(idx) => &name[..idx],
+        None => name,
+    }
+}
+
+fn parse_access_str(access_str: &str) -> Option<Vec<u32>> {
+    access_str
+        .split(':')
+        .map(|idx| idx.parse::<u32>().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libbpf_sys::{BPF_MEM, BPF_W, BPF_X};
+
+    /// Raw BTF data that is built type by type
+    struct RawBtf {
+        types: Vec<u8>,
+        strs: Vec<u8>,
+    }
+
+    impl RawBtf {
+        fn new() -> Self {
+            RawBtf {
+                types: vec![],
+                strs: vec![0],
+            }
+        }
+
+        fn add_str(&mut self, s: &str) -> u32 {
+            if s.is_empty() {
+                return 0;
+            }
+            let off = self.strs.len() as u32;
+            self.strs.extend(s.as_bytes());
+            self.strs.push(0);
+            off
+        }
+
+        fn push(&mut self, vals: &[u32]) {
+            for val in vals {
+                self.types.extend(&val.to_le_bytes());
+            }
+        }
+
+        fn add_type(&mut self, name: &str, kind: u32, vlen: u32, kflag: bool, size_or_type: u32) {
+            let name_off = self.add_str(name);
+            let info = (kflag as u32) << 31 | kind << 24 | vlen;
+            self.push(&[name_off, info, size_or_type]);
+        }
+
+        fn int(&mut self, name: &str, size: u32, signed: bool) {
+            self.add_type(name, BTF_KIND_INT, 0, false, size);
+            let encoding = if signed { BTF_INT_SIGNED } else { 0 };
+            self.push(&[encoding << 24 | size * 8]);
+        }
+
+        fn array(&mut self, elem_type: u32, index_type: u32, nelems: u32) {
+            self.add_type("", BTF_KIND_ARRAY, 0, false, 0);
+            self.push(&[elem_type, index_type, nelems]);
+        }
+
+        /// members are tuples of name, type id, bit offset and bitfield size
+        fn composite(&mut self, kind: u32, name: &str, size: u32, membs: &[(&str, u32, u32, u32)]) {
+            let kflag = membs
+                .iter()
+                .any(|(_, _, _, bitfield_size)| *bitfield_size != 0);
+            self.add_type(name, kind, membs.len() as u32, kflag, size);
+            for (name, type_id, bit_offset, bitfield_size) in membs {
+                let name_off = self.add_str(name);
+                self.push(&[name_off, *type_id, bitfield_size << 24 | bit_offset]);
+            }
+        }
+
+        fn enumeration(&mut self, name: &str, enus: &[(&str, i32)]) {
+            self.add_type(name, BTF_KIND_ENUM, enus.len() as u32, false, 4);
+            for (name, val) in enus {
+                let name_off = self.add_str(name);
+                self.push(&[name_off, *val as u32]);
+            }
+        }
+
+        fn build(&self) -> BTF {
+            let hdr_len = mem::size_of::<btf_header>() as u32;
+            let mut bytes = vec![];
+            bytes.extend(&(BTF_MAGIC as u16).to_le_bytes());
+            bytes.extend(&[1, 0]);
+            for val in &[
+                hdr_len,
+                0,
+                self.types.len() as u32,
+                self.types.len() as u32,
+                self.strs.len() as u32,
+            ] {
+                bytes.extend(&val.to_le_bytes());
+            }
+            bytes.extend(&self.types);
+            bytes.extend(&self.strs);
+            BTF::parse_raw(&bytes).unwrap()
+        }
+    }
+
+    /// BTF of a BPF program
+    fn local_btf() -> BTF {
+        let mut raw = RawBtf::new();
+        raw.int("int", 4, true); // 1
+        raw.int("unsigned int", 4, false); // 2
+        raw.composite(
+            BTF_KIND_STRUCT,
+            "inner",
+            8,
+            &[("a", 1, 0, 0), ("b", 1, 32, 0)],
+        ); // 3
+        raw.array(1, 2, 4); // 4
+        raw.composite(BTF_KIND_UNION, "", 4, &[("u1", 1, 0, 0), ("u2", 2, 0, 0)]); // 5
+        raw.composite(
+            BTF_KIND_STRUCT,
+            "task",
+            40,
+            &[
+                ("pid", 1, 0, 0),
+                ("in", 3, 32, 0),
+                ("arr", 4, 96, 0),
+                ("", 5, 224, 0),
+                ("flags", 2, 256, 3),
+                ("mode", 2, 259, 5),
+                ("gone", 1, 288, 0),
+            ],
+        ); // 6
+        raw.enumeration("state", &[("RUNNING", 0), ("STOPPED", 4)]); // 7
+        raw.build()
+    }
+
+    /// BTF of a kernel that has a different layout of the types
+    fn target_btf() -> BTF {
+        let mut raw = RawBtf::new();
+        raw.int("unsigned int", 4, false); // 1
+        raw.int("int", 4, true); // 2
+        raw.enumeration("state", &[("STOPPED", 8), ("RUNNING", 1)]); // 3
+        raw.composite(
+            BTF_KIND_STRUCT,
+            "inner",
+            16,
+            &[
+                ("pad", 1, 0, 0),
+                ("pad2", 1, 32, 0),
+                ("b", 2, 64, 0),
+                ("a", 2, 96, 0),
+            ],
+        ); // 4
+        raw.array(2, 1, 8); // 5
+        raw.composite(BTF_KIND_UNION, "", 4, &[("u2", 1, 0, 0), ("u1", 2, 0, 0)]); // 6
+        raw.composite(
+            BTF_KIND_STRUCT,
+            "task___new",
+            68,
+            &[
+                ("flags", 1, 0, 3),
+                ("mode", 1, 30, 5),
+                ("pid", 2, 64, 0),
+                ("extra", 1, 96, 0),
+                ("in", 4, 128, 0),
+                ("arr", 5, 256, 0),
+                ("", 6, 512, 0),
+            ],
+        ); // 7
+        raw.build()
+    }
+
+    fn reloc(type_id: u32, access_str: &str, kind: CoreRelocKind) -> CoreReloc {
+        CoreReloc {
+            insn_off: 0,
+            type_id,
+            access: parse_access_str(access_str).unwrap(),
+            kind,
+        }
+    }
+
+    fn insn(code: u32, off: i16, imm: i32) -> bpf_insn {
+        let mut insn: bpf_insn = unsafe { mem::zeroed() };
+        insn.code = code as u8;
+        insn.off = off;
+        insn.imm = imm;
+        insn
+    }
+
+    #[test]
+    fn test_resolve_field() {
+        let local = local_btf();
+        let target = target_btf();
+        let bit_offset = |btf: &BTF, type_id: u32, access_str: &str| {
+            local
+                .resolve_field(
+                    btf,
+                    type_id,
+                    &reloc(6, access_str, CoreRelocKind::FieldByteOffset),
+                )
+                .map(|spec| (spec.bit_offset, spec.bitfield_size))
+                .ok()
+        };
+        // task.pid
+        assert_eq!(bit_offset(&local, 6, "0:0"), Some((0, 0)));
+        assert_eq!(bit_offset(&target, 7, "0:0"), Some((64, 0)));
+        // task.in.a of a nested struct that moved
+        assert_eq!(bit_offset(&local, 6, "0:1:0"), Some((32, 0)));
+        assert_eq!(bit_offset(&target, 7, "0:1:0"), Some((224, 0)));
+        // task.arr[3]
+        assert_eq!(bit_offset(&local, 6, "0:2:3"), Some((192, 0)));
+        assert_eq!(bit_offset(&target, 7, "0:2:3"), Some((352, 0)));
+        // task.u2 of an anonymous union member
+        assert_eq!(bit_offset(&local, 6, "0:3:1"), Some((224, 0)));
+        assert_eq!(bit_offset(&target, 7, "0:3:1"), Some((512, 0)));
+        // task.mode bitfield
+        assert_eq!(bit_offset(&local, 6, "0:5"), Some((259, 5)));
+        assert_eq!(bit_offset(&target, 7, "0:5"), Some((30, 5)));
+        // task[1].pid
+        assert_eq!(bit_offset(&target, 7, "1:0"), Some((544 + 64, 0)));
+        // task.gone does not exist in the target
+        assert_eq!(bit_offset(&target, 7, "0:6"), None);
+        // index out of the members
+        assert_eq!(bit_offset(&local, 6, "0:7"), None);
+    }
+
+    #[test]
+    fn test_core_reloc_value() {
+        use CoreRelocKind::*;
+        let local = local_btf();
+        let target = target_btf();
+        let value = |access_str: &str, kind: CoreRelocKind| {
+            let reloc = reloc(6, access_str, kind);
+            (
+                local.core_reloc_value(&local, 6, &reloc).ok(),
+                local.resolve_core_reloc(&target, &reloc).unwrap(),
+            )
+        };
+        assert_eq!(value("0:0", FieldByteOffset), (Some(0), Some(8)));
+        assert_eq!(value("0:1:1", FieldByteOffset), (Some(8), Some(24)));
+        assert_eq!(value("0:0", FieldByteSize), (Some(4), Some(4)));
+        assert_eq!(value("0:0", FieldSigned), (Some(1), Some(1)));
+        assert_eq!(value("0:4", FieldSigned), (Some(0), Some(0)));
+        assert_eq!(value("0:6", FieldExists), (Some(1), Some(0)));
+        assert_eq!(value("0:6", FieldByteOffset), (Some(36), None));
+        assert_eq!(value("0", TypeSize), (Some(40), Some(68)));
+        assert_eq!(value("0", TypeExists), (Some(1), Some(1)));
+        assert_eq!(value("0", TypeIdLocal), (Some(6), Some(6)));
+        assert_eq!(value("0", TypeIdTarget), (Some(6), Some(7)));
+        assert_eq!(value("0", Unknown(42)), (None, None));
+
+        // task.flags is loaded with the 32-bit integer that holds it
+        assert_eq!(value("0:4", FieldByteOffset), (Some(32), Some(0)));
+        assert_eq!(value("0:4", FieldByteSize), (Some(4), Some(4)));
+        assert_eq!(value("0:4", FieldRShiftU64), (Some(61), Some(61)));
+        // task.mode straddles 32-bit boundary in the target
+        assert_eq!(value("0:5", FieldByteOffset), (Some(32), Some(0)));
+        assert_eq!(value("0:5", FieldByteSize), (Some(4), Some(8)));
+        assert_eq!(value("0:5", FieldRShiftU64), (Some(59), Some(59)));
+        if cfg!(target_endian = "little") {
+            assert_eq!(value("0:4", FieldLShiftU64), (Some(61), Some(61)));
+            assert_eq!(value("0:5", FieldLShiftU64), (Some(56), Some(29)));
+        }
+
+        let value = |access_str: &str, kind: CoreRelocKind| {
+            let reloc = reloc(7, access_str, kind);
+            (
+                local.core_reloc_value(&local, 7, &reloc).ok(),
+                local.resolve_core_reloc(&target, &reloc).unwrap(),
+            )
+        };
+        assert_eq!(value("1", EnumValueValue), (Some(4), Some(8)));
+        assert_eq!(value("0", EnumValueExists), (Some(1), Some(1)));
+        assert_eq!(value("2", EnumValueValue), (None, None));
+    }
+
+    #[test]
+    fn test_apply_core_relocations() {
+        use CoreRelocKind::*;
+        let local = local_btf();
+        let target = target_btf();
+        let ldx = BPF_LDX | BPF_MEM | BPF_W;
+        let add = BPF_ALU64 | BPF_K;
+        let mut code = vec![
+            insn(ldx, 0, 0),
+            insn(add, 0, 4),
+            insn(BPF_LD | BPF_IMM | BPF_DW, 0, 4),
+            insn(0, 0, 0),
+            insn(ldx, 36, 0),
+            insn(add, 0, 1),
+            insn(add, 0, 40),
+            insn(add, 0, 0),
+        ];
+        let insn_off = |idx: usize| (idx * mem::size_of::<bpf_insn>()) as u32;
+        let mut relocs = vec![
+            reloc(6, "0:0", FieldByteOffset),
+            reloc(6, "0:1:0", FieldByteOffset),
+            reloc(7, "1", EnumValueValue),
+            reloc(6, "0:6", FieldByteOffset),
+            reloc(6, "0:6", FieldExists),
+            reloc(6, "0", TypeSize),
+            reloc(6, "0", Unknown(42)),
+        ];
+        for (reloc, idx) in relocs.iter_mut().zip(&[0, 1, 2, 4, 5, 6, 7]) {
+            reloc.insn_off = insn_off(*idx);
+        }
+        local
+            .apply_core_relocations(&target, &relocs, &mut code)
+            .unwrap();
+        assert_eq!(code[0].off, 8);
+        assert_eq!(code[1].imm, 28);
+        assert_eq!((code[2].imm, code[3].imm), (8, 0));
+        assert_eq!(code[5].imm, 0);
+        assert_eq!(code[6].imm, 68);
+        for idx in &[4, 7] {
+            assert_eq!(code[*idx].code as u32, BPF_JMP | BPF_CALL);
+            assert_eq!(code[*idx].imm, CORE_POISON_HELPER_ID);
+        }
+
+        let mut relocs = vec![reloc(6, "0:0", FieldByteOffset)];
+        relocs[0].insn_off = insn_off(code.len());
+        assert!(local
+            .apply_core_relocations(&target, &relocs, &mut code)
+            .is_err());
+    }
+
+    #[test]
+    fn test_patch_insn() {
+        let ldx = BPF_LDX | BPF_MEM | BPF_W;
+        let st = BPF_ST | BPF_MEM | BPF_W;
+        let mut code = vec![
+            insn(BPF_ALU64 | BPF_K, 0, 4),
+            insn(BPF_ALU | BPF_K, 0, 4),
+            insn(BPF_ALU64 | BPF_X, 0, 0),
+            insn(ldx, 4, 0),
+            insn(st, 4, 7),
+            insn(BPF_LD | BPF_IMM | BPF_DW, 0, 4),
+            insn(0, 0, 0),
+            insn(BPF_JMP | BPF_CALL, 0, 1),
+            insn(BPF_LD | BPF_IMM | BPF_DW, 0, 4),
+        ];
+        patch_insn(&mut code, 0, 4, 28).unwrap();
+        assert_eq!(code[0].imm, 28);
+        patch_insn(&mut code, 1, 4, 0).unwrap();
+        assert_eq!(code[1].imm, 0);
+        assert!(patch_insn(&mut code, 2, 4, 28).is_err());
+
+        patch_insn(&mut code, 3, 4, 32).unwrap();
+        assert_eq!(code[3].off, 32);
+        patch_insn(&mut code, 4, 4, i16::MAX as u64).unwrap();
+        assert_eq!((code[4].off, code[4].imm), (i16::MAX, 7));
+        assert!(patch_insn(&mut code, 3, 4, i16::MAX as u64 + 1).is_err());
+        assert_eq!(code[3].off, 32);
+
+        patch_insn(&mut code, 5, 4, 0x1_0000_0002).unwrap();
+        assert_eq!((code[5].imm, code[6].imm), (2, 1));
+        assert!(patch_insn(&mut code, 7, 1, 2).is_err());
+        // the second half of ld_imm64 is missing
+        assert!(patch_insn(&mut code, 8, 4, 8).is_err());
+    }
+
+    #[test]
+    fn test_parse_core_relocations() {
+        let mut raw = RawBtf::new();
+        let sec_name_off = raw.add_str("kprobe/foo");
+        let access_off = raw.add_str("0:1:0");
+        let btf = raw.build();
+
+        let ext_bytes = |hdr_len: u32, core_relo_off: u32, recs: &[[u32; 4]]| {
+            let mut data = vec![];
+            for val in &[16, sec_name_off, recs.len() as u32] {
+                data.extend(&val.to_le_bytes());
+            }
+            for val in recs.iter().flatten() {
+                data.extend(&val.to_le_bytes());
+            }
+            let mut bytes = vec![];
+            bytes.extend(&(BTF_MAGIC as u16).to_le_bytes());
+            bytes.extend(&[1, 0]);
+            for val in &[hdr_len, 0, 0, 0, 0, core_relo_off, data.len() as u32] {
+                bytes.extend(&val.to_le_bytes());
+            }
+            bytes.resize(hdr_len as usize, 0);
+            bytes.extend(&data);
+            bytes
+        };
+
+        let recs = [[8, 3, access_off, 0], [16, 3, access_off, 42]];
+        let relocs = parse_core_relocations_data(&ext_bytes(32, 0, &recs), &btf).unwrap();
+        let relocs = &relocs["kprobe/foo"];
+        assert_eq!(relocs.len(), 2);
+        assert_eq!(
+            (relocs[0].insn_off, relocs[0].type_id, &relocs[0].access),
+            (8, 3, &vec![0, 1, 0])
+        );
+        assert_eq!(relocs[0].kind, CoreRelocKind::FieldByteOffset);
+        assert_eq!(relocs[1].kind, CoreRelocKind::Unknown(42));
+
+        // the header has no CO-RE relocation fields
+        let mut bytes = ext_bytes(32, 0, &recs);
+        bytes[4] = 24;
+        assert!(parse_core_relocations_data(&bytes, &btf)
+            .unwrap()
+            .is_empty());
+        // the records exceed the section
+        let mut bytes = ext_bytes(32, 0, &recs);
+        bytes.truncate(bytes.len() - 1);
+        assert!(parse_core_relocations_data(&bytes, &btf).is_err());
+        // the offset of the records overflows
+        assert!(parse_core_relocations_data(&ext_bytes(32, u32::MAX, &recs), &btf).is_err());
+        // illegal magic
+        let mut bytes = ext_bytes(32, 0, &recs);
+        bytes[0] = 0;
+        assert!(parse_core_relocations_data(&bytes, &btf).is_err());
+        // too small to be a header
+        assert!(parse_core_relocations_data(&[0; 8], &btf).is_err());
+    }
+
+    #[test]
+    fn test_essential_name() {
+        assert_eq!(essential_name("task_struct"), "task_struct");
+        assert_eq!(essential_name("task_struct___5_10"), "task_struct");
+        assert_eq!(essential_name("sock__common"), "sock__common");
+    }
+
+    #[test]
+    fn test_parse_access_str() {
+        assert_eq!(parse_access_str("0"), Some(vec![0]));
+        assert_eq!(parse_access_str("0:1:12"), Some(vec![0, 1, 12]));
+        assert_eq!(parse_access_str("0::1"), None);
+        assert_eq!(parse_access_str(""), None);
+    }
+}
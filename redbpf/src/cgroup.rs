@@ -0,0 +1,191 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*!
+Attach types and flags of cgroup BPF programs.
+
+cgroup BPF programs are attached to a cgroup v2 directory with
+[`CGroup::attach_cgroup`](../struct.CGroup.html#method.attach_cgroup). The
+[`AttachType`](enum.AttachType.html) selects the hook of the cgroup the
+program is run at and the [`Flags`](enum.Flags.html) control how the program
+coexists with the programs attached to the same cgroup or its ancestors.
+*/
+use std::default::Default;
+
+use libbpf_sys::{
+    bpf_attach_type, BPF_CGROUP_INET4_BIND, BPF_CGROUP_INET4_CONNECT, BPF_CGROUP_INET4_POST_BIND,
+    BPF_CGROUP_INET6_BIND, BPF_CGROUP_INET6_CONNECT, BPF_CGROUP_INET6_POST_BIND,
+    BPF_CGROUP_INET_EGRESS, BPF_CGROUP_INET_INGRESS, BPF_CGROUP_INET_SOCK_CREATE,
+    BPF_CGROUP_INET_SOCK_RELEASE, BPF_CGROUP_SYSCTL, BPF_CGROUP_UDP4_RECVMSG,
+    BPF_CGROUP_UDP4_SENDMSG, BPF_CGROUP_UDP6_RECVMSG, BPF_CGROUP_UDP6_SENDMSG, BPF_F_ALLOW_MULTI,
+    BPF_F_ALLOW_OVERRIDE,
+};
+
+/// The hook of a cgroup where a BPF program is attached.
+///
+/// The attach point of a program is chosen when it is defined, e.g.
+/// `#[cgroup_sock_addr("connect4")]`, because the kernel verifies the program
+/// against it when the program is loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum AttachType {
+    /// `cgroup_skb` programs run on ingress packets.
+    Ingress = BPF_CGROUP_INET_INGRESS,
+    /// `cgroup_skb` programs run on egress packets.
+    Egress = BPF_CGROUP_INET_EGRESS,
+    /// `cgroup_sock` programs run when a socket is created.
+    SockCreate = BPF_CGROUP_INET_SOCK_CREATE,
+    /// `cgroup_sock` programs run when a socket is released.
+    SockRelease = BPF_CGROUP_INET_SOCK_RELEASE,
+    /// `cgroup_sock` programs run after an IPv4 socket is bound.
+    PostBind4 = BPF_CGROUP_INET4_POST_BIND,
+    /// `cgroup_sock` programs run after an IPv6 socket is bound.
+    PostBind6 = BPF_CGROUP_INET6_POST_BIND,
+    /// `cgroup_sock_addr` programs run on `bind(2)` of IPv4 sockets.
+    Bind4 = BPF_CGROUP_INET4_BIND,
+    /// `cgroup_sock_addr` programs run on `bind(2)` of IPv6 sockets.
+    Bind6 = BPF_CGROUP_INET6_BIND,
+    /// `cgroup_sock_addr` programs run on `connect(2)` of IPv4 sockets.
+    Connect4 = BPF_CGROUP_INET4_CONNECT,
+    /// `cgroup_sock_addr` programs run on `connect(2)` of IPv6 sockets.
+    Connect6 = BPF_CGROUP_INET6_CONNECT,
+    /// `cgroup_sock_addr` programs run on `sendmsg(2)` of unconnected UDPv4
+    /// sockets.
+    SendMsg4 = BPF_CGROUP_UDP4_SENDMSG,
+    /// `cgroup_sock_addr` programs run on `sendmsg(2)` of unconnected UDPv6
+    /// sockets.
+    SendMsg6 = BPF_CGROUP_UDP6_SENDMSG,
+    /// `cgroup_sock_addr` programs run on `recvmsg(2)` of UDPv4 sockets.
+    RecvMsg4 = BPF_CGROUP_UDP4_RECVMSG,
+    /// `cgroup_sock_addr` programs run on `recvmsg(2)` of UDPv6 sockets.
+    RecvMsg6 = BPF_CGROUP_UDP6_RECVMSG,
+    /// `cgroup_sysctl` programs run on accesses to `/proc/sys`.
+    Sysctl = BPF_CGROUP_SYSCTL,
+}
+
+impl AttachType {
+    /// Parse the attach point part of the section name of a program of
+    /// `kind`, e.g. `connect4` of `cgroup_sock_addr/connect4/<name>`.
+    pub(crate) fn from_section(kind: &str, attach_point: &str) -> Option<AttachType> {
+        use AttachType::*;
+        let attach_type = match attach_point {
+            "ingress" => Ingress,
+            "egress" => Egress,
+            "sock_create" => SockCreate,
+            "sock_release" => SockRelease,
+            "post_bind4" => PostBind4,
+            "post_bind6" => PostBind6,
+            "bind4" => Bind4,
+            "bind6" => Bind6,
+            "connect4" => Connect4,
+            "connect6" => Connect6,
+            "sendmsg4" => SendMsg4,
+            "sendmsg6" => SendMsg6,
+            "recvmsg4" => RecvMsg4,
+            "recvmsg6" => RecvMsg6,
+            "sysctl" => Sysctl,
+            _ => return None,
+        };
+        if attach_type.program_kind() == kind {
+            Some(attach_type)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the kind of program that can be attached to this hook.
+    pub(crate) fn program_kind(&self) -> &'static str {
+        use AttachType::*;
+        match self {
+            Ingress | Egress => "cgroup_skb",
+            SockCreate | SockRelease | PostBind4 | PostBind6 => "cgroup_sock",
+            Bind4 | Bind6 | Connect4 | Connect6 | SendMsg4 | SendMsg6 | RecvMsg4 | RecvMsg6 => {
+                "cgroup_sock_addr"
+            }
+            Sysctl => "cgroup_sysctl",
+        }
+    }
+}
+
+impl From<AttachType> for bpf_attach_type {
+    fn from(attach_type: AttachType) -> Self {
+        attach_type as bpf_attach_type
+    }
+}
+
+/// Flags controlling how programs attached to a cgroup are combined.
+///
+/// See `BPF_PROG_ATTACH` in `bpf(2)` for the details.
+#[derive(Debug, Clone, Copy)]
+#[repr(u32)]
+pub enum Flags {
+    /// Only one program can be attached and programs attached to descendant
+    /// cgroups are not run for this hook.
+    Unset = 0,
+    /// Programs attached to descendant cgroups override this program.
+    AllowOverride = BPF_F_ALLOW_OVERRIDE,
+    /// Multiple programs can be attached to the same hook and all the programs
+    /// attached to the cgroup and its ancestors are run.
+    AllowMulti = BPF_F_ALLOW_MULTI,
+}
+
+impl Default for Flags {
+    fn default() -> Self {
+        Flags::Unset
+    }
+}
+
+mod test {
+    #[test]
+    fn test_from_section() {
+        use crate::cgroup::AttachType::{self, *};
+        assert_eq!(
+            AttachType::from_section("cgroup_skb", "ingress"),
+            Some(Ingress)
+        );
+        assert_eq!(
+            AttachType::from_section("cgroup_skb", "egress"),
+            Some(Egress)
+        );
+        assert_eq!(
+            AttachType::from_section("cgroup_sock", "sock_create"),
+            Some(SockCreate)
+        );
+        assert_eq!(
+            AttachType::from_section("cgroup_sock", "post_bind6"),
+            Some(PostBind6)
+        );
+        assert_eq!(
+            AttachType::from_section("cgroup_sock_addr", "connect4"),
+            Some(Connect4)
+        );
+        assert_eq!(
+            AttachType::from_section("cgroup_sock_addr", "recvmsg6"),
+            Some(RecvMsg6)
+        );
+        assert_eq!(
+            AttachType::from_section("cgroup_sysctl", "sysctl"),
+            Some(Sysctl)
+        );
+    }
+
+    #[test]
+    fn test_from_section_mismatch() {
+        use crate::cgroup::AttachType;
+        assert_eq!(AttachType::from_section("cgroup_skb", "connect4"), None);
+        assert_eq!(AttachType::from_section("cgroup_sock", "ingress"), None);
+        assert_eq!(
+            AttachType::from_section("cgroup_sock_addr", "post_bind4"),
+            None
+        );
+        assert_eq!(AttachType::from_section("cgroup_sysctl", "bind4"), None);
+        assert_eq!(AttachType::from_section("cgroup_skb", "sysctl"), None);
+        assert_eq!(AttachType::from_section("cgroup_skb", "in"), None);
+        assert_eq!(AttachType::from_section("cgroup_sock_addr", ""), None);
+        assert_eq!(AttachType::from_section("xdp", "ingress"), None);
+    }
+}
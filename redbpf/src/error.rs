@@ -5,6 +5,15 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::fmt;
+
+use libbpf_sys::bpf_map_def;
+
+/// Errors of redbpf
+///
+/// Errors caused by failed system calls carry the `errno` of the call, which
+/// can be read by [`errno`](#method.errno). Failures of loading programs
+/// carry the log of the verifier too.
 #[derive(Debug)]
 pub enum Error {
     StringConversion,
@@ -23,6 +32,124 @@ pub enum Error {
     ProgramAlreadyLinked,
     ElfError,
     BTF(String),
+    /// The kernel refused to load a program
+    ProgramLoad {
+        name: String,
+        error: ::std::io::Error,
+        /// The log of the verifier. It is empty if the verifier was not
+        /// involved in the failure.
+        verifier_log: String,
+    },
+    /// The kernel refused to create a map
+    MapCreate {
+        name: String,
+        def: bpf_map_def,
+        error: ::std::io::Error,
+    },
+    /// A system call on a map failed, e.g. updating an element
+    MapOperation {
+        name: String,
+        /// The operation, e.g. `update`
+        operation: &'static str,
+        error: ::std::io::Error,
+    },
+    /// The definition of a map does not match what it is used as
+    MapMismatch {
+        name: String,
+        def: bpf_map_def,
+        /// What the map is used as, e.g. `HashMap`
+        expected: String,
+    },
+}
+
+impl Error {
+    /// The `errno` of the failed system call, if any
+    pub fn errno(&self) -> Option<i32> {
+        match self {
+            Error::IO(error)
+            | Error::ProgramLoad { error, .. }
+            | Error::MapCreate { error, .. }
+            | Error::MapOperation { error, .. } => error.raw_os_error(),
+            _ => None,
+        }
+    }
+
+    /// The log of the verifier if loading a program failed
+    pub fn verifier_log(&self) -> Option<&str> {
+        match self {
+            Error::ProgramLoad { verifier_log, .. } if !verifier_log.is_empty() => {
+                Some(verifier_log)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Error::*;
+        match self {
+            StringConversion => write!(f, "string contains an interior nul byte"),
+            BPF => write!(f, "BPF operation failed"),
+            Map => write!(f, "map operation failed"),
+            Section(name) => write!(f, "invalid or unsupported section `{}'", name),
+            Parse(e) => write!(f, "failed to parse ELF: {}", e),
+            KernelRelease(release) => write!(f, "invalid kernel release `{}'", release),
+            IO(e) => write!(f, "{}", e),
+            Uname => write!(f, "uname failed"),
+            Reloc => write!(f, "failed to apply relocations"),
+            LibraryNotFound(name) => write!(f, "library `{}' not found", name),
+            SymbolNotFound(name) => write!(f, "symbol `{}' not found", name),
+            ProgramAlreadyLoaded => write!(f, "program is already loaded"),
+            ProgramNotLoaded => write!(f, "program is not loaded"),
+            ProgramAlreadyLinked => write!(f, "program is already attached"),
+            ElfError => write!(f, "malformed ELF"),
+            BTF(msg) => write!(f, "BTF error: {}", msg),
+            ProgramLoad {
+                name,
+                error,
+                verifier_log,
+            } => {
+                write!(f, "failed to load program `{}': {}", name, error)?;
+                if !verifier_log.is_empty() {
+                    write!(f, "\nverifier log:\n{}", verifier_log.trim_end())?;
+                }
+                Ok(())
+            }
+            MapOperation {
+                name,
+                operation,
+                error,
+            } => write!(f, "failed to {} map `{}': {}", operation, name, error),
+            MapMismatch {
+                name,
+                def,
+                expected,
+            } => write!(
+                f,
+                "map `{}' (type={} key_size={} value_size={} max_entries={} flags={:#x}) does not match {}",
+                name, def.type_, def.key_size, def.value_size, def.max_entries, def.map_flags, expected
+            ),
+            MapCreate { name, def, error } => write!(
+                f,
+                "failed to create map `{}' (type={} key_size={} value_size={} max_entries={} flags={:#x}): {}",
+                name, def.type_, def.key_size, def.value_size, def.max_entries, def.map_flags, error
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse(e) => Some(e),
+            Error::IO(error)
+            | Error::ProgramLoad { error, .. }
+            | Error::MapCreate { error, .. }
+            | Error::MapOperation { error, .. } => Some(error),
+            _ => None,
+        }
+    }
 }
 
 pub type Result<T> = ::std::result::Result<T, Error>;
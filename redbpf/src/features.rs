@@ -0,0 +1,260 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*!
+Probing BPF features of the running kernel.
+
+The functions of this module find out whether the running kernel supports a
+BPF feature by trying it out, e.g. by loading a tiny program of a program
+type or by creating a small map of a map type. This is more reliable than
+guessing from the kernel version because distributions backport BPF features
+to older kernels.
+
+Probing needs the same privileges as loading BPF programs. A probe reports
+that the feature is not supported if the process is not permitted to use
+`bpf(2)` at all.
+
+# Example
+```no_run
+use redbpf::features::Features;
+
+let features = Features::probe();
+if features.ringbuf {
+    // load the probe variant that sends events through a ring buffer
+} else {
+    // fall back to the variant that uses a perf event array
+}
+```
+*/
+
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::ptr;
+
+use libbpf_sys::{
+    bpf_attach_type, bpf_func_id, bpf_insn, bpf_link_create, bpf_link_create_opts, bpf_load_btf,
+    bpf_load_program_attr, bpf_load_program_xattr, bpf_map_type, bpf_prog_type,
+    BPF_FUNC_ktime_get_coarse_ns, BPF_CGROUP_INET_INGRESS, BPF_F_MMAPABLE, BPF_MAP_TYPE_ARRAY,
+    BPF_MAP_TYPE_RINGBUF, BPF_PERF_EVENT, BPF_PROG_TYPE_CGROUP_SKB, BPF_PROG_TYPE_SOCKET_FILTER,
+    BPF_PROG_TYPE_TRACEPOINT,
+};
+
+use crate::uname::get_kernel_internal_version;
+
+/// Kernel-wide BPF features
+///
+/// The fields are probed once by [`Features::probe`](#method.probe). Keep the
+/// result around instead of probing again since every probe issues a few
+/// `bpf(2)` system calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Features {
+    /// BTF can be loaded into the kernel
+    pub btf: bool,
+    /// `BPF_MAP_TYPE_RINGBUF` maps can be created
+    pub ringbuf: bool,
+    /// Programs can be attached with `bpf_link`s, e.g. by
+    /// [`CGroup::link_cgroup`](../struct.CGroup.html#method.link_cgroup)
+    pub bpf_link: bool,
+    /// kprobes, uprobes, tracepoints and perf events can be attached with
+    /// `bpf_link`s, e.g. by
+    /// [`KProbe::link_kprobe`](../struct.KProbe.html#method.link_kprobe)
+    pub perf_link: bool,
+    /// The memory of BPF objects is accounted to memory cgroups instead of
+    /// `RLIMIT_MEMLOCK`
+    pub memcg_account: bool,
+}
+
+impl Features {
+    /// Probe all the kernel-wide features
+    pub fn probe() -> Features {
+        Features {
+            btf: btf(),
+            ringbuf: ringbuf(),
+            bpf_link: bpf_link(),
+            perf_link: perf_link(),
+            memcg_account: memcg_account(),
+        }
+    }
+}
+
+/// Check whether programs of `prog_type` can be loaded
+///
+/// # Example
+/// ```no_run
+/// use libbpf_sys::BPF_PROG_TYPE_LSM;
+/// use redbpf::features;
+///
+/// if !features::prog_type(BPF_PROG_TYPE_LSM) {
+///     eprintln!("BPF LSM programs are not supported");
+/// }
+/// ```
+pub fn prog_type(prog_type: bpf_prog_type) -> bool {
+    unsafe { libbpf_sys::bpf_probe_prog_type(prog_type, 0) }
+}
+
+/// Check whether maps of `map_type` can be created
+pub fn map_type(map_type: bpf_map_type) -> bool {
+    unsafe { libbpf_sys::bpf_probe_map_type(map_type, 0) }
+}
+
+/// Check whether programs of `prog_type` are allowed to call `helper`
+///
+/// # Example
+/// ```no_run
+/// use libbpf_sys::{BPF_FUNC_get_current_task_btf, BPF_PROG_TYPE_KPROBE};
+/// use redbpf::features;
+///
+/// let supported = features::helper(BPF_PROG_TYPE_KPROBE, BPF_FUNC_get_current_task_btf);
+/// ```
+pub fn helper(prog_type: bpf_prog_type, helper: bpf_func_id) -> bool {
+    unsafe { libbpf_sys::bpf_probe_helper(helper, prog_type, 0) }
+}
+
+/// Check whether programs of `prog_type` can be loaded for `attach_type`
+///
+/// This is meaningful only for the program types whose attach type is
+/// verified when they are loaded, i.e. cgroup programs and `sk_lookup`
+/// programs. The attach type of the other program types is not checked until
+/// the program is attached. Tracing and LSM programs can not be probed
+/// because they need the BTF of their attach target.
+pub fn attach_type(prog_type: bpf_prog_type, attach_type: bpf_attach_type) -> bool {
+    match load_probe_program(prog_type, attach_type) {
+        Some(fd) => {
+            close(fd);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Check whether BTF can be loaded into the kernel
+pub fn btf() -> bool {
+    // header, `int` and the string table of "\0int\0"
+    let mut raw = Vec::with_capacity(24 + 16 + 5);
+    raw.extend_from_slice(&0xEB9Fu16.to_ne_bytes()); // magic
+    raw.extend_from_slice(&[1, 0]); // version, flags
+    for v in &[24u32, 0, 16, 16, 5] {
+        // hdr_len, type_off, type_len, str_off, str_len
+        raw.extend_from_slice(&v.to_ne_bytes());
+    }
+    const BTF_KIND_INT: u32 = 1;
+    const BTF_INT_SIGNED: u32 = 1;
+    for v in &[1u32, BTF_KIND_INT << 24, 4, BTF_INT_SIGNED << 24 | 32] {
+        // name_off, info, size, encoding
+        raw.extend_from_slice(&v.to_ne_bytes());
+    }
+    raw.extend_from_slice(b"\0int\0");
+
+    let fd = unsafe {
+        bpf_load_btf(
+            raw.as_ptr() as *const _,
+            raw.len() as u32,
+            ptr::null_mut(),
+            0,
+            false,
+        )
+    };
+    if fd < 0 {
+        return false;
+    }
+    close(fd);
+    true
+}
+
+/// Check whether `BPF_MAP_TYPE_RINGBUF` maps can be created
+///
+/// Send events through [`RingBufMap`](../struct.RingBufMap.html) if it is
+/// supported and through [`PerfMap`](../struct.PerfMap.html) otherwise.
+pub fn ringbuf() -> bool {
+    map_type(BPF_MAP_TYPE_RINGBUF)
+}
+
+/// Check whether `BPF_MAP_TYPE_ARRAY` maps can be created with
+/// `BPF_F_MMAPABLE` to be mapped into memory
+pub fn mmapable_array() -> bool {
+    let fd = unsafe { libbpf_sys::bpf_create_map(BPF_MAP_TYPE_ARRAY, 4, 8, 1, BPF_F_MMAPABLE) };
+    if fd < 0 {
+        return false;
+    }
+    close(fd);
+    true
+}
+
+/// Check whether programs can be attached with `bpf_link`s
+pub fn bpf_link() -> bool {
+    // Kernels without `BPF_LINK_CREATE` fail with `EINVAL`. Other kernels
+    // get as far as looking up the invalid cgroup fd.
+    match load_probe_program(BPF_PROG_TYPE_CGROUP_SKB, BPF_CGROUP_INET_INGRESS) {
+        Some(prog_fd) => link_create_fails_with_ebadf(prog_fd, BPF_CGROUP_INET_INGRESS),
+        None => false,
+    }
+}
+
+/// Check whether kprobes, uprobes, tracepoints and perf events can be
+/// attached with `bpf_link`s
+pub fn perf_link() -> bool {
+    match load_probe_program(BPF_PROG_TYPE_TRACEPOINT, 0) {
+        Some(prog_fd) => link_create_fails_with_ebadf(prog_fd, BPF_PERF_EVENT),
+        None => false,
+    }
+}
+
+/// Check whether the memory of BPF objects is accounted to memory cgroups
+///
+/// If it is not, `RLIMIT_MEMLOCK` limits the memory of BPF objects. redbpf
+/// raises the limit automatically when it hits it.
+pub fn memcg_account() -> bool {
+    // There is no direct way to probe this. `bpf_ktime_get_coarse_ns` was
+    // added in the same kernel release as memcg-based accounting.
+    helper(BPF_PROG_TYPE_SOCKET_FILTER, BPF_FUNC_ktime_get_coarse_ns)
+}
+
+/// Create a link of `prog_fd` to an invalid fd and close `prog_fd`
+fn link_create_fails_with_ebadf(prog_fd: RawFd, attach_type: bpf_attach_type) -> bool {
+    let opts = bpf_link_create_opts {
+        sz: mem::size_of::<bpf_link_create_opts>() as _,
+        ..Default::default()
+    };
+    let fd = unsafe { bpf_link_create(prog_fd, -1, attach_type, &opts) };
+    let errno = io::Error::last_os_error().raw_os_error();
+    if fd >= 0 {
+        close(fd);
+    }
+    close(prog_fd);
+    fd < 0 && errno == Some(libc::EBADF)
+}
+
+/// Load `r0 = 0; exit` as a program of `prog_type`
+fn load_probe_program(prog_type: bpf_prog_type, attach_type: bpf_attach_type) -> Option<RawFd> {
+    let mut insns = [bpf_insn::default(), bpf_insn::default()];
+    insns[0].code = (libbpf_sys::BPF_ALU64 | libbpf_sys::BPF_MOV | libbpf_sys::BPF_K) as u8;
+    insns[1].code = (libbpf_sys::BPF_JMP | libbpf_sys::BPF_EXIT) as u8;
+    let license = CString::new("GPL").unwrap();
+    let mut attr = bpf_load_program_attr {
+        prog_type,
+        expected_attach_type: attach_type,
+        insns: insns.as_ptr(),
+        insns_cnt: insns.len() as _,
+        license: license.as_ptr(),
+        ..unsafe { mem::zeroed() }
+    };
+    attr.__bindgen_anon_1.kern_version = get_kernel_internal_version().unwrap_or(0);
+    let fd = unsafe { bpf_load_program_xattr(&attr, ptr::null_mut(), 0) };
+    if fd < 0 {
+        None
+    } else {
+        Some(fd)
+    }
+}
+
+fn close(fd: RawFd) {
+    unsafe {
+        let _ = libc::close(fd);
+    }
+}
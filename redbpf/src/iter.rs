@@ -0,0 +1,101 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*!
+Targets of BPF iterators.
+
+A BPF iterator program walks over a kind of kernel object, its
+[`Target`](enum.Target.html), and writes whatever it wants to a seq file that
+userspace reads with [`Iter::bpf_iter`](../struct.Iter.html#method.bpf_iter).
+Since the whole walk happens in the kernel, the data read is a consistent
+snapshot, unlike what is obtained by parsing `/proc`.
+
+The target of a program is chosen when it is defined, e.g. `#[tcp_iter]`,
+because the kernel verifies the program against the context of the target when
+the program is loaded.
+*/
+
+/// The kind of kernel objects a BPF iterator walks over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// Every task of the system.
+    Task,
+    /// Every open file of every task of the system.
+    TaskFile,
+    /// Every TCP socket, including the listening and `TIME_WAIT` sockets.
+    Tcp,
+    /// Every UDP socket.
+    Udp,
+    /// Every BPF map loaded into the kernel.
+    BpfMap,
+    /// Every element of a BPF map. The map is chosen when the iterator is
+    /// attached with [`Iter::attach_map`](../struct.Iter.html#method.attach_map).
+    BpfMapElem,
+    /// Every BPF program loaded into the kernel.
+    BpfProg,
+}
+
+impl Target {
+    pub(crate) fn from_section(kind: &str) -> Option<Target> {
+        use Target::*;
+        Some(match kind {
+            "task_iter" => Task,
+            "task_file_iter" => TaskFile,
+            "tcp_iter" => Tcp,
+            "udp_iter" => Udp,
+            "bpf_map_iter" => BpfMap,
+            "bpf_map_elem_iter" => BpfMapElem,
+            "bpf_prog_iter" => BpfProg,
+            _ => return None,
+        })
+    }
+
+    /// The name the kernel registers the iterator target with.
+    pub fn name(&self) -> &'static str {
+        use Target::*;
+        match self {
+            Task => "task",
+            TaskFile => "task_file",
+            Tcp => "tcp",
+            Udp => "udp",
+            BpfMap => "bpf_map",
+            BpfMapElem => "bpf_map_elem",
+            BpfProg => "bpf_prog",
+        }
+    }
+
+    /// Name of the kernel function whose BTF type id the program is attached
+    /// to.
+    pub(crate) fn btf_func_name(&self) -> String {
+        format!("bpf_iter_{}", self.name())
+    }
+}
+
+mod test {
+    #[test]
+    fn test_from_section() {
+        use crate::iter::Target::{self, *};
+        assert_eq!(Target::from_section("task_iter"), Some(Task));
+        assert_eq!(Target::from_section("task_file_iter"), Some(TaskFile));
+        assert_eq!(Target::from_section("tcp_iter"), Some(Tcp));
+        assert_eq!(Target::from_section("udp_iter"), Some(Udp));
+        assert_eq!(Target::from_section("bpf_map_iter"), Some(BpfMap));
+        assert_eq!(Target::from_section("bpf_map_elem_iter"), Some(BpfMapElem));
+        assert_eq!(Target::from_section("bpf_prog_iter"), Some(BpfProg));
+        assert_eq!(Target::from_section("tcp_iter").unwrap().name(), "tcp");
+    }
+
+    #[test]
+    fn test_from_section_mismatch() {
+        use crate::iter::Target;
+        assert_eq!(Target::from_section("iter"), None);
+        assert_eq!(Target::from_section("task"), None);
+        assert_eq!(Target::from_section("tcp_iter/dump_tcp"), None);
+        assert_eq!(Target::from_section("kprobe"), None);
+        assert_eq!(Target::from_section(""), None);
+    }
+}
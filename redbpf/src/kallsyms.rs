@@ -0,0 +1,174 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+
+const TRACEFS_DIRS: [&str; 2] = ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"];
+const KPROBE_BLACKLIST: &str = "/sys/kernel/debug/kprobes/blacklist";
+
+/// List the kernel functions that kprobes can be attached to
+///
+/// The functions are read from `available_filter_functions` of tracefs. If
+/// tracefs is not mounted, the functions of `/proc/kallsyms` are listed
+/// instead. Functions in the kprobe blacklist are excluded either way.
+pub(crate) fn traceable_functions() -> io::Result<Vec<String>> {
+    let mut funcs = match available_filter_functions() {
+        Ok(funcs) => funcs,
+        Err(_) => kallsyms_functions()?,
+    };
+    let blacklist = kprobe_blacklist();
+    let mut seen = HashSet::new();
+    funcs.retain(|f| !blacklist.contains(f) && seen.insert(f.clone()));
+    Ok(funcs)
+}
+
+fn available_filter_functions() -> io::Result<Vec<String>> {
+    let mut last_err = io::Error::from(io::ErrorKind::NotFound);
+    for dir in TRACEFS_DIRS.iter() {
+        match fs::read_to_string(format!("{}/available_filter_functions", dir)) {
+            // each line is `<function>` or `<function> [<module>]`
+            Ok(content) => {
+                return Ok(content
+                    .lines()
+                    .filter_map(|line| line.split_whitespace().next())
+                    .map(String::from)
+                    .collect())
+            }
+            Err(e) => last_err = e,
+        }
+    }
+    Err(last_err)
+}
+
+fn kallsyms_functions() -> io::Result<Vec<String>> {
+    Ok(fs::read_to_string("/proc/kallsyms")?
+        .lines()
+        .filter_map(parse_kallsyms_line)
+        // the parts of functions split by the compiler can not be probed
+        .filter(|sym| matches!(sym.sym_type, 't' | 'T') && !sym.name.contains(".cold"))
+        .map(|sym| sym.name.to_string())
+        .collect())
+}
+
+/// A symbol of `/proc/kallsyms`
+#[derive(Debug, PartialEq)]
+pub(crate) struct KallsymsEntry<'a> {
+    pub addr: u64,
+    pub sym_type: char,
+    pub name: &'a str,
+    pub module: Option<&'a str>,
+}
+
+impl KallsymsEntry<'_> {
+    pub fn is_function(&self) -> bool {
+        matches!(self.sym_type, 't' | 'T' | 'w' | 'W')
+    }
+}
+
+/// Parse a line of `/proc/kallsyms`, which is `<address> <type> <symbol>` or
+/// `<address> <type> <symbol> [<module>]`
+pub(crate) fn parse_kallsyms_line(line: &str) -> Option<KallsymsEntry<'_>> {
+    let mut fields = line.split_whitespace();
+    let addr = u64::from_str_radix(fields.next()?, 16).ok()?;
+    let sym_type = fields.next()?.chars().next()?;
+    let name = fields.next()?;
+    let module = fields
+        .next()
+        .and_then(|m| m.strip_prefix('['))
+        .and_then(|m| m.strip_suffix(']'));
+    Some(KallsymsEntry {
+        addr,
+        sym_type,
+        name,
+        module,
+    })
+}
+
+fn kprobe_blacklist() -> HashSet<String> {
+    // each line is `<start address>-<end address> <function>`
+    fs::read_to_string(KPROBE_BLACKLIST)
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|line| line.split_whitespace().nth(1))
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Match `name` against a glob `pattern`
+///
+/// `*` matches any sequence of characters and `?` matches any single
+/// character.
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let name = name.as_bytes();
+    let (mut p, mut n) = (0, 0);
+    // the position of the last `*` and the position of `name` it matched up to
+    let mut star = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // let the last `*` match one more character
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+mod test {
+    #[test]
+    fn test_glob_match() {
+        use crate::kallsyms::glob_match;
+        assert!(glob_match("tcp_*", "tcp_v4_connect"));
+        assert!(glob_match("tcp_*", "tcp_"));
+        assert!(!glob_match("tcp_*", "udp_sendmsg"));
+        assert!(glob_match("*_sendmsg", "udp_sendmsg"));
+        assert!(glob_match("tcp_v?_connect", "tcp_v6_connect"));
+        assert!(!glob_match("tcp_v?_connect", "tcp_v46_connect"));
+        assert!(glob_match("*sock*", "inet_sock_set_state"));
+        assert!(glob_match("vfs_read", "vfs_read"));
+        assert!(!glob_match("vfs_read", "vfs_readv"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn test_parse_kallsyms_line() {
+        use crate::kallsyms::{parse_kallsyms_line, KallsymsEntry};
+        assert_eq!(
+            parse_kallsyms_line("ffffffff81000000 T startup_64"),
+            Some(KallsymsEntry {
+                addr: 0xffffffff81000000,
+                sym_type: 'T',
+                name: "startup_64",
+                module: None,
+            })
+        );
+        assert_eq!(
+            parse_kallsyms_line("ffffffffc0a01010 t nft_do_chain\t[nf_tables]"),
+            Some(KallsymsEntry {
+                addr: 0xffffffffc0a01010,
+                sym_type: 't',
+                name: "nft_do_chain",
+                module: Some("nf_tables"),
+            })
+        );
+        assert_eq!(parse_kallsyms_line("ffffffff81000000 T"), None);
+    }
+}
@@ -45,35 +45,54 @@ for kprobe in loader.kprobes_mut() {
 extern crate lazy_static;
 
 pub mod btf;
+pub mod cgroup;
 pub mod cpus;
 mod error;
+pub mod features;
+pub mod iter;
+mod kallsyms;
+pub mod link;
 #[cfg(feature = "load")]
 pub mod load;
+pub mod objects;
 mod perf;
 mod ringbuf;
+pub mod stats;
+pub mod symbolizer;
 mod symbols;
 pub mod sys;
+pub mod tc;
+pub mod usdt;
 pub mod xdp;
 
 pub use bpf_sys::uname;
-use goblin::elf::{reloc::RelocSection, section_header as hdr, Elf, SectionHeader, Sym};
+use goblin::elf::{
+    header::ET_EXEC,
+    reloc::RelocSection,
+    section_header as hdr,
+    sym::{STT_FUNC, STT_OBJECT},
+    Elf, SectionHeader, Sym,
+};
 use libbpf_sys::{
-    bpf_create_map_attr, bpf_create_map_xattr, bpf_insn, bpf_iter_create, bpf_link_create,
-    bpf_load_program_xattr, bpf_map_def, bpf_map_info, bpf_prog_type, BPF_ANY, BPF_MAP_TYPE_ARRAY,
-    BPF_MAP_TYPE_ARRAY_OF_MAPS, BPF_MAP_TYPE_BLOOM_FILTER, BPF_MAP_TYPE_CGROUP_ARRAY,
-    BPF_MAP_TYPE_CGROUP_STORAGE, BPF_MAP_TYPE_CPUMAP, BPF_MAP_TYPE_DEVMAP,
-    BPF_MAP_TYPE_DEVMAP_HASH, BPF_MAP_TYPE_HASH, BPF_MAP_TYPE_HASH_OF_MAPS,
+    bpf_attach_type, bpf_create_map_attr, bpf_create_map_xattr, bpf_insn, bpf_iter_create,
+    bpf_link_create, bpf_load_program_xattr, bpf_map_def, bpf_map_info, bpf_prog_attach,
+    bpf_prog_detach2, bpf_prog_type, bpf_raw_tracepoint_open, BPF_ANY, BPF_LSM_MAC,
+    BPF_MAP_TYPE_ARRAY, BPF_MAP_TYPE_ARRAY_OF_MAPS, BPF_MAP_TYPE_BLOOM_FILTER,
+    BPF_MAP_TYPE_CGROUP_ARRAY, BPF_MAP_TYPE_CGROUP_STORAGE, BPF_MAP_TYPE_CPUMAP,
+    BPF_MAP_TYPE_DEVMAP, BPF_MAP_TYPE_DEVMAP_HASH, BPF_MAP_TYPE_HASH, BPF_MAP_TYPE_HASH_OF_MAPS,
     BPF_MAP_TYPE_INODE_STORAGE, BPF_MAP_TYPE_LPM_TRIE, BPF_MAP_TYPE_LRU_HASH,
     BPF_MAP_TYPE_LRU_PERCPU_HASH, BPF_MAP_TYPE_PERCPU_ARRAY, BPF_MAP_TYPE_PERCPU_CGROUP_STORAGE,
     BPF_MAP_TYPE_PERCPU_HASH, BPF_MAP_TYPE_PERF_EVENT_ARRAY, BPF_MAP_TYPE_PROG_ARRAY,
     BPF_MAP_TYPE_QUEUE, BPF_MAP_TYPE_REUSEPORT_SOCKARRAY, BPF_MAP_TYPE_RINGBUF,
     BPF_MAP_TYPE_SK_STORAGE, BPF_MAP_TYPE_SOCKHASH, BPF_MAP_TYPE_SOCKMAP, BPF_MAP_TYPE_STACK,
     BPF_MAP_TYPE_STACK_TRACE, BPF_MAP_TYPE_STRUCT_OPS, BPF_MAP_TYPE_TASK_STORAGE,
-    BPF_MAP_TYPE_XSKMAP, BPF_SK_LOOKUP, BPF_SK_SKB_STREAM_PARSER, BPF_SK_SKB_STREAM_VERDICT,
-    BPF_TRACE_ITER,
+    BPF_MAP_TYPE_XSKMAP, BPF_SK_LOOKUP, BPF_SK_MSG_VERDICT, BPF_SK_SKB_STREAM_PARSER,
+    BPF_SK_SKB_STREAM_VERDICT, BPF_TRACE_FENTRY, BPF_TRACE_FEXIT, BPF_TRACE_ITER, BPF_TRACE_RAW_TP,
+    BPF_XDP,
 };
 
 use libc::{self, pid_t};
+use std::borrow::Cow;
 use std::collections::HashMap as RSHashMap;
 use std::ffi::{CStr, CString};
 use std::fs::{self, File};
@@ -84,13 +103,17 @@ use std::ops::{Deref, DerefMut};
 use std::os::unix::io::{FromRawFd, RawFd};
 use std::path::{Path, PathBuf};
 use std::ptr;
+use std::time::Duration;
 
 use crate::btf::{BtfKind, MapBtfTypeId, BTF};
 pub use crate::error::{Error, Result};
+use crate::link::Link;
 pub use crate::perf::*;
 pub use crate::ringbuf::*;
+use crate::stats::ProgramStats;
 use crate::symbols::*;
 use crate::uname::get_kernel_internal_version;
+use crate::usdt::UsdtSpec;
 
 use tracing::{debug, error, warn};
 
@@ -136,6 +159,9 @@ pub struct ModuleBuilder<'a> {
     map_builders: RSHashMap<usize, MapBuilder<'a>>,
     symval_to_map_builders: RSHashMap<u64, MapBuilder<'a>>,
     rels: Vec<RelocationInfo>,
+    text: Option<TextSection>,
+    // section header index of a map => directory to pin the map at
+    pin_dirs: RSHashMap<usize, PathBuf>,
     license: String,
     version: u32,
     // BTF should survive until all maps are created with it. So keep it
@@ -159,14 +185,41 @@ pub enum Program {
     XDP(XDP),
     StreamParser(StreamParser),
     StreamVerdict(StreamVerdict),
-    TaskIter(TaskIter),
+    SkMsg(SkMsg),
+    Iter(Iter),
     SkLookup(SkLookup),
+    PerfEvent(PerfEvent),
+    FEntry(Trampoline),
+    FExit(Trampoline),
+    RawTracePoint(RawTracePoint),
+    BtfTracePoint(BtfTracePoint),
+    Lsm(Lsm),
+    CGroupSkb(CGroup),
+    CGroupSock(CGroup),
+    CGroupSockAddr(CGroup),
+    CGroupSysctl(CGroup),
+    TcAction(TcAction),
+}
+
+/// The result of [`Program::test_run`](enum.Program.html#method.test_run)
+#[derive(Debug, Clone)]
+pub struct TestRun {
+    /// The return value of the program, e.g. `XdpAction` of XDP programs
+    pub retval: u32,
+    /// The packet data after the program ran
+    pub data_out: Vec<u8>,
+    /// The context after the program ran. It is empty if no context was
+    /// passed to the program.
+    pub ctx_out: Vec<u8>,
+    /// The average duration of a run
+    pub duration: Duration,
 }
 
 struct ProgramData {
     pub name: String,
     code: Vec<bpf_insn>,
     fd: Option<RawFd>,
+    pin_file: Option<Box<Path>>,
 }
 
 struct KProbeAttachmentPoint {
@@ -188,6 +241,8 @@ pub struct KProbe {
     common: ProgramData,
     attach_type: ProbeAttachType,
     attachment_points: Vec<KProbeAttachmentPoint>,
+    // kprobe_multi links made by `attach_kprobes_matching`
+    multi_links: Vec<Link>,
 }
 
 /// Type to work with `uprobes` or `uretprobes`.
@@ -205,12 +260,70 @@ pub struct SocketFilter {
 pub struct TracePoint {
     common: ProgramData,
 }
+
+/// Type to work with `raw_tracepoint` BPF programs.
+///
+/// Raw tracepoints are passed the raw arguments of the tracepoint instead of
+/// the arguments copied and formatted according to the tracepoint format, so
+/// they have less overhead than [`TracePoint`](./struct.TracePoint.html).
+pub struct RawTracePoint {
+    common: ProgramData,
+    link_fd: Option<RawFd>,
+}
+
+/// Type to work with `tp_btf` BPF programs.
+///
+/// BTF-enabled tracepoints are raw tracepoints whose arguments are typed by
+/// the BTF of the running kernel. This allows BPF programs to dereference the
+/// pointer arguments directly. The tracepoint is resolved when the program is
+/// parsed so the program is attached to it without giving a name.
+pub struct BtfTracePoint {
+    common: ProgramData,
+    attach_btf_id: u32,
+    link_fd: Option<RawFd>,
+}
 /// Type to work with `XDP` programs.
 pub struct XDP {
     common: ProgramData,
     interfaces: Vec<u32>,
 }
 
+/// Type to work with cgroup BPF programs.
+///
+/// `cgroup_skb`, `cgroup_sock`, `cgroup_sock_addr` and `cgroup_sysctl`
+/// programs are attached to cgroup v2 directories and run for the processes
+/// that belong to the cgroup or its descendants.
+///
+/// # Example
+/// ```no_run
+/// # static PROBE_CODE: &[u8] = &[];
+/// use redbpf::cgroup::{AttachType, Flags};
+/// use redbpf::load::Loader;
+///
+/// let mut loaded = Loader::load(PROBE_CODE).unwrap();
+/// loaded
+///     .cgroup_sock_addr_mut("rewrite_connect")
+///     .expect("rewrite_connect program not found")
+///     .attach_cgroup("/sys/fs/cgroup/my-container", AttachType::Connect4, Flags::AllowMulti)
+///     .unwrap();
+/// ```
+pub struct CGroup {
+    common: ProgramData,
+    // `None` if the program is restored from a file descriptor
+    expected_attach_type: Option<cgroup::AttachType>,
+    attachments: Vec<(RawFd, cgroup::AttachType)>,
+}
+
+/// Type to work with `tc_action` BPF programs.
+///
+/// `tc_action` programs are attached as direct-action filters of the `clsact`
+/// qdisc of network interfaces. See the [`tc`](./tc/index.html) module for
+/// managing the qdisc and listing the attached filters.
+pub struct TcAction {
+    common: ProgramData,
+    attachments: Vec<(u32, tc::AttachPoint, u16, u32)>,
+}
+
 /// Type to work with `stream_parser` BPF programs.
 pub struct StreamParser {
     common: ProgramData,
@@ -221,7 +334,17 @@ pub struct StreamVerdict {
     common: ProgramData,
 }
 
-/// A structure supporting BPF iterators that handle `task`
+/// Type to work with `sk_msg` BPF programs.
+pub struct SkMsg {
+    common: ProgramData,
+}
+
+/// Type to work with BPF iterators
+///
+/// An iterator walks over the kernel objects of its
+/// [`Target`](./iter/enum.Target.html), e.g. tasks, TCP sockets or the
+/// elements of a BPF map, and writes data that is read by
+/// [`Iter::bpf_iter`](./struct.Iter.html#method.bpf_iter).
 ///
 /// # Example
 /// ```no_run
@@ -236,17 +359,25 @@ pub struct StreamVerdict {
 ///     .expect("dump_tgid task iterator not found");
 /// for tgid in tasks
 ///     .bpf_iter::<libc::pid_t>()
-///     .expect("error on Taskiter::bpf_iter")
+///     .expect("error on Iter::bpf_iter")
 /// {
 ///     println!("{}", tgid);
 /// }
 /// ```
-pub struct TaskIter {
+pub struct Iter {
     common: ProgramData,
+    target: iter::Target,
     attach_btf_id: u32,
     link_fd: Option<RawFd>,
 }
 
+/// BPF iterators of `task`
+///
+/// Task iterators are held by `Program::Iter` like the other iterators. This
+/// alias is kept for the code written before the other iterators were
+/// supported.
+pub type TaskIter = Iter;
+
 /// Type to work with [`sk_lookup`] BPF programs.
 ///
 /// `sk_lookup` programs were introduced with Linux 5.9 and make it possible to
@@ -302,6 +433,100 @@ pub struct SkLookup {
     link: Option<(RawFd, RawFd)>,
 }
 
+/// The kind of perf event that triggers a `perf_event` BPF program.
+///
+/// `CpuClock` and `CpuCycles` cover the common case of on-CPU profiling. Other
+/// events can be requested by passing the raw `config` value of
+/// `PERF_TYPE_SOFTWARE` or `PERF_TYPE_HARDWARE` defined in
+/// [`redbpf::sys::perf`](./sys/perf/index.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerfEventType {
+    /// The software `cpu-clock` event. It is available everywhere, including
+    /// virtual machines that do not expose hardware counters.
+    CpuClock,
+    /// The hardware `cycles` event.
+    CpuCycles,
+    /// A software event identified by one of the `perf_sw_ids_*` constants.
+    Software(u32),
+    /// A hardware event identified by one of the `perf_hw_id_*` constants.
+    Hardware(u32),
+}
+
+/// Type to work with `perf_event` BPF programs.
+///
+/// `perf_event` programs are run whenever the perf event they are attached to
+/// overflows, which makes them suitable for sampling profilers.
+///
+/// # Example
+/// ```no_run
+/// # static PROBE_CODE: &[u8] = &[];
+/// use redbpf::load::Loader;
+/// use redbpf::PerfEventType;
+///
+/// let mut loaded = Loader::load(PROBE_CODE).unwrap();
+/// // sample every online CPU 99 times per second
+/// loaded
+///     .perf_event_mut("sample_stack")
+///     .unwrap()
+///     .attach_perf_event(PerfEventType::CpuClock, 99)
+///     .unwrap();
+/// ```
+pub struct PerfEvent {
+    common: ProgramData,
+    pfds: Vec<RawFd>,
+}
+
+/// Type to work with `fentry` or `fexit` BPF programs.
+///
+/// `fentry` and `fexit` programs are attached to kernel functions through BPF
+/// trampolines. They are cheaper than `kprobes` and `kretprobes`, and `fexit`
+/// programs can read the arguments of the function as well as its return
+/// value. The kernel function to attach to is resolved from the section name
+/// of the program using the BTF of the running kernel, so BTF support is
+/// required (Linux 5.5 or later).
+///
+/// # Example
+/// ```no_run
+/// # static PROBE_CODE: &[u8] = &[];
+/// use redbpf::load::Loader;
+///
+/// let mut loaded = Loader::load(PROBE_CODE).unwrap();
+/// for prog in loaded.fentries_mut() {
+///     prog.attach_trampoline().unwrap();
+/// }
+/// ```
+pub struct Trampoline {
+    common: ProgramData,
+    attach_type: ProbeAttachType,
+    attach_btf_id: u32,
+    link_fd: Option<RawFd>,
+}
+
+/// Type to work with BPF LSM programs.
+///
+/// BPF LSM programs are attached to Linux Security Module hooks such as
+/// `file_open` and can deny the operation being checked. The kernel must be
+/// built with `CONFIG_BPF_LSM` and `bpf` must be listed in the enabled LSMs,
+/// e.g. by booting with `lsm=...,bpf`.
+///
+/// # Example
+/// ```no_run
+/// # static PROBE_CODE: &[u8] = &[];
+/// use redbpf::load::Loader;
+///
+/// let mut loaded = Loader::load(PROBE_CODE).unwrap();
+/// loaded
+///     .lsm_mut("file_open")
+///     .expect("file_open LSM program not found")
+///     .attach_lsm()
+///     .unwrap();
+/// ```
+pub struct Lsm {
+    common: ProgramData,
+    attach_btf_id: u32,
+    link_fd: Option<RawFd>,
+}
+
 /// A base BPF map data structure
 ///
 /// It is a base data structure that contains a map definition and auxiliary
@@ -319,9 +544,17 @@ pub struct Map {
     fd: RawFd,
     config: bpf_map_def,
     section_data: bool,
+    global_variables: RSHashMap<String, GlobalVariable>,
     pin_file: Option<Box<Path>>,
 }
 
+/// Location of a global variable in the data of the section defining it
+#[derive(Debug, Clone, Copy)]
+struct GlobalVariable {
+    offset: usize,
+    size: usize,
+}
+
 impl Clone for Map {
     fn clone(&self) -> Self {
         Map {
@@ -330,6 +563,7 @@ impl Clone for Map {
             fd: unsafe { libc::dup(self.fd) },
             config: self.config.clone(),
             section_data: self.section_data,
+            global_variables: self.global_variables.clone(),
             pin_file: self.pin_file.clone(),
         }
     }
@@ -343,7 +577,8 @@ enum MapBuilder<'a> {
     },
     SectionData {
         name: String,
-        bytes: &'a [u8],
+        bytes: Cow<'a, [u8]>,
+        variables: RSHashMap<String, GlobalVariable>,
     },
     ExistingMap(Map),
 }
@@ -410,6 +645,19 @@ pub struct SockMap<'a> {
     base: &'a Map,
 }
 
+/// SockHash structure for storing file descriptors of TCP sockets by
+/// userspace programs
+///
+/// Unlike [`SockMap`](./struct.SockMap.html), sockets are stored by keys of
+/// arbitrary type `K`, e.g., a connection 5-tuple.
+///
+/// The counterpart which is used by BPF program is:
+/// [`redbpf_probes::maps::SockHash`](../redbpf_probes/maps/struct.SockHash.html).
+pub struct SockHash<'a, K: Clone> {
+    base: &'a Map,
+    _k: PhantomData<K>,
+}
+
 /// Array map corresponding to BPF_MAP_TYPE_ARRAY
 ///
 /// # Example
@@ -488,6 +736,14 @@ pub struct RelocationInfo {
     sym_idx: usize,
 }
 
+/// Functions of the `.text` section that programs call with BPF-to-BPF calls
+struct TextSection {
+    shndx: usize,
+    code: Vec<bpf_insn>,
+    // (index of the first instruction, number of instructions) of each function
+    funcs: Vec<(usize, usize)>,
+}
+
 trait MapIterable<K: Clone, V: Clone> {
     fn get(&self, key: K) -> Option<V>;
     fn next_key(&self, key: Option<K>) -> Option<K>;
@@ -503,6 +759,7 @@ impl Program {
             name,
             code,
             fd: None,
+            pin_file: None,
         };
 
         Ok(match kind {
@@ -510,11 +767,13 @@ impl Program {
                 common,
                 attach_type: ProbeAttachType::Entry,
                 attachment_points: Vec::new(),
+                multi_links: Vec::new(),
             }),
             "kretprobe" => Program::KProbe(KProbe {
                 common,
                 attach_type: ProbeAttachType::Return,
                 attachment_points: Vec::new(),
+                multi_links: Vec::new(),
             }),
             "uprobe" => Program::UProbe(UProbe {
                 common,
@@ -534,33 +793,119 @@ impl Program {
             }),
             "streamparser" => Program::StreamParser(StreamParser { common }),
             "streamverdict" => Program::StreamVerdict(StreamVerdict { common }),
+            "sk_msg" => Program::SkMsg(SkMsg { common }),
             "sk_lookup" => Program::SkLookup(SkLookup { common, link: None }),
+            "raw_tracepoint" => Program::RawTracePoint(RawTracePoint {
+                common,
+                link_fd: None,
+            }),
+            "cgroup_skb" | "cgroup_sock" | "cgroup_sock_addr" | "cgroup_sysctl" => {
+                // the section name is `<kind>/<attach point>/<name>`
+                let section_name = common.name.clone();
+                let mut names = section_name.splitn(2, '/');
+                let expected_attach_type = names
+                    .next()
+                    .and_then(|attach_point| cgroup::AttachType::from_section(kind, attach_point))
+                    .ok_or_else(|| Error::Section(format!("{}/{}", kind, section_name)))?;
+                let mut common = common;
+                if let Some(name) = names.next() {
+                    common.name = name.to_string();
+                }
+                let prog = CGroup {
+                    common,
+                    expected_attach_type: Some(expected_attach_type),
+                    attachments: Vec::new(),
+                };
+                match kind {
+                    "cgroup_skb" => Program::CGroupSkb(prog),
+                    "cgroup_sock" => Program::CGroupSock(prog),
+                    "cgroup_sock_addr" => Program::CGroupSockAddr(prog),
+                    _ => Program::CGroupSysctl(prog),
+                }
+            }
+            "tc_action" => Program::TcAction(TcAction {
+                common,
+                attachments: Vec::new(),
+            }),
+            "perf_event" => Program::PerfEvent(PerfEvent {
+                common,
+                pfds: Vec::new(),
+            }),
             _ => return Err(Error::Section(kind.to_string())),
         })
     }
 
     fn with_btf(kind: &str, name: &str, code: &[u8], btf: &BTF) -> Result<Program> {
         let code = unsafe { zero::read_array_unsafe(code) }.to_vec();
-        let name = name.to_string();
-
         let common = ProgramData {
-            name,
+            name: name.to_string(),
             code,
             fd: None,
+            pin_file: None,
         };
 
         Ok(match kind {
-            "task_iter" => {
+            "task_iter" | "task_file_iter" | "tcp_iter" | "udp_iter" | "bpf_map_iter"
+            | "bpf_map_elem_iter" | "bpf_prog_iter" => {
+                let target = iter::Target::from_section(kind)
+                    .ok_or_else(|| Error::Section(kind.to_string()))?;
+                let func_name = target.btf_func_name();
+                let btf_id = btf
+                    .find_type_id(&func_name, BtfKind::Function)
+                    .ok_or_else(|| Error::BTF(format!("type id of {} not found", func_name)))?;
+                debug!("btf_id of {}: {}", func_name, btf_id);
+                Program::Iter(Iter {
+                    common,
+                    target,
+                    attach_btf_id: btf_id,
+                    link_fd: None,
+                })
+            }
+            "lsm" => {
+                let btf_id = btf
+                    .find_type_id(&format!("bpf_lsm_{}", name), BtfKind::Function)
+                    .ok_or_else(|| Error::BTF(format!("type id of bpf_lsm_{} not found", name)))?;
+                debug!("btf_id of bpf_lsm_{}: {}", name, btf_id);
+                Program::Lsm(Lsm {
+                    common,
+                    attach_btf_id: btf_id,
+                    link_fd: None,
+                })
+            }
+            "tp_btf" => {
                 let btf_id = btf
-                    .find_type_id("bpf_iter_task", BtfKind::Function)
-                    .ok_or_else(|| Error::BTF("type id of bpf_iter_task not found".to_string()))?;
-                debug!("btf_id of bpf_iter_task: {}", btf_id);
-                Program::TaskIter(TaskIter {
+                    .find_type_id(&format!("btf_trace_{}", name), BtfKind::TypeDef)
+                    .ok_or_else(|| {
+                        Error::BTF(format!("type id of btf_trace_{} not found", name))
+                    })?;
+                debug!("btf_id of btf_trace_{}: {}", name, btf_id);
+                Program::BtfTracePoint(BtfTracePoint {
                     common,
                     attach_btf_id: btf_id,
                     link_fd: None,
                 })
             }
+            "fentry" | "fexit" => {
+                let btf_id = btf.find_type_id(name, BtfKind::Function).ok_or_else(|| {
+                    Error::BTF(format!("type id of kernel function {} not found", name))
+                })?;
+                debug!("btf_id of {}: {}", name, btf_id);
+                let prog = Trampoline {
+                    common,
+                    attach_type: if kind == "fentry" {
+                        ProbeAttachType::Entry
+                    } else {
+                        ProbeAttachType::Return
+                    },
+                    attach_btf_id: btf_id,
+                    link_fd: None,
+                };
+                if kind == "fentry" {
+                    Program::FEntry(prog)
+                } else {
+                    Program::FExit(prog)
+                }
+            }
             _ => return Err(Error::Section(kind.to_string())),
         })
     }
@@ -574,8 +919,17 @@ impl Program {
             SocketFilter(_) => libbpf_sys::BPF_PROG_TYPE_SOCKET_FILTER,
             TracePoint(_) => libbpf_sys::BPF_PROG_TYPE_TRACEPOINT,
             StreamParser(_) | StreamVerdict(_) => libbpf_sys::BPF_PROG_TYPE_SK_SKB,
-            TaskIter(_) => libbpf_sys::BPF_PROG_TYPE_TRACING,
+            SkMsg(_) => libbpf_sys::BPF_PROG_TYPE_SK_MSG,
+            Iter(_) | FEntry(_) | FExit(_) | BtfTracePoint(_) => libbpf_sys::BPF_PROG_TYPE_TRACING,
+            RawTracePoint(_) => libbpf_sys::BPF_PROG_TYPE_RAW_TRACEPOINT,
+            Lsm(_) => libbpf_sys::BPF_PROG_TYPE_LSM,
+            CGroupSkb(_) => libbpf_sys::BPF_PROG_TYPE_CGROUP_SKB,
+            CGroupSock(_) => libbpf_sys::BPF_PROG_TYPE_CGROUP_SOCK,
+            CGroupSockAddr(_) => libbpf_sys::BPF_PROG_TYPE_CGROUP_SOCK_ADDR,
+            CGroupSysctl(_) => libbpf_sys::BPF_PROG_TYPE_CGROUP_SYSCTL,
+            TcAction(_) => libbpf_sys::BPF_PROG_TYPE_SCHED_CLS,
             SkLookup(_) => libbpf_sys::BPF_PROG_TYPE_SK_LOOKUP,
+            PerfEvent(_) => libbpf_sys::BPF_PROG_TYPE_PERF_EVENT,
         }
     }
 
@@ -590,8 +944,16 @@ impl Program {
             TracePoint(p) => &p.common,
             StreamParser(p) => &p.common,
             StreamVerdict(p) => &p.common,
-            TaskIter(p) => &p.common,
+            SkMsg(p) => &p.common,
+            Iter(p) => &p.common,
             SkLookup(p) => &p.common,
+            PerfEvent(p) => &p.common,
+            FEntry(p) | FExit(p) => &p.common,
+            RawTracePoint(p) => &p.common,
+            BtfTracePoint(p) => &p.common,
+            Lsm(p) => &p.common,
+            CGroupSkb(p) | CGroupSock(p) | CGroupSockAddr(p) | CGroupSysctl(p) => &p.common,
+            TcAction(p) => &p.common,
         }
     }
 
@@ -606,8 +968,16 @@ impl Program {
             TracePoint(p) => &mut p.common,
             StreamParser(p) => &mut p.common,
             StreamVerdict(p) => &mut p.common,
-            TaskIter(p) => &mut p.common,
+            SkMsg(p) => &mut p.common,
+            Iter(p) => &mut p.common,
             SkLookup(p) => &mut p.common,
+            PerfEvent(p) => &mut p.common,
+            FEntry(p) | FExit(p) => &mut p.common,
+            RawTracePoint(p) => &mut p.common,
+            BtfTracePoint(p) => &mut p.common,
+            Lsm(p) => &mut p.common,
+            CGroupSkb(p) | CGroupSock(p) | CGroupSockAddr(p) | CGroupSysctl(p) => &mut p.common,
+            TcAction(p) => &mut p.common,
         }
     }
 
@@ -619,6 +989,250 @@ impl Program {
         &self.data().fd
     }
 
+    /// Read the runtime statistics of the program
+    ///
+    /// The run count and the run time are counted only while run time
+    /// statistics are enabled by [`stats::enable_stats`](./stats/fn.enable_stats.html).
+    pub fn stats(&self) -> Result<ProgramStats> {
+        let fd = self.data().fd.ok_or(Error::ProgramNotLoaded)?;
+        Ok(ProgramStats::from(&get_prog_info(fd)?))
+    }
+
+    /// Run the program on `input` in the kernel without attaching it
+    ///
+    /// The program runs `repeat` times on the packet data `input`, and on the
+    /// context `ctx_in` if it is given, e.g. `xdp_md` of XDP programs or
+    /// `__sk_buff` of socket filters and `tc` actions. This is useful to test
+    /// the verdicts of packet programs with crafted packets.
+    ///
+    /// Only XDP, socket filter and `tc` action programs can be run. This
+    /// requires Linux 4.12 or later. Passing `ctx_in` requires a later kernel
+    /// that accepts the context of the program type, e.g. Linux 5.0 for
+    /// `__sk_buff` and 5.15 for `xdp_md`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use redbpf::load::Loader;
+    /// let loaded = Loader::load_file("xdp.elf").expect("error loading probe");
+    /// let prog = loaded.program("drop_udp").expect("program not found");
+    /// let packet = [0u8; 64];
+    /// let result = prog.test_run(&packet, None, 1).expect("error on test run");
+    /// assert_eq!(result.retval, 1); // XDP_DROP
+    /// ```
+    pub fn test_run(&self, input: &[u8], ctx_in: Option<&[u8]>, repeat: u32) -> Result<TestRun> {
+        use Program::*;
+
+        match self {
+            XDP(_) | SocketFilter(_) | TcAction(_) => {}
+            _ => {
+                error!("test run is not supported for program `{}'", self.name());
+                return Err(Error::BPF);
+            }
+        }
+        let fd = self.data().fd.ok_or(Error::ProgramNotLoaded)?;
+        // XDP programs can grow the packet at its head and tail
+        let mut data_out = vec![0u8; input.len() + 4096];
+        let mut ctx_out = vec![0u8; ctx_in.map(|ctx| ctx.len()).unwrap_or(0)];
+        let mut opts = libbpf_sys::bpf_test_run_opts {
+            sz: mem::size_of::<libbpf_sys::bpf_test_run_opts>() as _,
+            data_in: input.as_ptr() as *const _,
+            data_size_in: input.len() as u32,
+            data_out: data_out.as_mut_ptr() as *mut _,
+            data_size_out: data_out.len() as u32,
+            repeat: repeat as _,
+            ..Default::default()
+        };
+        if let Some(ctx_in) = ctx_in {
+            opts.ctx_in = ctx_in.as_ptr() as *const _;
+            opts.ctx_size_in = ctx_in.len() as u32;
+            opts.ctx_out = ctx_out.as_mut_ptr() as *mut _;
+            opts.ctx_size_out = ctx_out.len() as u32;
+        }
+        if unsafe { libbpf_sys::bpf_prog_test_run_opts(fd, &mut opts) } < 0 {
+            let err = io::Error::last_os_error();
+            error!("error on bpf_prog_test_run_opts: {}", err);
+            return Err(Error::IO(err));
+        }
+        data_out.truncate(opts.data_size_out as usize);
+        ctx_out.truncate(opts.ctx_size_out as usize);
+        Ok(TestRun {
+            retval: opts.retval,
+            data_out,
+            ctx_out,
+            duration: Duration::from_nanos(opts.duration as u64),
+        })
+    }
+
+    /// Create `Program` from a file which represents a pinned program
+    ///
+    /// The kind of the program is decided by its program type that is read
+    /// by `bpf_obj_get_info_by_fd`. Since some program types are shared by
+    /// several kinds of programs, the restored program is one of them:
+    /// `kprobe`, `uprobe` and their return probes are restored as
+    /// `Program::KProbe`, and stream parsers and verdicts are restored as
+    /// `Program::StreamVerdict`. `cgroup` programs keep the kind of their hook
+    /// but not the hook itself. Programs of `BPF_PROG_TYPE_TRACING`, i.e.
+    /// `fentry`, `fexit`, `tp_btf` and iterators, can not be restored.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use redbpf::Program;
+    /// let mut prog = Program::from_pin_file("/sys/fs/bpf/xdp_prog").expect("error on restoring program");
+    /// if let Program::XDP(xdp) = &mut prog {
+    ///     xdp.attach_xdp("eth0", redbpf::xdp::Flags::default()).expect("error on attaching XDP");
+    /// }
+    /// ```
+    pub fn from_pin_file(file: impl AsRef<Path>) -> Result<Program> {
+        let file = file.as_ref();
+        let fd = unsafe {
+            let cpathname = CString::new(file.to_str().unwrap())?;
+            libbpf_sys::bpf_obj_get(cpathname.as_ptr())
+        };
+        if fd < 0 {
+            error!("error on bpf_obj_get: {}", io::Error::last_os_error());
+            return Err(Error::IO(io::Error::last_os_error()));
+        }
+        Program::from_fd(fd, Some(Box::from(file)))
+    }
+
+    /// Open the program of the given id
+    ///
+    /// The ids of the programs are listed by
+    /// [`objects::programs`](./objects/fn.programs.html). The kind of the
+    /// program is decided in the same way as
+    /// [`from_pin_file`](#method.from_pin_file).
+    pub fn from_id(id: u32) -> Result<Program> {
+        let fd = unsafe { libbpf_sys::bpf_prog_get_fd_by_id(id) };
+        if fd < 0 {
+            let err = io::Error::last_os_error();
+            error!("error on bpf_prog_get_fd_by_id: {}", err);
+            return Err(Error::IO(err));
+        }
+        Program::from_fd(fd, None)
+    }
+
+    /// Create `Program` from `fd` of a program. The `fd` is closed on errors.
+    fn from_fd(fd: RawFd, pin_file: Option<Box<Path>>) -> Result<Program> {
+        let prog_info = match get_prog_info(fd) {
+            Ok(info) => info,
+            Err(e) => {
+                unsafe { libc::close(fd) };
+                return Err(e);
+            }
+        };
+        let name = unsafe {
+            CStr::from_ptr(&prog_info.name as *const _)
+                .to_string_lossy()
+                .into_owned()
+        };
+        let common = ProgramData {
+            name,
+            code: Vec::new(),
+            fd: Some(fd),
+            pin_file,
+        };
+        // the expected attach type of a loaded program is not known
+        let cgroup_prog = |common| CGroup {
+            common,
+            expected_attach_type: None,
+            attachments: Vec::new(),
+        };
+
+        // `common` is dropped on errors so the fd is closed
+        Ok(match prog_info.type_ {
+            libbpf_sys::BPF_PROG_TYPE_KPROBE => Program::KProbe(KProbe {
+                common,
+                attach_type: ProbeAttachType::Entry,
+                attachment_points: Vec::new(),
+                multi_links: Vec::new(),
+            }),
+            libbpf_sys::BPF_PROG_TYPE_TRACEPOINT => Program::TracePoint(TracePoint { common }),
+            libbpf_sys::BPF_PROG_TYPE_SOCKET_FILTER => {
+                Program::SocketFilter(SocketFilter { common })
+            }
+            libbpf_sys::BPF_PROG_TYPE_XDP => Program::XDP(XDP {
+                common,
+                interfaces: Vec::new(),
+            }),
+            libbpf_sys::BPF_PROG_TYPE_SK_SKB => Program::StreamVerdict(StreamVerdict { common }),
+            libbpf_sys::BPF_PROG_TYPE_SK_MSG => Program::SkMsg(SkMsg { common }),
+            libbpf_sys::BPF_PROG_TYPE_SK_LOOKUP => {
+                Program::SkLookup(SkLookup { common, link: None })
+            }
+            libbpf_sys::BPF_PROG_TYPE_RAW_TRACEPOINT => Program::RawTracePoint(RawTracePoint {
+                common,
+                link_fd: None,
+            }),
+            libbpf_sys::BPF_PROG_TYPE_LSM => Program::Lsm(Lsm {
+                common,
+                attach_btf_id: 0,
+                link_fd: None,
+            }),
+            libbpf_sys::BPF_PROG_TYPE_CGROUP_SKB => Program::CGroupSkb(cgroup_prog(common)),
+            libbpf_sys::BPF_PROG_TYPE_CGROUP_SOCK => Program::CGroupSock(cgroup_prog(common)),
+            libbpf_sys::BPF_PROG_TYPE_CGROUP_SOCK_ADDR => {
+                Program::CGroupSockAddr(cgroup_prog(common))
+            }
+            libbpf_sys::BPF_PROG_TYPE_CGROUP_SYSCTL => Program::CGroupSysctl(cgroup_prog(common)),
+            libbpf_sys::BPF_PROG_TYPE_SCHED_CLS => Program::TcAction(TcAction {
+                common,
+                attachments: Vec::new(),
+            }),
+            libbpf_sys::BPF_PROG_TYPE_PERF_EVENT => Program::PerfEvent(PerfEvent {
+                common,
+                pfds: Vec::new(),
+            }),
+            type_ => {
+                error!(
+                    "program `{}' of type {} can not be restored",
+                    common.name, type_
+                );
+                return Err(Error::BPF);
+            }
+        })
+    }
+
+    /// Pin the loaded program to BPF FS
+    ///
+    /// The program stays loaded after this process exits so other processes
+    /// can attach it, e.g. `tc filter add dev eth0 ingress bpf pinned <file>`,
+    /// or restore it with [`Program::from_pin_file`](#method.from_pin_file).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use redbpf::load::Loader;
+    /// let mut loaded = Loader::load_file("file.elf").expect("error loading probe");
+    /// let prog = loaded.program_mut("xdp_prog").expect("program not found");
+    /// prog.pin("/sys/fs/bpf/xdp_prog").expect("error on pinning");
+    /// ```
+    pub fn pin(&mut self, file: impl AsRef<Path>) -> Result<()> {
+        let file = file.as_ref();
+        let fd = self.fd().ok_or(Error::ProgramNotLoaded)?;
+        let data = self.data_mut();
+        if data.pin_file.is_some() {
+            error!("already pinned");
+            return Err(Error::BPF);
+        }
+        pin_bpf_obj(fd, file)?;
+        data.pin_file = Some(Box::from(file));
+        Ok(())
+    }
+
+    /// Unpin the program
+    ///
+    /// The program is unloaded when it is dropped after this unless it is
+    /// still attached somewhere.
+    pub fn unpin(&mut self) -> Result<()> {
+        let data = self.data_mut();
+        if data.pin_file.is_none() {
+            error!("not pinned");
+            return Err(Error::BPF);
+        }
+        unpin_bpf_obj(data.pin_file.as_ref().unwrap())?;
+        data.pin_file = None;
+        Ok(())
+    }
+
     /// Load the BPF program.
     ///
     /// BPF programs need to be loaded before they can be attached. Loading will fail if the BPF verifier rejects the code.
@@ -634,6 +1248,22 @@ impl Program {
     /// }
     /// ```
     pub fn load(&mut self, kernel_version: u32, license: String) -> Result<()> {
+        self.load_with_log_level(kernel_version, license, 1)
+    }
+
+    /// Load the BPF program with the given level of the verifier log.
+    ///
+    /// The verifier log is collected only if loading fails, and is returned
+    /// by [`Error::verifier_log`](enum.Error.html#method.verifier_log). Level
+    /// `1` logs the instructions the verifier has checked, level `2` logs
+    /// the state of the registers at every instruction too and `0` does not
+    /// collect the log at all.
+    pub fn load_with_log_level(
+        &mut self,
+        kernel_version: u32,
+        license: String,
+        log_level: u32,
+    ) -> Result<()> {
         if self.fd().is_some() {
             return Err(Error::ProgramAlreadyLoaded);
         }
@@ -652,10 +1282,32 @@ impl Program {
         attr.log_level = 0;
 
         match self {
-            Program::TaskIter(bpf_iter) => {
+            Program::Iter(bpf_iter) => {
                 attr.expected_attach_type = BPF_TRACE_ITER;
                 attr.__bindgen_anon_2.attach_btf_id = bpf_iter.attach_btf_id;
             }
+            Program::FEntry(prog) | Program::FExit(prog) => {
+                attr.expected_attach_type = match prog.attach_type {
+                    ProbeAttachType::Entry => BPF_TRACE_FENTRY,
+                    ProbeAttachType::Return => BPF_TRACE_FEXIT,
+                };
+                attr.__bindgen_anon_2.attach_btf_id = prog.attach_btf_id;
+            }
+            Program::BtfTracePoint(prog) => {
+                attr.expected_attach_type = BPF_TRACE_RAW_TP;
+                attr.__bindgen_anon_2.attach_btf_id = prog.attach_btf_id;
+            }
+            Program::Lsm(prog) => {
+                attr.expected_attach_type = BPF_LSM_MAC;
+                attr.__bindgen_anon_2.attach_btf_id = prog.attach_btf_id;
+            }
+            Program::CGroupSkb(prog)
+            | Program::CGroupSock(prog)
+            | Program::CGroupSockAddr(prog)
+            | Program::CGroupSysctl(prog) => {
+                attr.expected_attach_type = prog.expected_attach_type.map_or(0, Into::into);
+                attr.__bindgen_anon_1.kern_version = kernel_version;
+            }
             Program::SkLookup(_) => {
                 attr.expected_attach_type = BPF_SK_LOOKUP;
                 attr.__bindgen_anon_1.kern_version = kernel_version;
@@ -699,15 +1351,28 @@ impl Program {
         }
 
         // unknown error. print log from bpf verifier and give up loading BPF program
-        attr.log_level = 1;
-        let mut printed = false;
+        if log_level == 0 {
+            let error = io::Error::last_os_error();
+            error!(
+                "error loading BPF program `{}' with bpf_load_program_xattr: {}",
+                self.name(),
+                error
+            );
+            return Err(Error::ProgramLoad {
+                name: self.name().to_string(),
+                error,
+                verifier_log: String::new(),
+            });
+        }
+        attr.log_level = log_level;
         let mut vec_len = 64 * 1024;
-        while !printed {
+        loop {
             let mut buf_vec = vec![0; vec_len];
             let log_buffer: MutDataPtr = buf_vec.as_mut_ptr();
             let buf_size = buf_vec.capacity() * mem::size_of_val(unsafe { &*log_buffer });
             let fd =
                 unsafe { libbpf_sys::bpf_load_program_xattr(&attr, log_buffer, buf_size as u64) };
+            let error = io::Error::last_os_error();
             if fd >= 0 {
                 warn!(
                     "bpf_load_program_xattr had failed but it unexpectedly succeeded while reproducing the error"
@@ -715,7 +1380,7 @@ impl Program {
                 self.data_mut().fd = Some(fd);
                 return Ok(());
             }
-            if let Some(libc::ENOSPC) = io::Error::last_os_error().raw_os_error() {
+            if let Some(libc::ENOSPC) = error.raw_os_error() {
                 // If the size of the buffer is not large
                 // enough to store all verifier messages, errno
                 // is set to ENOSPC. So, pass the bigger log
@@ -724,17 +1389,22 @@ impl Program {
                 continue;
             }
 
-            let cstr = unsafe { CStr::from_ptr(log_buffer) };
+            let verifier_log = unsafe { CStr::from_ptr(log_buffer) }
+                .to_string_lossy()
+                .into_owned();
             error!(
                 "error loading BPF program `{}' with bpf_load_program_xattr. ret={} os error={}: {}",
                 self.name(),
-                fd, io::Error::last_os_error(),
-                cstr.to_str().unwrap()
+                fd,
+                error,
+                verifier_log
             );
-            printed = true;
+            return Err(Error::ProgramLoad {
+                name: self.name().to_string(),
+                error,
+                verifier_log,
+            });
         }
-
-        Err(Error::BPF)
     }
 }
 
@@ -852,19 +1522,102 @@ impl KProbe {
         }
     }
 
-    /// Detach the `kprobe` or `kretprobe`
+    /// Attach the `kprobe` or `kretprobe` to all the kernel functions whose
+    /// names match a glob `pattern`
     ///
-    /// This method is not needed to be called manually because all attachment
-    /// points are detached and closed automatically when `KProbe` is
-    /// dropped. But this method provides a feature for detaching a bpf program
-    /// from kprobe event selectively.
+    /// `*` in `pattern` matches any sequence of characters and `?` matches
+    /// any single character. The candidates are the functions listed in
+    /// `available_filter_functions` of tracefs, or in `/proc/kallsyms` if
+    /// tracefs is not mounted, excluding the functions of the kprobe
+    /// blacklist.
+    ///
+    /// On Linux 5.18 or later all the matching functions are attached at once
+    /// with a single `kprobe_multi` link. For this, the program is loaded once
+    /// more as a `kprobe_multi` program, so it is not available for programs
+    /// obtained from pinned paths or ids. The link is released when `KProbe`
+    /// is dropped and [`detach_kprobe`](#method.detach_kprobe) does not
+    /// detach it.
+    ///
+    /// Otherwise each function is attached with a perf event of its own, so
+    /// attaching to hundreds of functions takes a while. Attaching to one
+    /// function does not stop at failures on the other functions. So the
+    /// result of each function that matches `pattern` is returned.
     ///
     /// # Example
     /// ```no_run
-    /// use redbpf::Module;
-    /// let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
-    /// for kprobe in module.kprobes_mut() {
-    ///     kprobe.attach_kprobe(&kprobe.name(), 0).unwrap();
+    /// use redbpf::load::Loader;
+    /// let mut loaded = Loader::load_file("file.elf").expect("error loading probe");
+    /// let kprobe = loaded.kprobe_mut("tcp_probe").expect("kprobe not found");
+    /// for (fn_name, result) in kprobe.attach_kprobes_matching("tcp_*").unwrap() {
+    ///     if let Err(e) = result {
+    ///         eprintln!("error on attaching to {}: {}", fn_name, e);
+    ///     }
+    /// }
+    /// ```
+    pub fn attach_kprobes_matching(&mut self, pattern: &str) -> Result<Vec<(String, Result<()>)>> {
+        if self.common.fd.is_none() {
+            return Err(Error::ProgramNotLoaded);
+        }
+        let funcs: Vec<String> = kallsyms::traceable_functions()
+            .map_err(|e| {
+                error!("error on listing traceable functions: {}", e);
+                Error::IO(e)
+            })?
+            .into_iter()
+            .filter(|fn_name| kallsyms::glob_match(pattern, fn_name))
+            .collect();
+        if !funcs.is_empty() && !self.common.code.is_empty() && link::kprobe_multi_supported() {
+            match self.link_kprobe_multi(&funcs) {
+                Ok(link) => {
+                    self.multi_links.push(link);
+                    return Ok(funcs.into_iter().map(|fn_name| (fn_name, Ok(()))).collect());
+                }
+                Err(e) => warn!(
+                    "error on attaching kprobe_multi link: {}. attach kprobes one by one",
+                    e
+                ),
+            }
+        }
+        Ok(funcs
+            .into_iter()
+            .map(|fn_name| {
+                let result = self.attach_kprobe(&fn_name, 0);
+                (fn_name, result)
+            })
+            .collect())
+    }
+
+    fn link_kprobe_multi(&self, fn_names: &[String]) -> Result<Link> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        let license = if get_prog_info(fd)?.gpl_compatible() != 0 {
+            "GPL"
+        } else {
+            "Proprietary"
+        };
+        let multi_fd =
+            link::load_kprobe_multi_program(&self.common.name, &self.common.code, license)?;
+        let retprobe = matches!(self.attach_type, ProbeAttachType::Return);
+        let result = Link::with_kprobe_multi(multi_fd, fn_names, retprobe);
+        // the link holds a reference to the program
+        unsafe {
+            libc::close(multi_fd);
+        }
+        result
+    }
+
+    /// Detach the `kprobe` or `kretprobe`
+    ///
+    /// This method is not needed to be called manually because all attachment
+    /// points are detached and closed automatically when `KProbe` is
+    /// dropped. But this method provides a feature for detaching a bpf program
+    /// from kprobe event selectively.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use redbpf::Module;
+    /// let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
+    /// for kprobe in module.kprobes_mut() {
+    ///     kprobe.attach_kprobe(&kprobe.name(), 0).unwrap();
     ///     // do some stuff...
     ///     kprobe.detach_kprobe(&kprobe.name(), 0).unwrap();
     /// }
@@ -877,6 +1630,31 @@ impl KProbe {
         Ok(())
     }
 
+    /// Attach the `kprobe` or `kretprobe` with a [`Link`](./link/struct.Link.html).
+    ///
+    /// The program stays attached while the returned `Link` is alive or
+    /// pinned. This requires Linux 5.15 or later.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use redbpf::Module;
+    /// let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
+    /// for kprobe in module.kprobes() {
+    ///     let mut link = kprobe.link_kprobe(&kprobe.name(), 0).unwrap();
+    ///     link.pin(format!("/sys/fs/bpf/{}", kprobe.name())).unwrap();
+    /// }
+    /// ```
+    pub fn link_kprobe(&self, fn_name: &str, offset: u64) -> Result<Link> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        let pfd = unsafe {
+            match self.attach_type {
+                ProbeAttachType::Entry => perf::open_kprobe_perf_event(fn_name, offset)?,
+                ProbeAttachType::Return => perf::open_kretprobe_perf_event(fn_name, offset)?,
+            }
+        };
+        Link::with_perf_event(fd, pfd)
+    }
+
     pub fn name(&self) -> String {
         self.common.name.to_string()
     }
@@ -947,15 +1725,148 @@ impl UProbe {
         pid: Option<pid_t>,
     ) -> Result<()> {
         let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
-
-        let path = if let Some(pid) = pid {
-            resolve_proc_maps_lib(pid, target).unwrap_or_else(|| target.to_string())
-        } else {
-            match (target.starts_with('/'), LD_SO_CACHE.as_ref()) {
-                (false, Ok(cache)) => cache.resolve(target).unwrap_or(target).to_string(),
-                _ => target.to_owned(),
+        let pfd = self.open_perf_event(fn_name, offset, semaphore, target, pid)?;
+        unsafe {
+            let ret = perf::attach_perf_event(fd, pfd);
+            if ret.is_ok() {
+                self.attachment_points.push(UProbeAttachmentPoint {
+                    fn_name: fn_name.map(String::from),
+                    offset,
+                    target: target.to_owned(),
+                    pid,
+                    pfd,
+                });
+            } else {
+                libc::close(pfd);
             }
+            ret
+        }
+    }
+
+    /// Attach the `uprobe` or `uretprobe` with a [`Link`](./link/struct.Link.html).
+    ///
+    /// Arguments are the same as the ones of
+    /// [`attach_uprobe`](#method.attach_uprobe). The program stays attached
+    /// while the returned `Link` is alive or pinned. This requires Linux 5.15
+    /// or later.
+    pub fn link_uprobe(
+        &self,
+        fn_name: Option<&str>,
+        offset: u64,
+        target: &str,
+        pid: Option<pid_t>,
+    ) -> Result<Link> {
+        self.link_uprobe_with_semaphore(fn_name, offset, 0, target, pid)
+    }
+
+    /// Attach the `uprobe` or `uretprobe` with a [`Link`](./link/struct.Link.html).
+    ///
+    /// Arguments are the same as the ones of
+    /// [`attach_uprobe_with_semaphore`](#method.attach_uprobe_with_semaphore).
+    /// The program stays attached while the returned `Link` is alive or
+    /// pinned. This requires Linux 5.15 or later.
+    pub fn link_uprobe_with_semaphore(
+        &self,
+        fn_name: Option<&str>,
+        offset: u64,
+        semaphore: u32,
+        target: &str,
+        pid: Option<pid_t>,
+    ) -> Result<Link> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        let pfd = self.open_perf_event(fn_name, offset, semaphore, target, pid)?;
+        Link::with_perf_event(fd, pfd)
+    }
+
+    /// Attach the `uprobe` to a USDT probe.
+    ///
+    /// Attach the probe to all the sites of the USDT probe `provider`:`name`
+    /// defined in the library or binary at `target`. If the probe has a
+    /// semaphore, the kernel increments it while the probe is attached so
+    /// that the traced program evaluates the arguments of the probe. This
+    /// requires Linux 4.20 or later.
+    ///
+    /// If a `pid` is passed, only the corresponding process is traced.
+    ///
+    /// Returns the addresses of the sites in the memory of the traced
+    /// process with the locations of their arguments. Store them in a
+    /// `HashMap<u64, UsdtSpec>` map so that the BPF program can look up the
+    /// spec of the site it is hit at by `regs.ip()` and read the arguments
+    /// with `redbpf_probes::usdt::UsdtSpec::arg`. The addresses of sites in
+    /// shared libraries and PIE binaries are known only if a `pid` is passed,
+    /// so such sites are attached but not returned otherwise. The sites whose
+    /// arguments can not be read by BPF programs are not returned either.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use redbpf::Module;
+    /// let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
+    /// let uprobe = module.uprobe_mut("query_start").expect("bpf program not found");
+    /// let specs = uprobe.attach_usdt("postgresql", "query__start", "/usr/bin/postgres", Some(1234)).unwrap();
+    /// ```
+    pub fn attach_usdt(
+        &mut self,
+        provider: &str,
+        name: &str,
+        target: &str,
+        pid: Option<pid_t>,
+    ) -> Result<Vec<(u64, UsdtSpec)>> {
+        let path = resolve_target_path(target, pid);
+        let data = fs::read(&path)?;
+        let elf = Elf::parse(&data)?;
+        let sites: Vec<_> = usdt::parse_probes(&elf, &data)
+            .into_iter()
+            .filter(|probe| probe.provider == provider && probe.name == name)
+            .collect();
+        if sites.is_empty() {
+            return Err(Error::SymbolNotFound(format!("{}:{}", provider, name)));
+        }
+        let maps = match pid {
+            Some(pid) => proc_maps(pid)?,
+            None => Vec::new(),
         };
+
+        let mut specs = Vec::new();
+        for site in sites.iter() {
+            self.attach_uprobe_with_semaphore(
+                None,
+                site.offset,
+                site.semaphore as u32,
+                &path,
+                pid,
+            )?;
+            let addr = if elf.header.e_type == ET_EXEC {
+                Some(site.addr)
+            } else {
+                maps.iter()
+                    .find(|map| {
+                        map.path == path
+                            && map.offset <= site.offset
+                            && site.offset < map.offset + (map.end - map.start)
+                    })
+                    .map(|map| map.start + site.offset - map.offset)
+            };
+            match (addr, site.spec()) {
+                (Some(addr), Some(spec)) => specs.push((addr, spec)),
+                (_, None) => warn!(
+                    "arguments of USDT probe {}:{} at {:#x} are not supported: {}",
+                    provider, name, site.addr, site.args
+                ),
+                (None, _) => {}
+            }
+        }
+        Ok(specs)
+    }
+
+    fn open_perf_event(
+        &self,
+        fn_name: Option<&str>,
+        offset: u64,
+        semaphore: u32,
+        target: &str,
+        pid: Option<pid_t>,
+    ) -> Result<RawFd> {
+        let path = resolve_target_path(target, pid);
         let sym_offset = if let Some(fn_name) = fn_name {
             let data = fs::read(&path)?;
             let parser = ElfSymbols::parse(&data)?;
@@ -967,27 +1878,14 @@ impl UProbe {
             0
         };
         unsafe {
-            let pfd = match self.attach_type {
+            match self.attach_type {
                 ProbeAttachType::Entry => {
-                    perf::open_uprobe_perf_event(&path, offset + sym_offset, semaphore, pid)?
+                    perf::open_uprobe_perf_event(&path, offset + sym_offset, semaphore, pid)
                 }
                 ProbeAttachType::Return => {
-                    perf::open_uretprobe_perf_event(&path, offset + sym_offset, semaphore, pid)?
+                    perf::open_uretprobe_perf_event(&path, offset + sym_offset, semaphore, pid)
                 }
-            };
-            let ret = perf::attach_perf_event(fd, pfd);
-            if ret.is_ok() {
-                self.attachment_points.push(UProbeAttachmentPoint {
-                    fn_name: fn_name.map(String::from),
-                    offset,
-                    target: target.to_owned(),
-                    pid,
-                    pfd,
-                });
-            } else {
-                libc::close(pfd);
             }
-            ret
         }
     }
 
@@ -1030,6 +1928,18 @@ impl UProbe {
     }
 }
 
+/// Resolve the library or binary `target` of uprobes to its path
+fn resolve_target_path(target: &str, pid: Option<pid_t>) -> String {
+    if let Some(pid) = pid {
+        resolve_proc_maps_lib(pid, target).unwrap_or_else(|| target.to_string())
+    } else {
+        match (target.starts_with('/'), LD_SO_CACHE.as_ref()) {
+            (false, Ok(cache)) => cache.resolve(target).unwrap_or(target).to_string(),
+            _ => target.to_owned(),
+        }
+    }
+}
+
 impl TracePoint {
     pub fn attach_trace_point(&mut self, category: &str, name: &str) -> Result<()> {
         let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
@@ -1039,98 +1949,86 @@ impl TracePoint {
         }
     }
 
+    /// Attach the tracepoint program with a [`Link`](./link/struct.Link.html).
+    ///
+    /// The program stays attached while the returned `Link` is alive or
+    /// pinned. This requires Linux 5.15 or later.
+    pub fn link_trace_point(&self, category: &str, name: &str) -> Result<Link> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        let pfd = unsafe { perf::open_tracepoint_perf_event(category, name)? };
+        Link::with_perf_event(fd, pfd)
+    }
+
     pub fn name(&self) -> String {
         self.common.name.to_string()
     }
 }
 
-impl XDP {
-    /// Attach the XDP program.
-    ///
-    /// Attach the XDP program to the given network interface.
+impl PerfEvent {
+    /// Attach the `perf_event` program to every online CPU.
     ///
-    /// # Example
-    /// ```no_run
-    /// # use redbpf::{Module, xdp};
-    /// # let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
-    /// # for uprobe in module.xdps_mut() {
-    /// uprobe.attach_xdp("eth0", xdp::Flags::default()).unwrap();
-    /// # }
-    /// ```
-    pub fn attach_xdp(&mut self, interface: &str, flags: xdp::Flags) -> Result<()> {
-        self.attach_xdp_by_index(if_nametoindex(interface)?, flags)
+    /// A perf event of type `event` is opened on each online CPU for all
+    /// processes and the program is run `sample_freq` times per second on
+    /// each of them.
+    pub fn attach_perf_event(&mut self, event: PerfEventType, sample_freq: u64) -> Result<()> {
+        let cpus = cpus::get_online()?;
+        for cpu in cpus {
+            self.attach_perf_event_on_cpu(event, sample_freq, -1, cpu)?;
+        }
+        Ok(())
     }
 
-    /// Attach the XDP program to interface by it's index.
+    /// Attach the `perf_event` program to a perf event opened on `cpu`.
     ///
-    /// Attach the XDP program to the given network interface.
-    ///
-    /// # Example
-    /// ```no_run
-    /// # use redbpf::{Module, xdp};
-    /// # let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
-    /// # for uprobe in module.xdps_mut() {
-    /// uprobe.attach_xdp_by_index(2, xdp::Flags::default()).unwrap();
-    /// # }
-    /// ```
-    pub fn attach_xdp_by_index(&mut self, ifindex: u32, flags: xdp::Flags) -> Result<()> {
+    /// `pid` and `cpu` follow the semantics of `perf_event_open(2)`: `pid` of
+    /// -1 measures all processes and `cpu` of -1 measures `pid` on any CPU.
+    pub fn attach_perf_event_on_cpu(
+        &mut self,
+        event: PerfEventType,
+        sample_freq: u64,
+        pid: pid_t,
+        cpu: cpus::CpuId,
+    ) -> Result<()> {
         let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
-        self.interfaces.push(ifindex);
-        if let Err(e) = unsafe { attach_xdp(ifindex, fd, flags as u32) } {
-            if let Error::IO(oserr) = e {
-                error!("error attaching xdp to interface #{}: {}", ifindex, oserr);
+        let pfd = open_sampling_perf_event(event, sample_freq, pid, cpu)?;
+        unsafe {
+            if let Err(e) = perf::attach_perf_event(fd, pfd) {
+                let _ = libc::close(pfd);
+                return Err(e);
             }
-            Err(Error::BPF)
-        } else {
-            Ok(())
         }
+        self.pfds.push(pfd);
+        Ok(())
     }
 
-    /// Detach the XDP program.
+    /// Attach the `perf_event` program to a perf event opened on `cpu` with a
+    /// [`Link`](./link/struct.Link.html).
     ///
-    /// Detach the XDP program from the given network interface, if attached.
-    ///
-    /// # Example
-    /// ```no_run
-    /// # use redbpf::{Module, xdp};
-    /// # let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
-    /// # for uprobe in module.xdps_mut() {
-    /// uprobe.attach_xdp("eth0", xdp::Flags::default()).unwrap();
-    /// uprobe.detach_xdp("eth0").unwrap();
-    /// # }
-    /// ```
-    pub fn detach_xdp(&mut self, interface: &str) -> Result<()> {
-        self.detach_xdp_by_index(if_nametoindex(interface)?)
+    /// Arguments are the same as the ones of
+    /// [`attach_perf_event_on_cpu`](#method.attach_perf_event_on_cpu). The
+    /// program stays attached while the returned `Link` is alive or pinned.
+    /// This requires Linux 5.15 or later.
+    pub fn link_perf_event_on_cpu(
+        &self,
+        event: PerfEventType,
+        sample_freq: u64,
+        pid: pid_t,
+        cpu: cpus::CpuId,
+    ) -> Result<Link> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        let pfd = open_sampling_perf_event(event, sample_freq, pid, cpu)?;
+        Link::with_perf_event(fd, pfd)
     }
 
-    /// Detach the XDP program from interface by it's index.
-    ///
-    /// Detach the XDP program from the given network interface, if attached.
-    ///
-    /// # Example
-    /// ```no_run
-    /// # use redbpf::{Module, xdp};
-    /// # let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
-    /// # for uprobe in module.xdps_mut() {
-    /// uprobe.attach_xdp_by_index(23, xdp::Flags::default()).unwrap();
-    /// uprobe.detach_xdp_by_index(23).unwrap();
-    /// # }
-    /// ```
-    pub fn detach_xdp_by_index(&mut self, ifindex: u32) -> Result<()> {
-        // The linear search here isn't great, but self.interfaces will almost always be short.
-        let index = self
-            .interfaces
-            .iter()
-            .enumerate()
-            .find_map(|(i, v)| (*v == ifindex).then(|| i))
-            .ok_or(Error::ProgramNotLoaded)?;
-        if let Err(e) = unsafe { detach_xdp(ifindex) } {
-            if let Error::IO(ref oserr) = e {
-                error!("error detaching xdp from interface #{}: {}", ifindex, oserr);
+    /// Detach the `perf_event` program from all the perf events it is
+    /// attached to.
+    pub fn detach_perf_event(&mut self) -> Result<()> {
+        for pfd in self.pfds.drain(..) {
+            unsafe {
+                let _ = perf::detach_perf_event(pfd);
+                let _ = libc::close(pfd);
             }
-            return Err(e);
         }
-        self.interfaces.swap_remove(index);
         Ok(())
     }
 
@@ -1139,356 +2037,1233 @@ impl XDP {
     }
 }
 
-impl Drop for XDP {
+impl Drop for PerfEvent {
     fn drop(&mut self) {
-        for ifindex in self.interfaces.iter() {
-            let _ = unsafe { detach_xdp(*ifindex) };
-        }
+        let _ = self.detach_perf_event();
     }
 }
 
-unsafe fn open_raw_sock(name: &str) -> Result<RawFd> {
-    let sock = libc::socket(
-        libc::PF_PACKET,
-        libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
-        (libc::ETH_P_ALL as u16).to_be().into(),
-    );
-    if sock < 0 {
-        return Err(Error::IO(io::Error::last_os_error()));
+fn open_sampling_perf_event(
+    event: PerfEventType,
+    sample_freq: u64,
+    pid: pid_t,
+    cpu: cpus::CpuId,
+) -> Result<RawFd> {
+    let (type_, config) = match event {
+        PerfEventType::CpuClock => (
+            sys::perf::perf_type_id_PERF_TYPE_SOFTWARE,
+            sys::perf::perf_sw_ids_PERF_COUNT_SW_CPU_CLOCK,
+        ),
+        PerfEventType::CpuCycles => (
+            sys::perf::perf_type_id_PERF_TYPE_HARDWARE,
+            sys::perf::perf_hw_id_PERF_COUNT_HW_CPU_CYCLES,
+        ),
+        PerfEventType::Software(config) => (sys::perf::perf_type_id_PERF_TYPE_SOFTWARE, config),
+        PerfEventType::Hardware(config) => (sys::perf::perf_type_id_PERF_TYPE_HARDWARE, config),
+    };
+    unsafe { perf::open_sampling_perf_event(type_, config as u64, sample_freq, pid, cpu) }
+}
+
+impl Trampoline {
+    /// Attach the `fentry` or `fexit` program to the kernel function it was
+    /// defined for.
+    pub fn attach_trampoline(&mut self) -> Result<()> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        if self.link_fd.is_some() {
+            return Err(Error::ProgramAlreadyLinked);
+        }
+        self.link_fd = Some(raw_tracepoint_open(None, fd, &self.common.name)?);
+        Ok(())
     }
 
-    // Do not bind on empty interface names
-    if name.is_empty() {
-        return Ok(sock);
+    /// Attach the `fentry` or `fexit` program with a
+    /// [`Link`](./link/struct.Link.html).
+    ///
+    /// The program stays attached while the returned `Link` is alive or
+    /// pinned.
+    pub fn link_trampoline(&self) -> Result<Link> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        Ok(Link::from_fd(raw_tracepoint_open(
+            None,
+            fd,
+            &self.common.name,
+        )?))
     }
 
-    let mut sll = mem::zeroed::<libc::sockaddr_ll>();
-    sll.sll_family = libc::AF_PACKET as u16;
-    let ciface = CString::new(name).unwrap();
-    sll.sll_ifindex = libc::if_nametoindex(ciface.as_ptr()) as i32;
-    if sll.sll_ifindex == 0 {
-        libc::close(sock);
-        return Err(Error::IO(io::Error::last_os_error()));
+    /// Detach the `fentry` or `fexit` program from the kernel function.
+    pub fn detach_trampoline(&mut self) -> Result<()> {
+        if let Some(link_fd) = self.link_fd.take() {
+            unsafe {
+                let _ = libc::close(link_fd);
+            }
+        }
+        Ok(())
     }
 
-    sll.sll_protocol = (libc::ETH_P_ALL as u16).to_be() as u16;
-    if libc::bind(
-        sock,
-        &sll as *const _ as *const _,
-        mem::size_of_val(&sll) as u32,
-    ) < 0
-    {
-        libc::close(sock);
-        return Err(Error::IO(io::Error::last_os_error()));
+    pub fn name(&self) -> String {
+        self.common.name.to_string()
     }
 
-    Ok(sock)
+    pub fn attach_type_str(&self) -> &'static str {
+        match self.attach_type {
+            ProbeAttachType::Entry => "fentry",
+            ProbeAttachType::Return => "fexit",
+        }
+    }
 }
 
-fn if_nametoindex(dev_name: &str) -> Result<u32> {
-    let ciface = CString::new(dev_name).unwrap();
-    let ifindex = unsafe { libc::if_nametoindex(ciface.as_ptr()) };
-    if ifindex == 0 {
-        return Err(Error::IO(io::Error::last_os_error()));
+/// Attach `prog_fd` to the raw tracepoint `name`, or to the BTF-enabled hook
+/// that the program was loaded for if `name` is `None`
+fn raw_tracepoint_open(name: Option<&str>, prog_fd: RawFd, prog_name: &str) -> Result<RawFd> {
+    let cname = name.map(CString::new).transpose()?;
+    let link_fd = unsafe {
+        bpf_raw_tracepoint_open(cname.as_ref().map_or(ptr::null(), |n| n.as_ptr()), prog_fd)
+    };
+    if link_fd < 0 {
+        let err = io::Error::last_os_error();
+        error!(
+            "error on bpf_raw_tracepoint_open for {}: {}",
+            prog_name, err
+        );
+        return Err(Error::IO(err));
+    }
+    Ok(link_fd)
+}
+
+impl Drop for Trampoline {
+    fn drop(&mut self) {
+        let _ = self.detach_trampoline();
     }
-    Ok(ifindex)
 }
 
-unsafe fn attach_xdp(ifindex: u32, progfd: libc::c_int, flags: libc::c_uint) -> Result<()> {
-    if libbpf_sys::bpf_set_link_xdp_fd(ifindex as i32, progfd, flags) != 0 {
-        return Err(Error::IO(io::Error::last_os_error()));
+impl RawTracePoint {
+    /// Attach the raw tracepoint program to the tracepoint `name`.
+    ///
+    /// Unlike [`TracePoint::attach_trace_point`] no category is needed,
+    /// e.g. `sched_switch` instead of `sched` and `sched_switch`.
+    ///
+    /// [`TracePoint::attach_trace_point`]: ./struct.TracePoint.html#method.attach_trace_point
+    pub fn attach_raw_trace_point(&mut self, name: &str) -> Result<()> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        if self.link_fd.is_some() {
+            return Err(Error::ProgramAlreadyLinked);
+        }
+        self.link_fd = Some(raw_tracepoint_open(Some(name), fd, name)?);
+        Ok(())
+    }
+
+    /// Attach the raw tracepoint program to the tracepoint `name` with a
+    /// [`Link`](./link/struct.Link.html).
+    ///
+    /// The program stays attached while the returned `Link` is alive or
+    /// pinned.
+    pub fn link_raw_trace_point(&self, name: &str) -> Result<Link> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        Ok(Link::from_fd(raw_tracepoint_open(Some(name), fd, name)?))
+    }
+
+    /// Detach the raw tracepoint program.
+    pub fn detach_raw_trace_point(&mut self) -> Result<()> {
+        if let Some(link_fd) = self.link_fd.take() {
+            unsafe {
+                let _ = libc::close(link_fd);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn name(&self) -> String {
+        self.common.name.to_string()
     }
-    Ok(())
 }
 
-unsafe fn detach_xdp(ifindex: u32) -> Result<()> {
-    attach_xdp(ifindex, -1, 0)
+impl Drop for RawTracePoint {
+    fn drop(&mut self) {
+        let _ = self.detach_raw_trace_point();
+    }
 }
 
-impl SocketFilter {
-    /// Attach the socket filter program.
+impl BtfTracePoint {
+    /// Attach the `tp_btf` program to the tracepoint it was defined for.
+    pub fn attach_btf_trace_point(&mut self) -> Result<()> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        if self.link_fd.is_some() {
+            return Err(Error::ProgramAlreadyLinked);
+        }
+        self.link_fd = Some(raw_tracepoint_open(None, fd, &self.common.name)?);
+        Ok(())
+    }
+
+    /// Attach the `tp_btf` program with a [`Link`](./link/struct.Link.html).
     ///
-    /// Attach the socket filter program to the given network interface.
+    /// The program stays attached while the returned `Link` is alive or
+    /// pinned.
+    pub fn link_btf_trace_point(&self) -> Result<Link> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        Ok(Link::from_fd(raw_tracepoint_open(
+            None,
+            fd,
+            &self.common.name,
+        )?))
+    }
+
+    /// Detach the `tp_btf` program.
+    pub fn detach_btf_trace_point(&mut self) -> Result<()> {
+        if let Some(link_fd) = self.link_fd.take() {
+            unsafe {
+                let _ = libc::close(link_fd);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn name(&self) -> String {
+        self.common.name.to_string()
+    }
+}
+
+impl Drop for BtfTracePoint {
+    fn drop(&mut self) {
+        let _ = self.detach_btf_trace_point();
+    }
+}
+
+impl Lsm {
+    /// Attach the LSM program to the LSM hook it was defined for.
+    pub fn attach_lsm(&mut self) -> Result<()> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        if self.link_fd.is_some() {
+            return Err(Error::ProgramAlreadyLinked);
+        }
+        let link_fd = unsafe { bpf_link_create(fd, 0, BPF_LSM_MAC, ptr::null()) };
+        if link_fd < 0 {
+            let err = io::Error::last_os_error();
+            error!(
+                "error on bpf_link_create for LSM program {}: {}",
+                self.common.name, err
+            );
+            return Err(Error::IO(err));
+        }
+        self.link_fd = Some(link_fd);
+        Ok(())
+    }
+
+    /// Attach the LSM program with a [`Link`](./link/struct.Link.html).
+    ///
+    /// The program stays attached while the returned `Link` is alive or
+    /// pinned.
+    pub fn link_lsm(&self) -> Result<Link> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        Link::create(fd, 0, BPF_LSM_MAC, 0)
+    }
+
+    /// Detach the LSM program from the LSM hook.
+    pub fn detach_lsm(&mut self) -> Result<()> {
+        if let Some(link_fd) = self.link_fd.take() {
+            unsafe {
+                let _ = libc::close(link_fd);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn name(&self) -> String {
+        self.common.name.to_string()
+    }
+}
+
+impl Drop for Lsm {
+    fn drop(&mut self) {
+        let _ = self.detach_lsm();
+    }
+}
+
+impl XDP {
+    /// Attach the XDP program.
+    ///
+    /// Attach the XDP program to the given network interface.
     ///
     /// # Example
     /// ```no_run
-    /// use redbpf::Module;
-    /// let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
-    /// for sf in module.socket_filters_mut() {
-    ///     sf.attach_socket_filter("eth0").unwrap();
-    /// }
+    /// # use redbpf::{Module, xdp};
+    /// # let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
+    /// # for uprobe in module.xdps_mut() {
+    /// uprobe.attach_xdp("eth0", xdp::Flags::default()).unwrap();
+    /// # }
     /// ```
-    pub fn attach_socket_filter(&mut self, interface: &str) -> Result<RawFd> {
+    pub fn attach_xdp(&mut self, interface: &str, flags: xdp::Flags) -> Result<()> {
+        self.attach_xdp_by_index(if_nametoindex(interface)?, flags)
+    }
+
+    /// Attach the XDP program to interface by it's index.
+    ///
+    /// Attach the XDP program to the given network interface.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use redbpf::{Module, xdp};
+    /// # let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
+    /// # for uprobe in module.xdps_mut() {
+    /// uprobe.attach_xdp_by_index(2, xdp::Flags::default()).unwrap();
+    /// # }
+    /// ```
+    pub fn attach_xdp_by_index(&mut self, ifindex: u32, flags: xdp::Flags) -> Result<()> {
         let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
-        unsafe {
-            let sfd = open_raw_sock(interface)?;
-            if libc::setsockopt(
-                sfd,
-                libc::SOL_SOCKET,
-                libc::SO_ATTACH_BPF,
-                &fd as *const _ as *const _,
-                mem::size_of_val(&fd) as u32,
-            ) < 0
-            {
-                libc::close(sfd);
-                Err(Error::IO(io::Error::last_os_error()))
-            } else {
-                Ok(sfd)
+        self.interfaces.push(ifindex);
+        if let Err(e) = unsafe { attach_xdp(ifindex, fd, flags as u32) } {
+            error!("error attaching xdp to interface #{}: {}", ifindex, e);
+            Err(e)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Attach the XDP program to the given network interface with a
+    /// [`Link`](./link/struct.Link.html).
+    ///
+    /// Unlike [`attach_xdp`](#method.attach_xdp), the program can not be
+    /// replaced by other processes while the returned `Link` is alive or
+    /// pinned. This requires Linux 5.9 or later.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use redbpf::{Module, xdp};
+    /// # let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
+    /// # for prog in module.xdps() {
+    /// let mut link = prog.link_xdp("eth0", xdp::Flags::default()).unwrap();
+    /// link.pin("/sys/fs/bpf/xdp_eth0").unwrap();
+    /// # }
+    /// ```
+    pub fn link_xdp(&self, interface: &str, flags: xdp::Flags) -> Result<Link> {
+        self.link_xdp_by_index(if_nametoindex(interface)?, flags)
+    }
+
+    /// Attach the XDP program to interface by it's index with a
+    /// [`Link`](./link/struct.Link.html).
+    ///
+    /// Only one of the mode flags, `SkbMode`, `DrvMode` and `HwMode`, can be
+    /// passed as `flags`. `UpdateIfNoExist` is not needed because a link can
+    /// not replace the program attached to the interface anyway.
+    pub fn link_xdp_by_index(&self, ifindex: u32, flags: xdp::Flags) -> Result<Link> {
+        use xdp::Flags::*;
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        if !matches!(flags, Unset | SkbMode | DrvMode | HwMode) {
+            error!(
+                "xdp link can not be created with flags {:?}. only one mode flag is allowed",
+                flags
+            );
+            return Err(Error::IO(io::Error::from(ErrorKind::InvalidInput)));
+        }
+        Link::create(fd, ifindex as RawFd, BPF_XDP, flags as u32)
+    }
+
+    /// Detach the XDP program.
+    ///
+    /// Detach the XDP program from the given network interface, if attached.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use redbpf::{Module, xdp};
+    /// # let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
+    /// # for uprobe in module.xdps_mut() {
+    /// uprobe.attach_xdp("eth0", xdp::Flags::default()).unwrap();
+    /// uprobe.detach_xdp("eth0").unwrap();
+    /// # }
+    /// ```
+    pub fn detach_xdp(&mut self, interface: &str) -> Result<()> {
+        self.detach_xdp_by_index(if_nametoindex(interface)?)
+    }
+
+    /// Detach the XDP program from interface by it's index.
+    ///
+    /// Detach the XDP program from the given network interface, if attached.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use redbpf::{Module, xdp};
+    /// # let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
+    /// # for uprobe in module.xdps_mut() {
+    /// uprobe.attach_xdp_by_index(23, xdp::Flags::default()).unwrap();
+    /// uprobe.detach_xdp_by_index(23).unwrap();
+    /// # }
+    /// ```
+    pub fn detach_xdp_by_index(&mut self, ifindex: u32) -> Result<()> {
+        // The linear search here isn't great, but self.interfaces will almost always be short.
+        let index = self
+            .interfaces
+            .iter()
+            .enumerate()
+            .find_map(|(i, v)| (*v == ifindex).then(|| i))
+            .ok_or(Error::ProgramNotLoaded)?;
+        if let Err(e) = unsafe { detach_xdp(ifindex) } {
+            if let Error::IO(ref oserr) = e {
+                error!("error detaching xdp from interface #{}: {}", ifindex, oserr);
             }
+            return Err(e);
         }
+        self.interfaces.swap_remove(index);
+        Ok(())
+    }
+
+    pub fn name(&self) -> String {
+        self.common.name.to_string()
+    }
+}
+
+impl Drop for XDP {
+    fn drop(&mut self) {
+        for ifindex in self.interfaces.iter() {
+            let _ = unsafe { detach_xdp(*ifindex) };
+        }
+    }
+}
+
+impl TcAction {
+    /// Attach the `tc_action` program to `interface`.
+    ///
+    /// The `clsact` qdisc is created if the interface does not have one yet,
+    /// and the program is added to it as a direct-action `bpf` filter of
+    /// `attach_point` with the given `priority` and `handle`. Both of them
+    /// should be non-zero so that the filter can be identified later.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use redbpf::{Module, tc};
+    /// # let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
+    /// # for tc_action in module.tc_actions_mut() {
+    /// tc_action.attach_tc("eth0", tc::AttachPoint::Egress, 1, 1).unwrap();
+    /// # }
+    /// ```
+    pub fn attach_tc(
+        &mut self,
+        interface: &str,
+        attach_point: tc::AttachPoint,
+        priority: u16,
+        handle: u32,
+    ) -> Result<()> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        let ifindex = if_nametoindex(interface)?;
+        tc::create_clsact_by_index(ifindex)?;
+        tc::attach_filter(
+            ifindex,
+            attach_point,
+            priority,
+            handle,
+            fd,
+            &self.common.name,
+        )?;
+        self.attachments
+            .push((ifindex, attach_point, priority, handle));
+        Ok(())
+    }
+
+    /// Detach the `tc_action` program from `attach_point` of `interface`.
+    ///
+    /// The `clsact` qdisc is left in place.
+    pub fn detach_tc(&mut self, interface: &str, attach_point: tc::AttachPoint) -> Result<()> {
+        let ifindex = if_nametoindex(interface)?;
+        let mut result = Ok(());
+        let mut remaining = Vec::new();
+        for (idx, ap, priority, handle) in self.attachments.drain(..) {
+            if idx == ifindex && ap == attach_point {
+                if let Err(e) = tc::detach_filter(idx, ap, priority, handle) {
+                    result = Err(e);
+                }
+            } else {
+                remaining.push((idx, ap, priority, handle));
+            }
+        }
+        self.attachments = remaining;
+        result
+    }
+
+    pub fn name(&self) -> String {
+        self.common.name.to_string()
+    }
+}
+
+impl Drop for TcAction {
+    fn drop(&mut self) {
+        for (ifindex, attach_point, priority, handle) in self.attachments.drain(..) {
+            let _ = tc::detach_filter(ifindex, attach_point, priority, handle);
+        }
+    }
+}
+
+unsafe fn open_raw_sock(name: &str) -> Result<RawFd> {
+    let sock = libc::socket(
+        libc::PF_PACKET,
+        libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+        (libc::ETH_P_ALL as u16).to_be().into(),
+    );
+    if sock < 0 {
+        return Err(Error::IO(io::Error::last_os_error()));
+    }
+
+    // Do not bind on empty interface names
+    if name.is_empty() {
+        return Ok(sock);
+    }
+
+    let mut sll = mem::zeroed::<libc::sockaddr_ll>();
+    sll.sll_family = libc::AF_PACKET as u16;
+    let ciface = CString::new(name).unwrap();
+    sll.sll_ifindex = libc::if_nametoindex(ciface.as_ptr()) as i32;
+    if sll.sll_ifindex == 0 {
+        libc::close(sock);
+        return Err(Error::IO(io::Error::last_os_error()));
+    }
+
+    sll.sll_protocol = (libc::ETH_P_ALL as u16).to_be() as u16;
+    if libc::bind(
+        sock,
+        &sll as *const _ as *const _,
+        mem::size_of_val(&sll) as u32,
+    ) < 0
+    {
+        libc::close(sock);
+        return Err(Error::IO(io::Error::last_os_error()));
+    }
+
+    Ok(sock)
+}
+
+fn if_nametoindex(dev_name: &str) -> Result<u32> {
+    let ciface = CString::new(dev_name).unwrap();
+    let ifindex = unsafe { libc::if_nametoindex(ciface.as_ptr()) };
+    if ifindex == 0 {
+        return Err(Error::IO(io::Error::last_os_error()));
+    }
+    Ok(ifindex)
+}
+
+unsafe fn attach_xdp(ifindex: u32, progfd: libc::c_int, flags: libc::c_uint) -> Result<()> {
+    if libbpf_sys::bpf_set_link_xdp_fd(ifindex as i32, progfd, flags) != 0 {
+        return Err(Error::IO(io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+unsafe fn detach_xdp(ifindex: u32) -> Result<()> {
+    attach_xdp(ifindex, -1, 0)
+}
+
+impl SocketFilter {
+    /// Attach the socket filter program.
+    ///
+    /// Attach the socket filter program to the given network interface.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use redbpf::Module;
+    /// let mut module = Module::parse(&std::fs::read("file.elf").unwrap()).unwrap();
+    /// for sf in module.socket_filters_mut() {
+    ///     sf.attach_socket_filter("eth0").unwrap();
+    /// }
+    /// ```
+    pub fn attach_socket_filter(&mut self, interface: &str) -> Result<RawFd> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        unsafe {
+            let sfd = open_raw_sock(interface)?;
+            if libc::setsockopt(
+                sfd,
+                libc::SOL_SOCKET,
+                libc::SO_ATTACH_BPF,
+                &fd as *const _ as *const _,
+                mem::size_of_val(&fd) as u32,
+            ) < 0
+            {
+                libc::close(sfd);
+                Err(Error::IO(io::Error::last_os_error()))
+            } else {
+                Ok(sfd)
+            }
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.common.name.to_string()
+    }
+}
+
+impl SkLookup {
+    /// Attach the `sk_lookup` to the given network namespace.
+    ///
+    /// In most cases it should be attached to `/proc/self/ns/net`.
+    pub fn attach_sk_lookup(&mut self, namespace: &str) -> Result<()> {
+        if self.link.is_some() {
+            return Err(Error::ProgramAlreadyLinked);
+        }
+
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        unsafe {
+            let namespace = CString::new(namespace)?;
+            let nfd = libc::open(namespace.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC);
+            if nfd < 0 {
+                return Err(Error::IO(io::Error::last_os_error()));
+            }
+
+            let lfd = bpf_link_create(fd, nfd, BPF_SK_LOOKUP, ptr::null());
+            if lfd < 0 {
+                libc::close(nfd);
+                return Err(Error::IO(io::Error::last_os_error()));
+            }
+
+            self.link = Some((nfd, lfd));
+        }
+
+        Ok(())
+    }
+
+    /// Attach the `sk_lookup` to the given network namespace with a
+    /// [`Link`](./link/struct.Link.html).
+    ///
+    /// The program stays attached while the returned `Link` is alive or
+    /// pinned.
+    pub fn link_sk_lookup(&self, namespace: &str) -> Result<Link> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        unsafe {
+            let namespace = CString::new(namespace)?;
+            let nfd = libc::open(namespace.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC);
+            if nfd < 0 {
+                return Err(Error::IO(io::Error::last_os_error()));
+            }
+            // the link holds a reference to the namespace by itself
+            let ret = Link::create(fd, nfd, BPF_SK_LOOKUP, 0);
+            libc::close(nfd);
+            ret
+        }
+    }
+}
+
+impl CGroup {
+    /// Attach the program to the cgroup v2 directory `path`.
+    ///
+    /// `attach_type` must be the hook that the program was defined for. Only
+    /// `cgroup_skb` programs can be attached to both `Ingress` and `Egress`
+    /// regardless of their definition. The hook of programs restored from
+    /// file descriptors is not checked since it is not known. Pass
+    /// [`Flags::AllowMulti`](./cgroup/enum.Flags.html#variant.AllowMulti) to
+    /// run the program alongside the other programs attached to the cgroup.
+    pub fn attach_cgroup(
+        &mut self,
+        path: &str,
+        attach_type: cgroup::AttachType,
+        flags: cgroup::Flags,
+    ) -> Result<()> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        self.check_attach_type(attach_type)?;
+
+        unsafe {
+            let cgfd = open_cgroup(path)?;
+            if bpf_prog_attach(fd, cgfd, attach_type.into(), flags as u32) < 0 {
+                let err = io::Error::last_os_error();
+                libc::close(cgfd);
+                return Err(Error::IO(err));
+            }
+
+            self.attachments.push((cgfd, attach_type));
+        }
+
+        Ok(())
+    }
+
+    /// Attach the program to the cgroup v2 directory `path` with a
+    /// [`Link`](./link/struct.Link.html).
+    ///
+    /// The program runs alongside the other programs attached to the cgroup
+    /// and it stays attached while the returned `Link` is alive or pinned.
+    pub fn link_cgroup(&self, path: &str, attach_type: cgroup::AttachType) -> Result<Link> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        self.check_attach_type(attach_type)?;
+        unsafe {
+            let cgfd = open_cgroup(path)?;
+            // the link holds a reference to the cgroup by itself
+            let ret = Link::create(fd, cgfd, attach_type.into(), 0);
+            libc::close(cgfd);
+            ret
+        }
+    }
+
+    fn check_attach_type(&self, attach_type: cgroup::AttachType) -> Result<()> {
+        use cgroup::AttachType::{Egress, Ingress};
+        let expected = match self.expected_attach_type {
+            Some(expected) => expected,
+            None => return Ok(()),
+        };
+        // the kernel checks the attach type against the expected attach type
+        // for all cgroup programs except for cgroup_skb
+        if attach_type != expected
+            && !(matches!(expected, Ingress | Egress) && matches!(attach_type, Ingress | Egress))
+        {
+            error!(
+                "program {} defined for {:?} can not be attached to {:?}",
+                self.common.name, expected, attach_type
+            );
+            return Err(Error::IO(io::Error::from(ErrorKind::InvalidInput)));
+        }
+        Ok(())
+    }
+
+    /// Detach the program from all the cgroups it is attached to.
+    pub fn detach_cgroup(&mut self) -> Result<()> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        for (cgfd, attach_type) in self.attachments.drain(..) {
+            unsafe {
+                let _ = bpf_prog_detach2(fd, cgfd, attach_type.into());
+                libc::close(cgfd);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn name(&self) -> String {
+        self.common.name.to_string()
+    }
+
+    /// Returns the hook the program was defined for, or `None` if the
+    /// program is restored from a file descriptor.
+    pub fn expected_attach_type(&self) -> Option<cgroup::AttachType> {
+        self.expected_attach_type
+    }
+}
+
+unsafe fn open_cgroup(path: &str) -> Result<RawFd> {
+    let path = CString::new(path)?;
+    let cgfd = libc::open(
+        path.as_ptr(),
+        libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC,
+    );
+    if cgfd < 0 {
+        return Err(Error::IO(io::Error::last_os_error()));
+    }
+    Ok(cgfd)
+}
+
+impl Drop for CGroup {
+    fn drop(&mut self) {
+        let _ = self.detach_cgroup();
+    }
+}
+
+impl Drop for SkLookup {
+    fn drop(&mut self) {
+        if let Some((nfd, lfd)) = self.link.take() {
+            unsafe {
+                libc::close(lfd);
+                libc::close(nfd);
+            }
+        }
+    }
+}
+
+impl Module {
+    pub fn parse(bytes: &[u8]) -> Result<Module> {
+        ModuleBuilder::parse(bytes)?.to_module()
+    }
+
+    pub fn map(&self, name: &str) -> Option<&Map> {
+        self.maps.iter().find(|m| m.name == name)
+    }
+
+    pub fn map_mut(&mut self, name: &str) -> Option<&mut Map> {
+        self.maps.iter_mut().find(|m| m.name == name)
+    }
+
+    /// Read the value of a global variable whose name is `var_name`
+    ///
+    /// The variable is defined in `.rodata`, `.data` or `.bss` section.
+    pub fn global_variable<T: Copy>(&self, var_name: &str) -> Result<T> {
+        self.global_variable_map(var_name)?
+            .global_variable(var_name)
+    }
+
+    /// Write `value` to a global variable whose name is `var_name`
+    ///
+    /// Only variables of `.data` and `.bss` sections can be written after
+    /// loading.
+    pub fn set_global_variable<T: Copy>(&self, var_name: &str, value: T) -> Result<()> {
+        self.global_variable_map(var_name)?
+            .set_global_variable(var_name, value)
+    }
+
+    /// Sum up the runtime statistics of all the loaded programs
+    pub fn stats(&self) -> Result<ProgramStats> {
+        let mut total = ProgramStats::default();
+        for prog in self.programs.iter().filter(|p| p.fd().is_some()) {
+            total += prog.stats()?;
+        }
+        Ok(total)
+    }
+
+    fn global_variable_map(&self, var_name: &str) -> Result<&Map> {
+        self.maps
+            .iter()
+            .find(|m| m.global_variables.contains_key(var_name))
+            .ok_or_else(|| {
+                error!("global variable `{}' not found", var_name);
+                Error::Map
+            })
+    }
+
+    pub fn program(&self, name: &str) -> Option<&Program> {
+        self.programs.iter().find(|p| p.name() == name)
+    }
+
+    pub fn program_mut(&mut self, name: &str) -> Option<&mut Program> {
+        self.programs.iter_mut().find(|p| p.name() == name)
+    }
+
+    pub fn kprobes(&self) -> impl Iterator<Item = &KProbe> {
+        use Program::*;
+        self.programs.iter().filter_map(|prog| match prog {
+            KProbe(p) | KRetProbe(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn kprobes_mut(&mut self) -> impl Iterator<Item = &mut KProbe> {
+        use Program::*;
+        self.programs.iter_mut().filter_map(|prog| match prog {
+            KProbe(p) | KRetProbe(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn kprobe_mut(&mut self, name: &str) -> Option<&mut KProbe> {
+        self.kprobes_mut().find(|p| p.common.name == name)
+    }
+
+    pub fn uprobes(&self) -> impl Iterator<Item = &UProbe> {
+        use Program::*;
+        self.programs.iter().filter_map(|prog| match prog {
+            UProbe(p) | URetProbe(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn uprobes_mut(&mut self) -> impl Iterator<Item = &mut UProbe> {
+        use Program::*;
+        self.programs.iter_mut().filter_map(|prog| match prog {
+            UProbe(p) | URetProbe(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn uprobe_mut(&mut self, name: &str) -> Option<&mut UProbe> {
+        self.uprobes_mut().find(|p| p.common.name == name)
+    }
+
+    pub fn xdps(&self) -> impl Iterator<Item = &XDP> {
+        use Program::*;
+        self.programs.iter().filter_map(|prog| match prog {
+            XDP(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn xdps_mut(&mut self) -> impl Iterator<Item = &mut XDP> {
+        use Program::*;
+        self.programs.iter_mut().filter_map(|prog| match prog {
+            XDP(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn xdp_mut(&mut self, name: &str) -> Option<&mut XDP> {
+        self.xdps_mut().find(|p| p.common.name == name)
+    }
+
+    pub fn socket_filters(&self) -> impl Iterator<Item = &SocketFilter> {
+        use Program::*;
+        self.programs.iter().filter_map(|prog| match prog {
+            SocketFilter(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn socket_filters_mut(&mut self) -> impl Iterator<Item = &mut SocketFilter> {
+        use Program::*;
+        self.programs.iter_mut().filter_map(|prog| match prog {
+            SocketFilter(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn socket_filter_mut(&mut self, name: &str) -> Option<&mut SocketFilter> {
+        self.socket_filters_mut().find(|p| p.common.name == name)
+    }
+
+    pub fn tc_actions(&self) -> impl Iterator<Item = &TcAction> {
+        use Program::*;
+        self.programs.iter().filter_map(|prog| match prog {
+            TcAction(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn tc_actions_mut(&mut self) -> impl Iterator<Item = &mut TcAction> {
+        use Program::*;
+        self.programs.iter_mut().filter_map(|prog| match prog {
+            TcAction(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn tc_action_mut(&mut self, name: &str) -> Option<&mut TcAction> {
+        self.tc_actions_mut().find(|p| p.common.name == name)
+    }
+
+    pub fn trace_points(&self) -> impl Iterator<Item = &TracePoint> {
+        use Program::*;
+        self.programs.iter().filter_map(|prog| match prog {
+            TracePoint(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn trace_points_mut(&mut self) -> impl Iterator<Item = &mut TracePoint> {
+        use Program::*;
+        self.programs.iter_mut().filter_map(|prog| match prog {
+            TracePoint(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn trace_point_mut(&mut self, name: &str) -> Option<&mut TracePoint> {
+        self.trace_points_mut().find(|p| p.common.name == name)
+    }
+
+    pub fn raw_trace_points(&self) -> impl Iterator<Item = &RawTracePoint> {
+        use Program::*;
+        self.programs.iter().filter_map(|prog| match prog {
+            RawTracePoint(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn raw_trace_points_mut(&mut self) -> impl Iterator<Item = &mut RawTracePoint> {
+        use Program::*;
+        self.programs.iter_mut().filter_map(|prog| match prog {
+            RawTracePoint(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn raw_trace_point_mut(&mut self, name: &str) -> Option<&mut RawTracePoint> {
+        self.raw_trace_points_mut().find(|p| p.common.name == name)
+    }
+
+    pub fn btf_trace_points(&self) -> impl Iterator<Item = &BtfTracePoint> {
+        use Program::*;
+        self.programs.iter().filter_map(|prog| match prog {
+            BtfTracePoint(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn btf_trace_points_mut(&mut self) -> impl Iterator<Item = &mut BtfTracePoint> {
+        use Program::*;
+        self.programs.iter_mut().filter_map(|prog| match prog {
+            BtfTracePoint(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn btf_trace_point_mut(&mut self, name: &str) -> Option<&mut BtfTracePoint> {
+        self.btf_trace_points_mut().find(|p| p.common.name == name)
+    }
+
+    pub fn stream_parsers(&self) -> impl Iterator<Item = &StreamParser> {
+        use Program::*;
+        self.programs.iter().filter_map(|prog| match prog {
+            StreamParser(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn stream_parsers_mut(&mut self) -> impl Iterator<Item = &mut StreamParser> {
+        use Program::*;
+        self.programs.iter_mut().filter_map(|prog| match prog {
+            StreamParser(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    pub fn stream_parser_mut(&mut self, name: &str) -> Option<&mut StreamParser> {
+        self.stream_parsers_mut().find(|p| p.common.name == name)
+    }
+
+    pub fn stream_verdicts(&self) -> impl Iterator<Item = &StreamVerdict> {
+        use Program::*;
+        self.programs.iter().filter_map(|prog| match prog {
+            StreamVerdict(p) => Some(p),
+            _ => None,
+        })
     }
 
-    pub fn name(&self) -> String {
-        self.common.name.to_string()
+    pub fn stream_verdicts_mut(&mut self) -> impl Iterator<Item = &mut StreamVerdict> {
+        use Program::*;
+        self.programs.iter_mut().filter_map(|prog| match prog {
+            StreamVerdict(p) => Some(p),
+            _ => None,
+        })
     }
-}
-
-impl SkLookup {
-    /// Attach the `sk_lookup` to the given network namespace.
-    ///
-    /// In most cases it should be attached to `/proc/self/ns/net`.
-    pub fn attach_sk_lookup(&mut self, namespace: &str) -> Result<()> {
-        if self.link.is_some() {
-            return Err(Error::ProgramAlreadyLinked);
-        }
 
-        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
-        unsafe {
-            let namespace = CString::new(namespace)?;
-            let nfd = libc::open(namespace.as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC);
-            if nfd < 0 {
-                return Err(Error::IO(io::Error::last_os_error()));
-            }
+    pub fn stream_verdict_mut(&mut self, name: &str) -> Option<&mut StreamVerdict> {
+        self.stream_verdicts_mut().find(|p| p.common.name == name)
+    }
 
-            let lfd = bpf_link_create(fd, nfd, BPF_SK_LOOKUP, ptr::null());
-            if lfd < 0 {
-                libc::close(nfd);
-                return Err(Error::IO(io::Error::last_os_error()));
-            }
+    pub fn sk_msgs(&self) -> impl Iterator<Item = &SkMsg> {
+        use Program::*;
+        self.programs.iter().filter_map(|prog| match prog {
+            SkMsg(p) => Some(p),
+            _ => None,
+        })
+    }
 
-            self.link = Some((nfd, lfd));
-        }
+    pub fn sk_msgs_mut(&mut self) -> impl Iterator<Item = &mut SkMsg> {
+        use Program::*;
+        self.programs.iter_mut().filter_map(|prog| match prog {
+            SkMsg(p) => Some(p),
+            _ => None,
+        })
+    }
 
-        Ok(())
+    pub fn sk_msg_mut(&mut self, name: &str) -> Option<&mut SkMsg> {
+        self.sk_msgs_mut().find(|p| p.common.name == name)
     }
-}
 
-impl Drop for SkLookup {
-    fn drop(&mut self) {
-        if let Some((nfd, lfd)) = self.link.take() {
-            unsafe {
-                libc::close(lfd);
-                libc::close(nfd);
-            }
-        }
+    pub fn sk_lookups(&self) -> impl Iterator<Item = &SkLookup> {
+        use Program::*;
+        self.programs.iter().filter_map(|prog| match prog {
+            SkLookup(p) => Some(p),
+            _ => None,
+        })
     }
-}
 
-impl Module {
-    pub fn parse(bytes: &[u8]) -> Result<Module> {
-        ModuleBuilder::parse(bytes)?.to_module()
+    pub fn sk_lookups_mut(&mut self) -> impl Iterator<Item = &mut SkLookup> {
+        use Program::*;
+        self.programs.iter_mut().filter_map(|prog| match prog {
+            SkLookup(p) => Some(p),
+            _ => None,
+        })
     }
 
-    pub fn map(&self, name: &str) -> Option<&Map> {
-        self.maps.iter().find(|m| m.name == name)
+    pub fn sk_lookup_mut(&mut self, name: &str) -> Option<&mut SkLookup> {
+        self.sk_lookups_mut().find(|p| p.common.name == name)
     }
 
-    pub fn map_mut(&mut self, name: &str) -> Option<&mut Map> {
-        self.maps.iter_mut().find(|m| m.name == name)
+    pub fn bpf_iters(&self) -> impl Iterator<Item = &Iter> {
+        use Program::*;
+        self.programs.iter().filter_map(|prog| match prog {
+            Iter(p) => Some(p),
+            _ => None,
+        })
     }
 
-    pub fn program(&self, name: &str) -> Option<&Program> {
-        self.programs.iter().find(|p| p.name() == name)
+    pub fn bpf_iters_mut(&mut self) -> impl Iterator<Item = &mut Iter> {
+        use Program::*;
+        self.programs.iter_mut().filter_map(|prog| match prog {
+            Iter(p) => Some(p),
+            _ => None,
+        })
     }
 
-    pub fn program_mut(&mut self, name: &str) -> Option<&mut Program> {
-        self.programs.iter_mut().find(|p| p.name() == name)
+    pub fn bpf_iter_mut(&mut self, name: &str) -> Option<&mut Iter> {
+        self.bpf_iters_mut().find(|p| p.common.name == name)
     }
 
-    pub fn kprobes(&self) -> impl Iterator<Item = &KProbe> {
+    pub fn task_iters(&self) -> impl Iterator<Item = &TaskIter> {
         use Program::*;
         self.programs.iter().filter_map(|prog| match prog {
-            KProbe(p) | KRetProbe(p) => Some(p),
+            Iter(p) if p.target == iter::Target::Task => Some(p),
             _ => None,
         })
     }
 
-    pub fn kprobes_mut(&mut self) -> impl Iterator<Item = &mut KProbe> {
+    pub fn task_iters_mut(&mut self) -> impl Iterator<Item = &mut TaskIter> {
         use Program::*;
         self.programs.iter_mut().filter_map(|prog| match prog {
-            KProbe(p) | KRetProbe(p) => Some(p),
+            Iter(p) if p.target == iter::Target::Task => Some(p),
             _ => None,
         })
     }
 
-    pub fn kprobe_mut(&mut self, name: &str) -> Option<&mut KProbe> {
-        self.kprobes_mut().find(|p| p.common.name == name)
+    pub fn task_iter_mut(&mut self, name: &str) -> Option<&mut TaskIter> {
+        self.task_iters_mut().find(|p| p.common.name == name)
     }
 
-    pub fn uprobes(&self) -> impl Iterator<Item = &UProbe> {
+    pub fn perf_events(&self) -> impl Iterator<Item = &PerfEvent> {
         use Program::*;
         self.programs.iter().filter_map(|prog| match prog {
-            UProbe(p) | URetProbe(p) => Some(p),
+            PerfEvent(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn uprobes_mut(&mut self) -> impl Iterator<Item = &mut UProbe> {
+    pub fn perf_events_mut(&mut self) -> impl Iterator<Item = &mut PerfEvent> {
         use Program::*;
         self.programs.iter_mut().filter_map(|prog| match prog {
-            UProbe(p) | URetProbe(p) => Some(p),
+            PerfEvent(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn uprobe_mut(&mut self, name: &str) -> Option<&mut UProbe> {
-        self.uprobes_mut().find(|p| p.common.name == name)
+    pub fn perf_event_mut(&mut self, name: &str) -> Option<&mut PerfEvent> {
+        self.perf_events_mut().find(|p| p.common.name == name)
     }
 
-    pub fn xdps(&self) -> impl Iterator<Item = &XDP> {
+    pub fn fentries(&self) -> impl Iterator<Item = &Trampoline> {
         use Program::*;
         self.programs.iter().filter_map(|prog| match prog {
-            XDP(p) => Some(p),
+            FEntry(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn xdps_mut(&mut self) -> impl Iterator<Item = &mut XDP> {
+    pub fn fentries_mut(&mut self) -> impl Iterator<Item = &mut Trampoline> {
         use Program::*;
         self.programs.iter_mut().filter_map(|prog| match prog {
-            XDP(p) => Some(p),
+            FEntry(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn xdp_mut(&mut self, name: &str) -> Option<&mut XDP> {
-        self.xdps_mut().find(|p| p.common.name == name)
+    pub fn fentry_mut(&mut self, name: &str) -> Option<&mut Trampoline> {
+        self.fentries_mut().find(|p| p.common.name == name)
     }
 
-    pub fn socket_filters(&self) -> impl Iterator<Item = &SocketFilter> {
+    pub fn fexits(&self) -> impl Iterator<Item = &Trampoline> {
         use Program::*;
         self.programs.iter().filter_map(|prog| match prog {
-            SocketFilter(p) => Some(p),
+            FExit(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn socket_filters_mut(&mut self) -> impl Iterator<Item = &mut SocketFilter> {
+    pub fn fexits_mut(&mut self) -> impl Iterator<Item = &mut Trampoline> {
         use Program::*;
         self.programs.iter_mut().filter_map(|prog| match prog {
-            SocketFilter(p) => Some(p),
+            FExit(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn socket_filter_mut(&mut self, name: &str) -> Option<&mut SocketFilter> {
-        self.socket_filters_mut().find(|p| p.common.name == name)
+    pub fn fexit_mut(&mut self, name: &str) -> Option<&mut Trampoline> {
+        self.fexits_mut().find(|p| p.common.name == name)
     }
 
-    pub fn trace_points(&self) -> impl Iterator<Item = &TracePoint> {
+    pub fn lsms(&self) -> impl Iterator<Item = &Lsm> {
         use Program::*;
         self.programs.iter().filter_map(|prog| match prog {
-            TracePoint(p) => Some(p),
+            Lsm(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn trace_points_mut(&mut self) -> impl Iterator<Item = &mut TracePoint> {
+    pub fn lsms_mut(&mut self) -> impl Iterator<Item = &mut Lsm> {
         use Program::*;
         self.programs.iter_mut().filter_map(|prog| match prog {
-            TracePoint(p) => Some(p),
+            Lsm(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn trace_point_mut(&mut self, name: &str) -> Option<&mut TracePoint> {
-        self.trace_points_mut().find(|p| p.common.name == name)
+    pub fn lsm_mut(&mut self, name: &str) -> Option<&mut Lsm> {
+        self.lsms_mut().find(|p| p.common.name == name)
     }
 
-    pub fn stream_parsers(&self) -> impl Iterator<Item = &StreamParser> {
+    pub fn cgroup_skbs(&self) -> impl Iterator<Item = &CGroup> {
         use Program::*;
         self.programs.iter().filter_map(|prog| match prog {
-            StreamParser(p) => Some(p),
+            CGroupSkb(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn stream_parsers_mut(&mut self) -> impl Iterator<Item = &mut StreamParser> {
+    pub fn cgroup_skbs_mut(&mut self) -> impl Iterator<Item = &mut CGroup> {
         use Program::*;
         self.programs.iter_mut().filter_map(|prog| match prog {
-            StreamParser(p) => Some(p),
+            CGroupSkb(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn stream_parser_mut(&mut self, name: &str) -> Option<&mut StreamParser> {
-        self.stream_parsers_mut().find(|p| p.common.name == name)
+    pub fn cgroup_skb_mut(&mut self, name: &str) -> Option<&mut CGroup> {
+        self.cgroup_skbs_mut().find(|p| p.common.name == name)
     }
 
-    pub fn stream_verdicts(&self) -> impl Iterator<Item = &StreamVerdict> {
+    pub fn cgroup_socks(&self) -> impl Iterator<Item = &CGroup> {
         use Program::*;
         self.programs.iter().filter_map(|prog| match prog {
-            StreamVerdict(p) => Some(p),
+            CGroupSock(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn stream_verdicts_mut(&mut self) -> impl Iterator<Item = &mut StreamVerdict> {
+    pub fn cgroup_socks_mut(&mut self) -> impl Iterator<Item = &mut CGroup> {
         use Program::*;
         self.programs.iter_mut().filter_map(|prog| match prog {
-            StreamVerdict(p) => Some(p),
+            CGroupSock(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn stream_verdict_mut(&mut self, name: &str) -> Option<&mut StreamVerdict> {
-        self.stream_verdicts_mut().find(|p| p.common.name == name)
+    pub fn cgroup_sock_mut(&mut self, name: &str) -> Option<&mut CGroup> {
+        self.cgroup_socks_mut().find(|p| p.common.name == name)
     }
 
-    pub fn sk_lookups(&self) -> impl Iterator<Item = &SkLookup> {
+    pub fn cgroup_sock_addrs(&self) -> impl Iterator<Item = &CGroup> {
         use Program::*;
         self.programs.iter().filter_map(|prog| match prog {
-            SkLookup(p) => Some(p),
+            CGroupSockAddr(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn sk_lookups_mut(&mut self) -> impl Iterator<Item = &mut SkLookup> {
+    pub fn cgroup_sock_addrs_mut(&mut self) -> impl Iterator<Item = &mut CGroup> {
         use Program::*;
         self.programs.iter_mut().filter_map(|prog| match prog {
-            SkLookup(p) => Some(p),
+            CGroupSockAddr(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn sk_lookup_mut(&mut self, name: &str) -> Option<&mut SkLookup> {
-        self.sk_lookups_mut().find(|p| p.common.name == name)
+    pub fn cgroup_sock_addr_mut(&mut self, name: &str) -> Option<&mut CGroup> {
+        self.cgroup_sock_addrs_mut().find(|p| p.common.name == name)
     }
 
-    pub fn task_iters(&self) -> impl Iterator<Item = &TaskIter> {
+    pub fn cgroup_sysctls(&self) -> impl Iterator<Item = &CGroup> {
         use Program::*;
         self.programs.iter().filter_map(|prog| match prog {
-            TaskIter(p) => Some(p),
+            CGroupSysctl(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn task_iters_mut(&mut self) -> impl Iterator<Item = &mut TaskIter> {
+    pub fn cgroup_sysctls_mut(&mut self) -> impl Iterator<Item = &mut CGroup> {
         use Program::*;
         self.programs.iter_mut().filter_map(|prog| match prog {
-            TaskIter(p) => Some(p),
+            CGroupSysctl(p) => Some(p),
             _ => None,
         })
     }
 
-    pub fn task_iter_mut(&mut self, name: &str) -> Option<&mut TaskIter> {
-        self.task_iters_mut().find(|p| p.common.name == name)
+    pub fn cgroup_sysctl_mut(&mut self, name: &str) -> Option<&mut CGroup> {
+        self.cgroup_sysctls_mut().find(|p| p.common.name == name)
     }
 }
 
 impl<'a> ModuleBuilder<'a> {
     /// Parse binary data of ELF relocatable file
     ///
+    /// If the file contains CO-RE relocations in its `.BTF.ext` section, the
+    /// field offsets, field existence checks and type sizes that the
+    /// instructions of programs hold are relocated against the BTF of the
+    /// running kernel, `/sys/kernel/btf/vmlinux`. So a program compiled
+    /// against one kernel version can be loaded into other kernel versions.
+    ///
     /// # Example
     /// ```no_run
     /// # static ELF_BINARY: [u8; 128] = [0u8; 128];
@@ -1509,6 +3284,9 @@ impl<'a> ModuleBuilder<'a> {
         // symval_to_maps: symbol value => map
         let mut symval_to_map_builders = RSHashMap::new();
 
+        let mut text = None;
+        // map symbol name => directory to pin the map at
+        let mut pin_records = RSHashMap::new();
         let mut license = String::new();
         let mut version = 0u32;
         // BTF is optional
@@ -1531,14 +3309,39 @@ impl<'a> ModuleBuilder<'a> {
                 (hdr::SHT_PROGBITS, Some("license"), _) => {
                     license = zero::read_str(content).to_string()
                 }
+                (hdr::SHT_PROGBITS, Some("maps.pin"), None) => {
+                    for sym in symtab.iter().filter(|sym| sym.st_shndx == shndx) {
+                        let sym_name = strtab.get_at(sym.st_name).ok_or(Error::ElfError)?;
+                        if let Some(map_sym_name) = sym_name.strip_prefix("MAP_PIN_") {
+                            let start = sym.st_value as usize;
+                            let end = start + sym.st_size as usize;
+                            let dir = content.get(start..end).ok_or(Error::ElfError)?;
+                            pin_records.insert(
+                                map_sym_name.to_string(),
+                                PathBuf::from(zero::read_str(dir)),
+                            );
+                        }
+                    }
+                }
+                (hdr::SHT_PROGBITS, Some(".text"), None) if !content.is_empty() => {
+                    text = Some(TextSection::new(shndx, &content, &symtab)?);
+                }
                 (hdr::SHT_NOBITS, Some(name @ ".bss"), None) => {
-                    let map_builder = MapBuilder::with_section_data(name, &content)?;
+                    let map_builder = MapBuilder::with_section_data(
+                        name,
+                        Cow::Owned(vec![0; shdr.sh_size as usize]),
+                        section_variables(&object, &symtab, shndx),
+                    )?;
                     map_builders.insert(shndx, map_builder);
                 }
                 (hdr::SHT_PROGBITS, Some(name), None)
                     if name.starts_with(".data") || name.starts_with(".rodata") =>
                 {
-                    let map_builder = MapBuilder::with_section_data(name, &content)?;
+                    let map_builder = MapBuilder::with_section_data(
+                        name,
+                        Cow::Borrowed(content),
+                        section_variables(&object, &symtab, shndx),
+                    )?;
                     map_builders.insert(shndx, map_builder);
                 }
                 (hdr::SHT_PROGBITS, Some("maps"), Some(name)) => {
@@ -1592,18 +3395,36 @@ impl<'a> ModuleBuilder<'a> {
                 | (hdr::SHT_PROGBITS, Some(kind @ "uprobe"), Some(name))
                 | (hdr::SHT_PROGBITS, Some(kind @ "uretprobe"), Some(name))
                 | (hdr::SHT_PROGBITS, Some(kind @ "tracepoint"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "raw_tracepoint"), Some(name))
                 | (hdr::SHT_PROGBITS, Some(kind @ "xdp"), Some(name))
                 | (hdr::SHT_PROGBITS, Some(kind @ "socketfilter"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "tc_action"), Some(name))
                 | (hdr::SHT_PROGBITS, Some(kind @ "streamparser"), Some(name))
                 | (hdr::SHT_PROGBITS, Some(kind @ "streamverdict"), Some(name))
-                | (hdr::SHT_PROGBITS, Some(kind @ "sk_lookup"), Some(name)) => {
+                | (hdr::SHT_PROGBITS, Some(kind @ "sk_msg"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "sk_lookup"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "perf_event"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "cgroup_skb"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "cgroup_sock"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "cgroup_sock_addr"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "cgroup_sysctl"), Some(name)) => {
                     let prog = Program::new(kind, name, &content)?;
                     programs.insert(shndx, prog);
                 }
-                (hdr::SHT_PROGBITS, Some(kind @ "task_iter"), Some(name)) => {
+                (hdr::SHT_PROGBITS, Some(kind @ "task_iter"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "task_file_iter"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "tcp_iter"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "udp_iter"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "bpf_map_iter"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "bpf_map_elem_iter"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "bpf_prog_iter"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "fentry"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "fexit"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "tp_btf"), Some(name))
+                | (hdr::SHT_PROGBITS, Some(kind @ "lsm"), Some(name)) => {
                     if vmlinux_btf.is_none() {
                         vmlinux_btf = Some(btf::parse_vmlinux_btf().map_err(|e| {
-                            // Raise an error because BPF iter programs can not run without BTF support.
+                            // Raise an error because these programs can not run without BTF support.
                             error!("error on btf::parse_vmlinux_btf: {:?}", e);
                             e
                         })?);
@@ -1621,12 +3442,103 @@ impl<'a> ModuleBuilder<'a> {
             }
         }
 
+        // BTF of the object is also required to relocate CO-RE accesses even
+        // if it could not be loaded into the kernel
+        let unloaded_btf = if btf.is_none() {
+            BTF::parse_elf(&object, bytes).ok()
+        } else {
+            None
+        };
+        if let Some(local_btf) = btf.as_ref().or_else(|| unloaded_btf.as_ref()) {
+            // variables that are not found in the symbol table, e.g. static
+            // ones, may still be described by BTF
+            for (_, map_builder) in map_builders.iter_mut() {
+                if let MapBuilder::SectionData {
+                    name, variables, ..
+                } = map_builder
+                {
+                    for (var_name, offset, size) in local_btf.get_datasec_vars(name) {
+                        variables.entry(var_name).or_insert(GlobalVariable {
+                            offset: offset as usize,
+                            size: size as usize,
+                        });
+                    }
+                }
+            }
+
+            let core_relocs = btf::parse_core_relocations(&object, bytes, local_btf)?;
+            if !core_relocs.is_empty() {
+                if vmlinux_btf.is_none() {
+                    vmlinux_btf = Some(btf::parse_vmlinux_btf().map_err(|e| {
+                        error!("error on btf::parse_vmlinux_btf: {:?}", e);
+                        e
+                    })?);
+                }
+                for (shndx, prog) in programs.iter_mut() {
+                    let sec_name = get_section_name(&object, &object.section_headers[*shndx])?;
+                    if let Some(relocs) = core_relocs.get(sec_name) {
+                        local_btf
+                            .apply_core_relocations(
+                                vmlinux_btf.as_ref().unwrap(),
+                                relocs,
+                                &mut prog.data_mut().code,
+                            )
+                            .map_err(|e| {
+                                error!("error on CO-RE relocation of {}: {:?}", sec_name, e);
+                                e
+                            })?;
+                    }
+                }
+                if let Some(text) = text.as_mut() {
+                    if let Some(relocs) = core_relocs.get(".text") {
+                        local_btf
+                            .apply_core_relocations(
+                                vmlinux_btf.as_ref().unwrap(),
+                                relocs,
+                                &mut text.code,
+                            )
+                            .map_err(|e| {
+                                error!("error on CO-RE relocation of .text: {:?}", e);
+                                e
+                            })?;
+                    }
+                }
+            }
+        }
+
+        // Pinning is recorded by the symbol name of a map but each map of
+        // `maps/<name>` sections is identified by its section header index
+        let mut pin_dirs = RSHashMap::new();
+        if !pin_records.is_empty() {
+            for shndx in map_builders.keys() {
+                let map_sym_name = symtab.iter().find_map(|sym| {
+                    let name = strtab.get_at(sym.st_name)?;
+                    if sym.st_shndx == *shndx && pin_records.contains_key(name) {
+                        Some(name)
+                    } else {
+                        None
+                    }
+                });
+                if let Some(dir) = map_sym_name.and_then(|name| pin_records.remove(name)) {
+                    pin_dirs.insert(*shndx, dir);
+                }
+            }
+            for name in pin_records.keys() {
+                warn!(
+                    "map of which symbol name is `{}' not found. not pinned",
+                    name
+                );
+            }
+        }
+
         Ok(ModuleBuilder {
             object,
             programs,
             map_builders,
             symval_to_map_builders,
             rels,
+            text,
+            pin_dirs,
             license,
             version,
             btf,
@@ -1638,6 +3550,15 @@ impl<'a> ModuleBuilder<'a> {
     /// When this method is called, `ModuleBuilder` is moved out so the
     /// instance can not be used any more.
     ///
+    /// Functions of the `.text` section that programs call with BPF-to-BPF
+    /// calls, i.e. functions marked with `#[inline(never)]`, are appended to
+    /// each program calling them and the offsets of the calls are fixed up.
+    ///
+    /// Maps defined with `#[map(pinning = "by_name")]` reuse the map of the
+    /// same name pinned at their pin path if there is. Otherwise they are
+    /// created and pinned there. A pinned map of a different definition is an
+    /// error.
+    ///
     /// # Example
     /// ```no_run
     /// # let arr = [0u8; 128];
@@ -1649,7 +3570,10 @@ impl<'a> ModuleBuilder<'a> {
         let symtab = self.object.syms.to_vec();
         let mut maps = RSHashMap::new();
         for (shndx, map_builder) in self.map_builders.into_iter() {
-            let map = map_builder.to_map()?;
+            let map = match self.pin_dirs.get(&shndx) {
+                Some(dir) => map_builder.to_pinned_map(dir)?,
+                None => map_builder.to_map()?,
+            };
             maps.insert(shndx, map);
         }
 
@@ -1659,14 +3583,44 @@ impl<'a> ModuleBuilder<'a> {
             symval_to_maps.insert(symval, map);
         }
 
-        // Rewrite programs with relocation data
+        // Rewrite programs and .text with relocation data
+        let text_shndx = self.text.as_ref().map(|text| text.shndx);
         for rel in self.rels.iter() {
-            if self.programs.contains_key(&rel.target_sec_idx) {
-                if let Err(_) = rel.apply(&mut self.programs, &maps, &symtab) {
-                    // means that not normal case, we should rely on symbol value instead of section header index
-                    rel.apply_with_symmap(&mut self.programs, &symval_to_maps, &symtab)
+            if Some(symtab[rel.sym_idx].st_shndx) == text_shndx {
+                // BPF-to-BPF calls are relocated when .text is linked below
+                continue;
+            }
+            let code = if let Some(prog) = self.programs.get_mut(&rel.target_sec_idx) {
+                &mut prog.data_mut().code
+            } else if let Some(text) = self
+                .text
+                .as_mut()
+                .filter(|text| text.shndx == rel.target_sec_idx)
+            {
+                &mut text.code
+            } else {
+                continue;
+            };
+            if let Err(_) = rel.apply_to_code(code, &maps, &symtab) {
+                // means that not normal case, we should rely on symbol value instead of section header index
+                rel.apply_with_symmap_to_code(code, &symval_to_maps, &symtab)
+                    .map_err(|e| {
+                        error!("can not relocate map");
+                        e
+                    })?;
+            }
+        }
+
+        // Append the functions of .text that each program calls
+        if let Some(text) = self.text.as_ref() {
+            let text_calls = pseudo_calls(&text.code, text.shndx, text.shndx, &self.rels, &symtab);
+            for (shndx, prog) in self.programs.iter_mut() {
+                let calls =
+                    pseudo_calls(&prog.data().code, *shndx, text.shndx, &self.rels, &symtab);
+                if !calls.is_empty() {
+                    text.link(&mut prog.data_mut().code, calls, &text_calls)
                         .map_err(|e| {
-                            error!("can not relocate map");
+                            error!("can not link .text into program {}", prog.name());
                             e
                         })?;
                 }
@@ -1701,7 +3655,7 @@ impl<'a> ModuleBuilder<'a> {
     /// builder.replace_map("sharedmap", Map::from_pin_file("/sys/fs/bpf/sharedmap").expect("error on Map::from_pin_file")).expect("error on ModuleBuilder::replace_map");
     /// let mut module = builder.to_module().expect("error on ModuleBuilder::to_module");
     /// ```
-    pub fn replace_map(&mut self, map_name: &str, new: Map) -> Result<&mut Self> {
+    pub fn replace_map(&mut self, map_name: &str, mut new: Map) -> Result<&mut Self> {
         for (_, map_builder) in self.map_builders.iter_mut() {
             match map_builder {
                 MapBuilder::Normal {
@@ -1710,37 +3664,32 @@ impl<'a> ModuleBuilder<'a> {
                     btf_type_id: _,
                 } => {
                     if name == map_name {
-                        if !(def.type_ == new.config.type_
-                            && def.key_size == new.config.key_size
-                            && def.value_size == new.config.value_size
-                            && def.max_entries == new.config.max_entries)
-                        {
+                        if !map_defs_match(def, &new.config) {
                             error!("map definition does not match");
-                            return Err(Error::Map);
+                            return Err(new.mismatch(&format!("map `{}'", name)));
                         }
                         *map_builder = MapBuilder::with_existing_map(new)?;
                         return Ok(self);
                     }
                 }
-                MapBuilder::SectionData { name, .. } => {
+                MapBuilder::SectionData {
+                    name, variables, ..
+                } => {
                     if name == map_name {
                         if !new.section_data {
                             error!("map is not for section data");
                             return Err(Error::Map);
                         }
+                        new.global_variables = variables.clone();
                         *map_builder = MapBuilder::with_existing_map(new)?;
                         return Ok(self);
                     }
                 }
                 MapBuilder::ExistingMap(map) => {
                     if map.name == map_name {
-                        if !(map.config.type_ == new.config.type_
-                            && map.config.key_size == new.config.key_size
-                            && map.config.value_size == new.config.value_size
-                            && map.config.max_entries == new.config.max_entries)
-                        {
+                        if !map_defs_match(&map.config, &new.config) {
                             error!("map definition does not match");
-                            return Err(Error::Map);
+                            return Err(new.mismatch(&format!("map `{}'", map.name)));
                         }
                         *map_builder = MapBuilder::with_existing_map(new)?;
                         return Ok(self);
@@ -1751,6 +3700,123 @@ impl<'a> ModuleBuilder<'a> {
         error!("map of which name is `{}' not found", map_name);
         Err(Error::Map)
     }
+
+    /// Names of the maps that will be created by
+    /// [`to_module`](#method.to_module), i.e. the maps that are neither
+    /// replaced, pinned by their definitions nor for global variables
+    pub(crate) fn maps_to_create(&self) -> Vec<String> {
+        self.map_builders
+            .iter()
+            .filter(|(shndx, _)| !self.pin_dirs.contains_key(shndx))
+            .filter_map(|(_, map_builder)| match map_builder {
+                MapBuilder::Normal { name, .. } => Some(name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Set the initial value of a global variable whose name is `var_name`
+    ///
+    /// The variable can be defined in any of `.rodata`, `.data` or `.bss`
+    /// sections. Since `.rodata` is frozen when it is loaded, this is the
+    /// only chance to set read-only variables. And then the verifier knows
+    /// their values so it can prune branches that are never taken, e.g. on
+    /// a target PID or a threshold that a probe is configured with.
+    ///
+    /// The variable should be defined with `#[no_mangle]` so that it can be
+    /// found by its name. And the probe should read it with
+    /// `core::ptr::read_volatile` because otherwise the compiler folds the
+    /// value it is defined with into the instructions.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # let arr = [0u8; 128];
+    /// # let bytes = &arr;
+    /// use redbpf::ModuleBuilder;
+    /// let mut builder = ModuleBuilder::parse(bytes).expect("error on ModuleBuilder::parse");
+    /// builder.set_global_variable("TARGET_PID", 1234u32).expect("error on ModuleBuilder::set_global_variable");
+    /// let mut module = builder.to_module().expect("error on ModuleBuilder::to_module");
+    /// ```
+    pub fn set_global_variable<T: Copy>(&mut self, var_name: &str, value: T) -> Result<&mut Self> {
+        for (_, map_builder) in self.map_builders.iter_mut() {
+            if let MapBuilder::SectionData {
+                bytes, variables, ..
+            } = map_builder
+            {
+                if let Some(var) = variables.get(var_name) {
+                    let bytes = bytes.to_mut();
+                    write_global_variable(bytes, var_name, var, value)?;
+                    return Ok(self);
+                }
+            }
+        }
+        error!("global variable `{}' not found", var_name);
+        Err(Error::Map)
+    }
+}
+
+/// Find global variables defined in data section `shndx` by the symbol table
+fn section_variables(
+    object: &Elf,
+    symtab: &[Sym],
+    shndx: usize,
+) -> RSHashMap<String, GlobalVariable> {
+    symtab
+        .iter()
+        .filter(|sym| sym.st_shndx == shndx && sym.st_type() == STT_OBJECT)
+        .filter_map(|sym| {
+            let name = object.strtab.get_at(sym.st_name)?;
+            if name.is_empty() {
+                return None;
+            }
+            Some((
+                name.to_string(),
+                GlobalVariable {
+                    offset: sym.st_value as usize,
+                    size: sym.st_size as usize,
+                },
+            ))
+        })
+        .collect()
+}
+
+fn write_global_variable<T: Copy>(
+    data: &mut [u8],
+    var_name: &str,
+    var: &GlobalVariable,
+    value: T,
+) -> Result<()> {
+    check_global_variable::<T>(data, var_name, var)?;
+    unsafe {
+        ptr::write_unaligned(data[var.offset..].as_mut_ptr() as *mut T, value);
+    }
+    Ok(())
+}
+
+fn read_global_variable<T: Copy>(data: &[u8], var_name: &str, var: &GlobalVariable) -> Result<T> {
+    check_global_variable::<T>(data, var_name, var)?;
+    Ok(unsafe { ptr::read_unaligned(data[var.offset..].as_ptr() as *const T) })
+}
+
+fn check_global_variable<T>(data: &[u8], var_name: &str, var: &GlobalVariable) -> Result<()> {
+    if mem::size_of::<T>() != var.size {
+        error!(
+            "size of global variable `{}' is {} but {} bytes are given",
+            var_name,
+            var.size,
+            mem::size_of::<T>()
+        );
+        return Err(Error::Map);
+    }
+    if !matches!(var.offset.checked_add(var.size), Some(end) if end <= data.len()) {
+        error!(
+            "global variable `{}' is out of the section of {} bytes",
+            var_name,
+            data.len()
+        );
+        return Err(Error::Map);
+    }
+    Ok(())
 }
 
 fn get_section_name<'o>(object: &'o Elf, shdr: &SectionHeader) -> Result<&'o str> {
@@ -1788,11 +3854,20 @@ impl RelocationInfo {
     ) -> Result<()> {
         // get the program we need to apply relocations to based on the program section index
         let prog = programs.get_mut(&self.target_sec_idx).ok_or(Error::Reloc)?;
+        self.apply_to_code(&mut prog.data_mut().code, maps, symtab)
+    }
+
+    #[inline]
+    fn apply_to_code(
+        &self,
+        code: &mut [bpf_insn],
+        maps: &RSHashMap<usize, Map>,
+        symtab: &[Sym],
+    ) -> Result<()> {
         // lookup the symbol we're relocating in the symbol table
         let sym = symtab[self.sym_idx];
         // get the map referenced by the program based on the symbol section index
         let insn_idx = (self.offset / std::mem::size_of::<bpf_insn>() as u64) as usize;
-        let code = &mut prog.data_mut().code;
         let map = maps.get(&sym.st_shndx).ok_or(Error::Reloc)?;
 
         // the index of the instruction we need to patch
@@ -1814,9 +3889,18 @@ impl RelocationInfo {
         symtab: &[Sym],
     ) -> Result<()> {
         let prog = programs.get_mut(&self.target_sec_idx).ok_or(Error::Reloc)?;
+        self.apply_with_symmap_to_code(&mut prog.data_mut().code, symval_to_maps, symtab)
+    }
+
+    #[inline]
+    fn apply_with_symmap_to_code(
+        &self,
+        code: &mut [bpf_insn],
+        symval_to_maps: &RSHashMap<u64, Map>,
+        symtab: &[Sym],
+    ) -> Result<()> {
         let sym = symtab[self.sym_idx];
         let insn_idx = (self.offset / std::mem::size_of::<bpf_insn>() as u64) as usize;
-        let code = &mut prog.data_mut().code;
         let map = symval_to_maps.get(&sym.st_value).ok_or(Error::Reloc)?;
         code[insn_idx].set_src_reg(libbpf_sys::BPF_PSEUDO_MAP_FD as u8);
         code[insn_idx].imm = map.fd;
@@ -1824,6 +3908,131 @@ impl RelocationInfo {
     }
 }
 
+impl TextSection {
+    fn new(shndx: usize, content: &[u8], symtab: &[Sym]) -> Result<TextSection> {
+        let code: Vec<bpf_insn> = unsafe { zero::read_array_unsafe(content) }.to_vec();
+        let insn_size = mem::size_of::<bpf_insn>();
+        let mut funcs = symtab
+            .iter()
+            .filter(|sym| sym.st_shndx == shndx && sym.st_type() == STT_FUNC)
+            .map(|sym| {
+                (
+                    sym.st_value as usize / insn_size,
+                    sym.st_size as usize / insn_size,
+                )
+            })
+            .collect::<Vec<(usize, usize)>>();
+        funcs.sort_unstable();
+        funcs.dedup();
+        // the size of each function must keep it within .text and apart from
+        // the next one
+        let mut end = 0;
+        for (start, len) in funcs.iter() {
+            if *len == 0 || *start < end || start + len > code.len() {
+                error!(
+                    "function at instruction {} of .text has invalid size {}",
+                    start, len
+                );
+                return Err(Error::Section(".text".to_string()));
+            }
+            end = start + len;
+        }
+        Ok(TextSection { shndx, code, funcs })
+    }
+
+    fn function_at(&self, insn_idx: usize) -> Option<(usize, usize)> {
+        self.funcs
+            .iter()
+            .find(|(start, len)| (*start..start + len).contains(&insn_idx))
+            .copied()
+    }
+
+    /// Append the functions `calls` refer to, and the functions those call in
+    /// turn, to `code` and fix up the offsets of the call instructions.
+    ///
+    /// `calls` and `text_calls` are pairs of the index of a call instruction
+    /// in `code` or in `.text` and the index of the called instruction in
+    /// `.text`. Every function is appended at most once per program.
+    fn link(
+        &self,
+        code: &mut Vec<bpf_insn>,
+        calls: Vec<(usize, usize)>,
+        text_calls: &[(usize, usize)],
+    ) -> Result<()> {
+        // index of a function in .text => index it is appended at in code
+        let mut appended = RSHashMap::new();
+        let mut pending = calls;
+        while let Some((call_idx, target)) = pending.pop() {
+            let (start, len) = self.function_at(target).ok_or_else(|| {
+                error!("no function at instruction {} of .text", target);
+                Error::Reloc
+            })?;
+            let base = match appended.get(&start) {
+                Some(base) => *base,
+                None => {
+                    let base = code.len();
+                    code.extend_from_slice(&self.code[start..start + len]);
+                    appended.insert(start, base);
+                    pending.extend(
+                        text_calls
+                            .iter()
+                            .filter(|(idx, _)| (start..start + len).contains(idx))
+                            .map(|(idx, callee)| (base + idx - start, *callee)),
+                    );
+                    base
+                }
+            };
+            code[call_idx].imm = (base + target - start) as i32 - call_idx as i32 - 1;
+        }
+        Ok(())
+    }
+}
+
+#[inline]
+fn is_pseudo_call(insn: &bpf_insn) -> bool {
+    insn.code == (libbpf_sys::BPF_JMP | libbpf_sys::BPF_CALL) as u8
+        && insn.src_reg() == libbpf_sys::BPF_PSEUDO_CALL as u8
+}
+
+/// Find BPF-to-BPF calls from `code` of section `shndx` into `.text`
+///
+/// Returns pairs of the index of a call instruction and the index of the
+/// instruction of `.text` it calls.
+fn pseudo_calls(
+    code: &[bpf_insn],
+    shndx: usize,
+    text_shndx: usize,
+    rels: &[RelocationInfo],
+    symtab: &[Sym],
+) -> Vec<(usize, usize)> {
+    let insn_size = mem::size_of::<bpf_insn>();
+    let mut calls = RSHashMap::new();
+    if shndx == text_shndx {
+        // calls within .text may be resolved already without relocations
+        for (idx, insn) in code.iter().enumerate() {
+            if is_pseudo_call(insn) {
+                calls.insert(idx, (idx as i64 + insn.imm as i64 + 1) as usize);
+            }
+        }
+    }
+    for rel in rels.iter().filter(|rel| rel.target_sec_idx == shndx) {
+        let sym = symtab[rel.sym_idx];
+        let idx = rel.offset as usize / insn_size;
+        if sym.st_shndx != text_shndx
+            || !matches!(code.get(idx), Some(insn) if is_pseudo_call(insn))
+        {
+            continue;
+        }
+        let target = sym.st_value as i64 / insn_size as i64 + code[idx].imm as i64 + 1;
+        calls.insert(idx, target as usize);
+    }
+    calls.into_iter().collect()
+}
+
+lazy_static! {
+    static ref MMAPABLE_ARRAY: bool = features::mmapable_array();
+}
+
 impl Map {
     pub fn load(name: &str, code: &[u8]) -> Result<Map> {
         let config: bpf_map_def = *unsafe { zero::read_unsafe(code) };
@@ -1843,8 +4052,9 @@ impl Map {
             None,
         )?;
         map.section_data = true;
-        // for BSS we don't need to copy the data, it's already 0-initialized
-        if name != ".bss" {
+        // for BSS we don't need to copy the data unless some variables are
+        // set, it's already 0-initialized
+        if name != ".bss" || data.iter().any(|b| *b != 0) {
             unsafe {
                 let ret = libbpf_sys::bpf_map_update_elem(
                     map.fd,
@@ -1853,13 +4063,119 @@ impl Map {
                     0,
                 );
                 if ret < 0 {
-                    return Err(Error::BPF);
+                    return Err(map.operation_error("update"));
                 }
             }
         }
+        if flags & libbpf_sys::BPF_F_RDONLY_PROG != 0 {
+            // Frozen maps can not be modified by userspace any more. So the
+            // verifier treats read-only variables as constants.
+            if unsafe { libbpf_sys::bpf_map_freeze(map.fd) } < 0 {
+                warn!("failed to freeze map `{}'. Ignore it", name);
+            }
+        }
         Ok(map)
     }
 
+    /// Read the value of a global variable whose name is `var_name`
+    ///
+    /// This map should be the one holding the data section that the variable
+    /// is defined in.
+    pub fn global_variable<T: Copy>(&self, var_name: &str) -> Result<T> {
+        let var = self.global_variables.get(var_name).ok_or_else(|| {
+            error!("global variable `{}' not found", var_name);
+            Error::Map
+        })?;
+        let data = self.section_bytes()?;
+        read_global_variable(&data, var_name, var)
+    }
+
+    /// Write `value` to a global variable whose name is `var_name`
+    ///
+    /// Variables of `.rodata` can not be written because the map is frozen
+    /// when it is loaded. Set them by
+    /// [`ModuleBuilder::set_global_variable`](struct.ModuleBuilder.html#method.set_global_variable)
+    /// before loading instead.
+    ///
+    /// On Linux 5.5 or later the maps of `.data` and `.bss` are created
+    /// memory-mappable and only the bytes of the variable are written, so
+    /// the other variables that running programs update are left intact.
+    /// Otherwise the whole section is read and written back, and updates
+    /// that programs make to the section in between are lost. So in that
+    /// case set variables only while no program using the section is
+    /// attached.
+    pub fn set_global_variable<T: Copy>(&self, var_name: &str, value: T) -> Result<()> {
+        let var = self.global_variables.get(var_name).ok_or_else(|| {
+            error!("global variable `{}' not found", var_name);
+            Error::Map
+        })?;
+        if self.config.map_flags & libbpf_sys::BPF_F_RDONLY_PROG != 0 {
+            error!("global variable `{}' is read-only", var_name);
+            return Err(Error::Map);
+        }
+        if !self.section_data {
+            error!("map `{}' does not hold section data", self.name);
+            return Err(Error::Map);
+        }
+        if self.config.map_flags & libbpf_sys::BPF_F_MMAPABLE != 0 {
+            return self.mmap_section(|data| write_global_variable(data, var_name, var, value));
+        }
+        let mut data = self.section_bytes()?;
+        write_global_variable(&mut data, var_name, var, value)?;
+        unsafe {
+            if libbpf_sys::bpf_map_update_elem(
+                self.fd,
+                &mut 0u32 as *mut _ as *mut _,
+                data.as_mut_ptr() as *mut _,
+                0,
+            ) < 0
+            {
+                return Err(self.operation_error("update"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Call `f` with the section data mapped into memory
+    fn mmap_section<R>(&self, f: impl FnOnce(&mut [u8]) -> Result<R>) -> Result<R> {
+        let size = self.config.value_size as usize;
+        unsafe {
+            let addr = libc::mmap(
+                ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                self.fd,
+                0,
+            );
+            if addr == libc::MAP_FAILED {
+                return Err(self.operation_error("mmap"));
+            }
+            let result = f(std::slice::from_raw_parts_mut(addr as *mut u8, size));
+            libc::munmap(addr, size);
+            result
+        }
+    }
+
+    fn section_bytes(&self) -> Result<Vec<u8>> {
+        if !self.section_data {
+            error!("map `{}' does not hold section data", self.name);
+            return Err(Error::Map);
+        }
+        let mut data = vec![0u8; self.config.value_size as usize];
+        unsafe {
+            if libbpf_sys::bpf_map_lookup_elem(
+                self.fd,
+                &mut 0u32 as *mut _ as *mut _,
+                data.as_mut_ptr() as *mut _,
+            ) < 0
+            {
+                return Err(self.operation_error("look up"));
+            }
+        }
+        Ok(data)
+    }
+
     fn with_map_def(
         name: &str,
         config: bpf_map_def,
@@ -1930,15 +4246,37 @@ impl Map {
                 fd,
                 config,
                 section_data: false,
+                global_variables: RSHashMap::new(),
                 pin_file: None,
             })
         } else {
+            let error = io::Error::last_os_error();
             error!(
                 "error on bpf_create_map_xattr. failed to load map `{}`: {}",
-                name,
-                io::Error::last_os_error()
+                name, error
             );
-            Err(Error::Map)
+            Err(Error::MapCreate {
+                name: name.to_string(),
+                def: config,
+                error,
+            })
+        }
+    }
+
+    /// Make an error of the failed `operation` with the last `errno`
+    fn operation_error(&self, operation: &'static str) -> Error {
+        Error::MapOperation {
+            name: self.name.clone(),
+            operation,
+            error: io::Error::last_os_error(),
+        }
+    }
+
+    fn mismatch(&self, expected: &str) -> Error {
+        Error::MapMismatch {
+            name: self.name.clone(),
+            def: self.config,
+            expected: expected.to_string(),
         }
     }
 
@@ -1960,21 +4298,42 @@ impl Map {
             error!("error on bpf_obj_get: {}", io::Error::last_os_error());
             return Err(Error::IO(io::Error::last_os_error()));
         }
-        let map_info = unsafe {
-            let mut info = mem::zeroed::<bpf_map_info>();
-            let mut info_len = mem::size_of_val(&info) as u32;
-            if libbpf_sys::bpf_obj_get_info_by_fd(fd, &mut info as *mut _ as *mut _, &mut info_len)
-                != 0
-            {
-                error!(
-                    "error on bpf_obj_get_info_by_fd: {}",
-                    io::Error::last_os_error()
-                );
-                return Err(Error::IO(io::Error::last_os_error()));
+        Map::from_fd(fd, Some(Box::from(file)))
+    }
+
+    /// Open the map of the given id
+    ///
+    /// The ids of the maps are listed by
+    /// [`objects::maps`](./objects/fn.maps.html).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use redbpf::{objects, Map};
+    /// for info in objects::maps().expect("error on listing maps") {
+    ///     if info.name == "persist_map" {
+    ///         let map = Map::from_id(info.id).expect("error on opening map");
+    ///     }
+    /// }
+    /// ```
+    pub fn from_id(id: u32) -> Result<Map> {
+        let fd = unsafe { libbpf_sys::bpf_map_get_fd_by_id(id) };
+        if fd < 0 {
+            let err = io::Error::last_os_error();
+            error!("error on bpf_map_get_fd_by_id: {}", err);
+            return Err(Error::IO(err));
+        }
+        Map::from_fd(fd, None)
+    }
+
+    /// Create `Map` from `fd` of a map. The `fd` is closed on errors.
+    fn from_fd(fd: RawFd, pin_file: Option<Box<Path>>) -> Result<Map> {
+        let map_info = match get_map_info(fd) {
+            Ok(info) => info,
+            Err(e) => {
+                unsafe { libc::close(fd) };
+                return Err(e);
             }
-            info
         };
-
         let name = unsafe {
             CStr::from_ptr(&map_info.name as *const _)
                 .to_string_lossy()
@@ -1992,7 +4351,8 @@ impl Map {
                 map_flags: map_info.map_flags,
             },
             section_data: false,
-            pin_file: Some(Box::from(file)),
+            global_variables: RSHashMap::new(),
+            pin_file,
         })
     }
 
@@ -2057,10 +4417,15 @@ impl<'a> MapBuilder<'a> {
         })
     }
 
-    fn with_section_data(name: &str, bytes: &'a [u8]) -> Result<Self> {
+    fn with_section_data(
+        name: &str,
+        bytes: Cow<'a, [u8]>,
+        variables: RSHashMap<String, GlobalVariable>,
+    ) -> Result<Self> {
         Ok(MapBuilder::SectionData {
             name: name.to_string(),
             bytes,
+            variables,
         })
     }
 
@@ -2092,18 +4457,59 @@ impl<'a> MapBuilder<'a> {
                 def,
                 btf_type_id,
             } => Map::with_map_def(name.as_ref(), def, btf_type_id),
-            MapBuilder::SectionData { name, bytes } => Map::with_section_data(
-                name.as_ref(),
+            MapBuilder::SectionData {
+                name,
                 bytes,
-                if name.starts_with(".rodata") {
+                variables,
+            } => {
+                let flags = if name.starts_with(".rodata") {
                     libbpf_sys::BPF_F_RDONLY_PROG
+                } else if *MMAPABLE_ARRAY {
+                    // writable variables are updated in place through mmap
+                    libbpf_sys::BPF_F_MMAPABLE
                 } else {
                     0
-                },
-            ),
+                };
+                let mut map = Map::with_section_data(name.as_ref(), &bytes, flags)?;
+                map.global_variables = variables;
+                Ok(map)
+            }
             MapBuilder::ExistingMap(map) => Ok(map),
         }
     }
+
+    /// Reuse the map pinned at `dir/<name>`, or create the map and pin it
+    /// there if it does not exist
+    ///
+    /// Maps that replaced the defined ones are used as they are.
+    fn to_pinned_map(self, dir: &Path) -> Result<Map> {
+        let file = match &self {
+            MapBuilder::Normal { name, .. } => dir.join(name),
+            _ => return self.to_map(),
+        };
+        if file.exists() {
+            let map = Map::from_pin_file(&file)?;
+            if let MapBuilder::Normal { name, def, .. } = &self {
+                if !map_defs_match(def, &map.config) {
+                    error!("definition of pinned map {:?} does not match", file);
+                    return Err(map.mismatch(&format!("map `{}'", name)));
+                }
+            }
+            debug!("reuse map pinned at {:?}", file);
+            return Ok(map);
+        }
+        let mut map = self.to_map()?;
+        map.pin(&file)?;
+        Ok(map)
+    }
+}
+
+/// Compare the definitions of maps except for the flags
+fn map_defs_match(a: &bpf_map_def, b: &bpf_map_def) -> bool {
+    a.type_ == b.type_
+        && a.key_size == b.key_size
+        && a.value_size == b.value_size
+        && a.max_entries == b.max_entries
 }
 
 impl<'base, K: Clone, V: Clone> HashMap<'base, K, V> {
@@ -2117,7 +4523,7 @@ impl<'base, K: Clone, V: Clone> HashMap<'base, K, V> {
                 "map definitions (map type and key/value size) of base `Map' and
             `HashMap' do not match"
             );
-            return Err(Error::Map);
+            return Err(base.mismatch("HashMap"));
         }
 
         Ok(HashMap {
@@ -2128,7 +4534,7 @@ impl<'base, K: Clone, V: Clone> HashMap<'base, K, V> {
     }
 
     pub fn set(&self, key: K, value: V) {
-        let _ = bpf_map_set(self.base.fd, key, value);
+        let _ = bpf_map_set(self.base, key, value);
     }
 
     pub fn get(&self, key: K) -> Option<V> {
@@ -2136,7 +4542,7 @@ impl<'base, K: Clone, V: Clone> HashMap<'base, K, V> {
     }
 
     pub fn delete(&self, key: K) {
-        let _ = bpf_map_delete(self.base.fd, key);
+        let _ = bpf_map_delete(self.base, key);
     }
 
     /// Return an iterator over all items in the map
@@ -2167,7 +4573,7 @@ impl<'base, K: Clone, V: Clone> LruHashMap<'base, K, V> {
             error!(
                 "map definitions (map type and key/value sizes) of base `Map' and `LruHashMap' do not match"
             );
-            return Err(Error::Map);
+            return Err(base.mismatch("LruHashMap"));
         }
 
         Ok(LruHashMap {
@@ -2178,7 +4584,7 @@ impl<'base, K: Clone, V: Clone> LruHashMap<'base, K, V> {
     }
 
     pub fn set(&self, key: K, value: V) {
-        let _ = bpf_map_set(self.base.fd, key, value);
+        let _ = bpf_map_set(self.base, key, value);
     }
 
     pub fn get(&self, key: K) -> Option<V> {
@@ -2186,7 +4592,7 @@ impl<'base, K: Clone, V: Clone> LruHashMap<'base, K, V> {
     }
 
     pub fn delete(&self, key: K) {
-        let _ = bpf_map_delete(self.base.fd, key);
+        let _ = bpf_map_delete(self.base, key);
     }
 
     /// Return an iterator over all items in the map
@@ -2215,7 +4621,7 @@ impl<'base, K: Clone, V: Clone> PerCpuHashMap<'base, K, V> {
             || BPF_MAP_TYPE_PERCPU_HASH != base.config.type_
         {
             error!("map definitions (size of key/value and map type) of base `Map' and `PerCpuHashMap' do not match");
-            return Err(Error::Map);
+            return Err(base.mismatch("PerCpuHashMap"));
         }
 
         Ok(PerCpuHashMap {
@@ -2235,7 +4641,7 @@ impl<'base, K: Clone, V: Clone> PerCpuHashMap<'base, K, V> {
     /// `Err` can be returned if the number of elements is wrong or underlying
     /// bpf_map_update_elem function returns a negative value.
     pub fn set(&self, key: K, values: PerCpuValues<V>) -> Result<()> {
-        bpf_percpu_map_set(self.base.fd, key, values)
+        bpf_percpu_map_set(self.base, key, values)
     }
 
     /// Get per-cpu values corresponding to the `key` from the BPF map
@@ -2248,7 +4654,7 @@ impl<'base, K: Clone, V: Clone> PerCpuHashMap<'base, K, V> {
 
     /// Delete `key` from the BPF map
     pub fn delete(&self, key: K) {
-        let _ = bpf_map_delete(self.base.fd, key);
+        let _ = bpf_map_delete(self.base, key);
     }
 
     /// Return an iterator over all items in the map
@@ -2277,7 +4683,7 @@ impl<'base, K: Clone, V: Clone> LruPerCpuHashMap<'base, K, V> {
             || BPF_MAP_TYPE_LRU_PERCPU_HASH != base.config.type_
         {
             error!("map definitions (size of key/value and map type) of base `Map' and `LruPerCpuHashMap' do not match");
-            return Err(Error::Map);
+            return Err(base.mismatch("LruPerCpuHashMap"));
         }
 
         Ok(LruPerCpuHashMap {
@@ -2297,7 +4703,7 @@ impl<'base, K: Clone, V: Clone> LruPerCpuHashMap<'base, K, V> {
     /// `Err` can be returned if the number of elements is wrong or underlying
     /// bpf_map_update_elem function returns a negative value.
     pub fn set(&self, key: K, values: PerCpuValues<V>) -> Result<()> {
-        bpf_percpu_map_set(self.base.fd, key, values)
+        bpf_percpu_map_set(self.base, key, values)
     }
 
     /// Get per-cpu values corresponding to the `key` from the BPF map
@@ -2310,7 +4716,7 @@ impl<'base, K: Clone, V: Clone> LruPerCpuHashMap<'base, K, V> {
 
     /// Delete `key` from the BPF map
     pub fn delete(&self, key: K) {
-        let _ = bpf_map_delete(self.base.fd, key);
+        let _ = bpf_map_delete(self.base, key);
     }
 
     /// Return an iterator over all items in the map
@@ -2342,7 +4748,7 @@ impl<'base, T: Clone> Array<'base, T> {
                 "map definitions (size of value, map type) of base `Map' and
             `Array' do not match"
             );
-            return Err(Error::Map);
+            return Err(base.mismatch("Array"));
         }
 
         Ok(Array {
@@ -2364,7 +4770,7 @@ impl<'base, T: Clone> Array<'base, T> {
             )
         };
         if rv < 0 {
-            Err(Error::Map)
+            Err(self.base.operation_error("update"))
         } else {
             Ok(())
         }
@@ -2466,7 +4872,7 @@ impl<'base, T: Clone> PerCpuArray<'base, T> {
                 "map definitions (size of value, map type) of base `Map' and
             `PerCpuArray' do not match"
             );
-            return Err(Error::Map);
+            return Err(base.mismatch("PerCpuArray"));
         }
 
         Ok(PerCpuArray {
@@ -2510,7 +4916,7 @@ impl<'base, T: Clone> PerCpuArray<'base, T> {
             )
         } < 0
         {
-            Err(Error::Map)
+            Err(self.base.operation_error("update"))
         } else {
             Ok(())
         }
@@ -2567,7 +4973,7 @@ impl<'base> ProgramArray<'base> {
                 "map definitions (sizes of key and value) of base `Map' and
             `ProgramArray' do not match"
             );
-            return Err(Error::Map);
+            return Err(base.mismatch("ProgramArray"));
         }
 
         Ok(ProgramArray { base })
@@ -2584,7 +4990,7 @@ impl<'base> ProgramArray<'base> {
             )
         } < 0
         {
-            return Err(Error::Map);
+            return Err(self.base.operation_error("look up"));
         }
         Ok(fd)
     }
@@ -2617,7 +5023,7 @@ impl<'base> ProgramArray<'base> {
             )
         };
         if ret < 0 {
-            return Err(Error::Map);
+            return Err(self.base.operation_error("update"));
         }
 
         Ok(())
@@ -2672,7 +5078,7 @@ impl StackTrace<'_> {
             if ret == 0 {
                 Ok(())
             } else {
-                Err(Error::Map)
+                Err(self.base.operation_error("delete"))
             }
         }
     }
@@ -2690,16 +5096,12 @@ impl StreamParser {
     /// loaded.stream_parsers().next().unwrap().attach_sockmap(&echo_sockmap).expect("Attaching sockmap failed");
     /// ```
     pub fn attach_sockmap(&self, sock_map: &SockMap) -> Result<()> {
-        let attach_fd = sock_map.base.fd;
-        let prog_fd = self.common.fd.unwrap();
+        attach_sock_map(self.common.fd, sock_map.base.fd, BPF_SK_SKB_STREAM_PARSER)
+    }
 
-        let ret =
-            unsafe { libbpf_sys::bpf_prog_attach(prog_fd, attach_fd, BPF_SK_SKB_STREAM_PARSER, 0) };
-        if ret < 0 {
-            Err(Error::BPF)
-        } else {
-            Ok(())
-        }
+    /// Attach `sock_hash` to stream parser BPF program.
+    pub fn attach_sockhash<K: Clone>(&self, sock_hash: &SockHash<K>) -> Result<()> {
+        attach_sock_map(self.common.fd, sock_hash.base.fd, BPF_SK_SKB_STREAM_PARSER)
     }
 }
 
@@ -2715,17 +5117,62 @@ impl StreamVerdict {
     /// loaded.stream_verdicts().next().unwrap().attach_sockmap(&echo_sockmap).expect("Attaching sockmap failed");
     /// ```
     pub fn attach_sockmap(&self, sock_map: &SockMap) -> Result<()> {
-        let attach_fd = sock_map.base.fd;
-        let prog_fd = self.common.fd.unwrap();
+        attach_sock_map(self.common.fd, sock_map.base.fd, BPF_SK_SKB_STREAM_VERDICT)
+    }
 
-        let ret = unsafe {
-            libbpf_sys::bpf_prog_attach(prog_fd, attach_fd, BPF_SK_SKB_STREAM_VERDICT, 0)
-        };
-        if ret < 0 {
-            Err(Error::BPF)
-        } else {
-            Ok(())
-        }
+    /// Attach `sock_hash` to stream verdict BPF program.
+    pub fn attach_sockhash<K: Clone>(&self, sock_hash: &SockHash<K>) -> Result<()> {
+        attach_sock_map(self.common.fd, sock_hash.base.fd, BPF_SK_SKB_STREAM_VERDICT)
+    }
+}
+
+impl SkMsg {
+    /// Attach `sock_map` to sk_msg BPF program.
+    ///
+    /// The program runs on every message sent through the sockets stored in
+    /// `sock_map`.
+    pub fn attach_sockmap(&self, sock_map: &SockMap) -> Result<()> {
+        attach_sock_map(self.common.fd, sock_map.base.fd, BPF_SK_MSG_VERDICT)
+    }
+
+    /// Attach `sock_hash` to sk_msg BPF program.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use redbpf::{load::Loader, SockHash};
+    ///
+    /// #[derive(Clone)]
+    /// #[repr(C)]
+    /// struct FiveTuple {
+    ///     // ...
+    /// }
+    ///
+    /// let loaded = Loader::load(b"bypass.elf").expect("error loading BPF program");
+    /// let sockhash = SockHash::<FiveTuple>::new(loaded.map("sockhash").expect("sockhash not found")).unwrap();
+    /// loaded.sk_msgs().next().unwrap().attach_sockhash(&sockhash).expect("Attaching sockhash failed");
+    /// ```
+    pub fn attach_sockhash<K: Clone>(&self, sock_hash: &SockHash<K>) -> Result<()> {
+        attach_sock_map(self.common.fd, sock_hash.base.fd, BPF_SK_MSG_VERDICT)
+    }
+
+    pub fn name(&self) -> String {
+        self.common.name.to_string()
+    }
+}
+
+fn attach_sock_map(
+    prog_fd: Option<RawFd>,
+    map_fd: RawFd,
+    attach_type: bpf_attach_type,
+) -> Result<()> {
+    let prog_fd = prog_fd.ok_or(Error::ProgramNotLoaded)?;
+    let ret = unsafe { libbpf_sys::bpf_prog_attach(prog_fd, map_fd, attach_type, 0) };
+    if ret < 0 {
+        let err = io::Error::last_os_error();
+        error!("error on bpf_prog_attach to sock map: {}", err);
+        Err(Error::IO(err))
+    } else {
+        Ok(())
     }
 }
 
@@ -2744,11 +5191,7 @@ impl<'a> SockMap<'a> {
             )
         };
         if ret < 0 {
-            error!(
-                "error on updating sockmap: {:?}",
-                io::Error::last_os_error()
-            );
-            Err(Error::Map)
+            Err(self.base.operation_error("update"))
         } else {
             Ok(())
         }
@@ -2758,13 +5201,43 @@ impl<'a> SockMap<'a> {
         let ret =
             unsafe { libbpf_sys::bpf_map_delete_elem(self.base.fd, &mut idx as *mut _ as *mut _) };
         if ret < 0 {
-            Err(Error::Map)
+            Err(self.base.operation_error("delete"))
         } else {
             Ok(())
         }
     }
 }
 
+impl<'a, K: Clone> SockHash<'a, K> {
+    pub fn new(base: &'a Map) -> Result<SockHash<'a, K>> {
+        if mem::size_of::<K>() != base.config.key_size as usize
+            || mem::size_of::<i32>() != base.config.value_size as usize
+            || BPF_MAP_TYPE_SOCKHASH != base.config.type_
+        {
+            error!(
+                "map definitions (map type and key/value size) of base `Map' and
+            `SockHash' do not match"
+            );
+            return Err(base.mismatch("SockHash"));
+        }
+
+        Ok(SockHash {
+            base,
+            _k: PhantomData,
+        })
+    }
+
+    /// Store the socket `fd` at `key`, replacing the socket already stored
+    /// there if any.
+    pub fn set(&mut self, key: K, fd: RawFd) -> Result<()> {
+        bpf_map_set(self.base, key, fd)
+    }
+
+    pub fn delete(&mut self, key: K) -> Result<()> {
+        bpf_map_delete(self.base, key)
+    }
+}
+
 impl<'base, K: Clone, V: Clone> LpmTrieMap<'base, K, V> {
     pub fn new(base: &'base Map) -> Result<Self> {
         if mem::size_of::<K>() + mem::size_of::<u32>() != base.config.key_size as usize
@@ -2775,7 +5248,7 @@ impl<'base, K: Clone, V: Clone> LpmTrieMap<'base, K, V> {
                 "map definitions (map type and key/value size) of base `Map' and
             `LpmTrieMap' do not match"
             );
-            return Err(Error::Map);
+            return Err(base.mismatch("LpmTrieMap"));
         }
 
         Ok(Self {
@@ -2786,7 +5259,7 @@ impl<'base, K: Clone, V: Clone> LpmTrieMap<'base, K, V> {
     }
 
     pub fn set(&self, key: LpmTrieMapKey<K>, value: V) {
-        let _ = bpf_map_set(self.base.fd, key, value);
+        let _ = bpf_map_set(self.base, key, value);
     }
 
     pub fn get(&self, key: LpmTrieMapKey<K>) -> Option<V> {
@@ -2794,7 +5267,7 @@ impl<'base, K: Clone, V: Clone> LpmTrieMap<'base, K, V> {
     }
 
     pub fn delete(&self, key: LpmTrieMapKey<K>) {
-        let _ = bpf_map_delete(self.base.fd, key);
+        let _ = bpf_map_delete(self.base, key);
     }
 
     /// Return an iterator over all items in the map
@@ -2823,7 +5296,7 @@ impl<K: Clone, V: Clone> MapIterable<LpmTrieMapKey<K>, V> for LpmTrieMap<'_, K,
 /// data. And userspace programs can read the data by `read()` system
 /// call. `BPFIter` implements `Iterator` trait to provide a convenient way for
 /// reading data. See
-/// [`TaskIter::bpf_iter`](./struct.TaskIter.html#method.bpf_iter) that creates
+/// [`Iter::bpf_iter`](./struct.Iter.html#method.bpf_iter) that creates
 /// this structure.
 pub struct BPFIter<T> {
     file: BufReader<File>,
@@ -2860,42 +5333,114 @@ impl<T> Iterator for BPFIter<T> {
     }
 }
 
-impl TaskIter {
-    fn create_link(&mut self) -> Result<()> {
-        let link_fd =
-            unsafe { bpf_link_create(self.common.fd.unwrap(), 0, BPF_TRACE_ITER, ptr::null()) };
+impl Iter {
+    fn create_link(&mut self, map_fd: Option<RawFd>) -> Result<()> {
+        self.link_fd = Some(self.open_link(map_fd)?);
+        Ok(())
+    }
+
+    fn open_link(&self, map_fd: Option<RawFd>) -> Result<RawFd> {
+        let fd = self.common.fd.ok_or(Error::ProgramNotLoaded)?;
+        let mut iter_info = libbpf_sys::bpf_iter_link_info::default();
+        let mut opts = libbpf_sys::bpf_link_create_opts::default();
+        opts.sz = mem::size_of::<libbpf_sys::bpf_link_create_opts>() as _;
+        if let Some(map_fd) = map_fd {
+            iter_info.map.map_fd = map_fd as u32;
+            opts.iter_info = &mut iter_info;
+            opts.iter_info_len = mem::size_of::<libbpf_sys::bpf_iter_link_info>() as u32;
+        }
+        let link_fd = unsafe { bpf_link_create(fd, 0, BPF_TRACE_ITER, &opts) };
         if link_fd < 0 {
-            error!("Error on bpf_link_create");
+            let err = io::Error::last_os_error();
+            error!(
+                "Error on bpf_link_create of {} iterator: {}",
+                self.target.name(),
+                err
+            );
+            return Err(Error::IO(err));
+        }
+        Ok(link_fd)
+    }
+
+    /// Create a [`Link`](./link/struct.Link.html) of the iterator
+    ///
+    /// `map` must be given for `bpf_map_elem` iterators only. Once the link
+    /// is pinned to BPF FS, reading the pinned file runs the iterator, e.g.
+    /// `cat /sys/fs/bpf/tasks`.
+    pub fn link_iter(&self, map: Option<&Map>) -> Result<Link> {
+        if map.is_some() != (self.target == iter::Target::BpfMapElem) {
+            error!(
+                "a map must be given for bpf_map_elem iterators and only for them: {}",
+                self.common.name
+            );
             return Err(Error::BPF);
         }
-        self.link_fd = Some(link_fd);
-        Ok(())
+        Ok(Link::from_fd(self.open_link(map.map(|map| map.fd))?))
+    }
+
+    /// Attach the `bpf_map_elem` iterator to `map`
+    ///
+    /// The iterator walks over the elements of `map`. This must be called
+    /// before [`bpf_iter`](#method.bpf_iter) for iterators whose target is
+    /// [`Target::BpfMapElem`](./iter/enum.Target.html#variant.BpfMapElem).
+    /// The kernel rejects the map if its key or value is smaller than what
+    /// the program reads.
+    pub fn attach_map(&mut self, map: &Map) -> Result<()> {
+        if self.target != iter::Target::BpfMapElem {
+            error!(
+                "can not attach a map to {} iterator {}",
+                self.target.name(),
+                self.common.name
+            );
+            return Err(Error::BPF);
+        }
+        if self.link_fd.is_some() {
+            return Err(Error::ProgramAlreadyLinked);
+        }
+        self.create_link(Some(map.fd))
     }
 
     /// Create an iterator that iterates over data written by BPF iterators
     ///
+    /// Every call walks over the kernel objects again, so the data is a
+    /// snapshot taken at the time of the call.
+    ///
     /// See [`BPFIter<T>`](./struct.BPFIter.html) for more information.
     pub fn bpf_iter<T>(&mut self) -> Result<impl Iterator<Item = T>> {
         if self.common.fd.is_none() {
-            error!("can not call TaskIter::iter before program is loaded");
+            error!("can not call Iter::bpf_iter before program is loaded");
             return Err(Error::ProgramNotLoaded);
         }
 
         if self.link_fd.is_none() {
-            self.create_link()?;
+            if self.target == iter::Target::BpfMapElem {
+                error!("Iter::attach_map should be called before Iter::bpf_iter");
+                return Err(Error::BPF);
+            }
+            self.create_link(None)?;
         }
 
         let iter_fd = unsafe { bpf_iter_create(self.link_fd.clone().unwrap()) };
         if iter_fd < 0 {
-            error!("Error on bpf_iter_create");
-            return Err(Error::BPF);
+            let err = io::Error::last_os_error();
+            error!("Error on bpf_iter_create: {}", err);
+            return Err(Error::IO(err));
         }
 
         Ok(BPFIter::from(iter_fd)?)
     }
+
+    /// The kind of kernel objects the iterator walks over
+    pub fn target(&self) -> iter::Target {
+        self.target
+    }
+
+    pub fn name(&self) -> String {
+        self.common.name.to_string()
+    }
 }
 
-impl Drop for TaskIter {
+impl Drop for Iter {
     fn drop(&mut self) {
         if let Some(link_fd) = self.link_fd {
             unsafe {
@@ -2938,17 +5483,17 @@ fn data<'d>(bytes: &'d [u8], shdr: &SectionHeader) -> &'d [u8] {
     &bytes[offset..end]
 }
 
-fn bpf_map_set<K: Clone, V: Clone>(fd: RawFd, mut key: K, mut value: V) -> Result<()> {
+fn bpf_map_set<K: Clone, V: Clone>(map: &Map, mut key: K, mut value: V) -> Result<()> {
     if unsafe {
         libbpf_sys::bpf_map_update_elem(
-            fd,
+            map.fd,
             &mut key as *mut _ as *mut _,
             &mut value as *mut _ as *mut _,
             0,
         )
     } < 0
     {
-        Err(Error::Map)
+        Err(map.operation_error("update"))
     } else {
         Ok(())
     }
@@ -2969,9 +5514,9 @@ fn bpf_map_get<K: Clone, V: Clone>(fd: RawFd, mut key: K) -> Option<V> {
     Some(unsafe { value.assume_init() })
 }
 
-fn bpf_map_delete<K: Clone>(fd: RawFd, mut key: K) -> Result<()> {
-    if unsafe { libbpf_sys::bpf_map_delete_elem(fd, &mut key as *mut _ as *mut _) } < 0 {
-        Err(Error::Map)
+fn bpf_map_delete<K: Clone>(map: &Map, mut key: K) -> Result<()> {
+    if unsafe { libbpf_sys::bpf_map_delete_elem(map.fd, &mut key as *mut _ as *mut _) } < 0 {
+        Err(map.operation_error("delete"))
     } else {
         Ok(())
     }
@@ -3006,7 +5551,7 @@ fn bpf_map_get_next_key<K: Clone>(fd: RawFd, key: Option<K>) -> Option<K> {
 }
 
 fn bpf_percpu_map_set<K: Clone, V: Clone>(
-    fd: RawFd,
+    map: &Map,
     mut key: K,
     values: PerCpuValues<V>,
 ) -> Result<()> {
@@ -3029,10 +5574,10 @@ fn bpf_percpu_map_set<K: Clone, V: Clone>(
     }
 
     if unsafe {
-        libbpf_sys::bpf_map_update_elem(fd, &mut key as *mut _ as *mut _, data as *mut _, 0)
+        libbpf_sys::bpf_map_update_elem(map.fd, &mut key as *mut _ as *mut _, data as *mut _, 0)
     } < 0
     {
-        Err(Error::Map)
+        Err(map.operation_error("update"))
     } else {
         Ok(())
     }
@@ -3063,6 +5608,34 @@ fn bpf_percpu_map_get<K: Clone, V: Clone>(fd: RawFd, mut key: K) -> Option<PerCp
     Some(values.into())
 }
 
+pub(crate) fn get_prog_info(fd: RawFd) -> Result<libbpf_sys::bpf_prog_info> {
+    unsafe {
+        let mut info = mem::zeroed::<libbpf_sys::bpf_prog_info>();
+        let mut info_len = mem::size_of_val(&info) as u32;
+        if libbpf_sys::bpf_obj_get_info_by_fd(fd, &mut info as *mut _ as *mut _, &mut info_len) != 0
+        {
+            let err = io::Error::last_os_error();
+            error!("error on bpf_obj_get_info_by_fd: {}", err);
+            return Err(Error::IO(err));
+        }
+        Ok(info)
+    }
+}
+
+pub(crate) fn get_map_info(fd: RawFd) -> Result<bpf_map_info> {
+    unsafe {
+        let mut info = mem::zeroed::<bpf_map_info>();
+        let mut info_len = mem::size_of_val(&info) as u32;
+        if libbpf_sys::bpf_obj_get_info_by_fd(fd, &mut info as *mut _ as *mut _, &mut info_len) != 0
+        {
+            let err = io::Error::last_os_error();
+            error!("error on bpf_obj_get_info_by_fd: {}", err);
+            return Err(Error::IO(err));
+        }
+        Ok(info)
+    }
+}
+
 fn map_type_name(map_type: u32) -> String {
     match map_type {
         BPF_MAP_TYPE_HASH => "HashMap",
@@ -3099,3 +5672,218 @@ fn map_type_name(map_type: u32) -> String {
     }
     .to_string()
 }
+
+mod test {
+    #[test]
+    fn test_to_pinned_map() {
+        use crate::{get_map_info, Error, MapBuilder};
+        use libbpf_sys::{bpf_map_def, BPF_MAP_TYPE_ARRAY, BPF_MAP_TYPE_HASH};
+        use std::path::Path;
+        use std::{fs, process};
+
+        let builder = |type_| MapBuilder::Normal {
+            name: "counts".to_string(),
+            def: bpf_map_def {
+                type_,
+                key_size: 4,
+                value_size: 8,
+                max_entries: 16,
+                map_flags: 0,
+            },
+            btf_type_id: None,
+        };
+        // pinning maps requires privileges and a mounted BPF FS
+        let dir = Path::new("/sys/fs/bpf").join(format!("redbpf-test-{}", process::id()));
+        if fs::create_dir(&dir).is_err() {
+            eprintln!("skip test_to_pinned_map: can not create {:?}", dir);
+            return;
+        }
+        let file = dir.join("counts");
+
+        let created = builder(BPF_MAP_TYPE_HASH).to_pinned_map(&dir);
+        let reused = builder(BPF_MAP_TYPE_HASH).to_pinned_map(&dir);
+        let mismatched = builder(BPF_MAP_TYPE_ARRAY).to_pinned_map(&dir);
+        let pinned = file.exists();
+        let _ = fs::remove_dir_all(&dir);
+
+        let created = created.unwrap();
+        assert!(pinned);
+        assert_eq!(created.pin_file.as_deref(), Some(file.as_path()));
+        let reused = reused.unwrap();
+        assert_eq!(
+            get_map_info(reused.fd).unwrap().id,
+            get_map_info(created.fd).unwrap().id
+        );
+        assert!(matches!(mismatched, Err(Error::MapMismatch { .. })));
+    }
+    #[test]
+    fn test_text_section_new() {
+        use crate::TextSection;
+        use goblin::elf::sym::{Sym, STT_FUNC};
+        use libbpf_sys::bpf_insn;
+
+        let func = |value: u64, size: u64| Sym {
+            st_info: STT_FUNC,
+            st_shndx: 1,
+            st_value: value * 8,
+            st_size: size * 8,
+            ..Default::default()
+        };
+        let content = vec![0u8; 6 * std::mem::size_of::<bpf_insn>()];
+        let text = TextSection::new(1, &content, &[func(4, 2), func(0, 3)]).unwrap();
+        assert_eq!(text.funcs, vec![(0, 3), (4, 2)]);
+        assert_eq!(text.function_at(2), Some((0, 3)));
+        assert_eq!(text.function_at(3), None);
+
+        // overlapping, out of .text and empty functions
+        assert!(TextSection::new(1, &content, &[func(0, 3), func(2, 2)]).is_err());
+        assert!(TextSection::new(1, &content, &[func(4, 3)]).is_err());
+        assert!(TextSection::new(1, &content, &[func(0, 0)]).is_err());
+    }
+
+    #[test]
+    fn test_text_section_link() {
+        use crate::{pseudo_calls, RelocationInfo, TextSection};
+        use goblin::elf::sym::{Sym, STT_FUNC};
+        use libbpf_sys::{bpf_insn, BPF_CALL, BPF_EXIT, BPF_JMP, BPF_PSEUDO_CALL};
+
+        // f0 calls f1 with a call resolved by the compiler and f2 with a
+        // relocated call, f1 calls f2 and the program calls f0 and f2
+        let marker = |id: i32| bpf_insn {
+            code: 0xb7, // r0 = imm
+            imm: id,
+            ..Default::default()
+        };
+        let call = |imm: i32| {
+            let mut insn = bpf_insn {
+                code: (BPF_JMP | BPF_CALL) as u8,
+                imm,
+                ..Default::default()
+            };
+            insn.set_src_reg(BPF_PSEUDO_CALL as u8);
+            insn
+        };
+        let exit = bpf_insn {
+            code: (BPF_JMP | BPF_EXIT) as u8,
+            ..Default::default()
+        };
+        let text_code = vec![
+            marker(100),
+            call(2),
+            call(-1),
+            exit,
+            marker(101),
+            call(-1),
+            exit,
+            marker(102),
+            exit,
+        ];
+        let func = |value: u64, size: u64| Sym {
+            st_info: STT_FUNC,
+            st_shndx: 1,
+            st_value: value * 8,
+            st_size: size * 8,
+            ..Default::default()
+        };
+        let symtab = vec![Sym::default(), func(0, 4), func(4, 3), func(7, 2)];
+        let rel = |target_sec_idx, insn_idx: u64, sym_idx| RelocationInfo {
+            target_sec_idx,
+            offset: insn_idx * 8,
+            sym_idx,
+        };
+        let rels = vec![rel(1, 2, 3), rel(1, 5, 3), rel(2, 0, 1), rel(2, 1, 3)];
+        let content = unsafe {
+            std::slice::from_raw_parts(
+                text_code.as_ptr() as *const u8,
+                text_code.len() * std::mem::size_of::<bpf_insn>(),
+            )
+        };
+        let text = TextSection::new(1, content, &symtab).unwrap();
+
+        let mut text_calls = pseudo_calls(&text.code, 1, 1, &rels, &symtab);
+        text_calls.sort_unstable();
+        assert_eq!(text_calls, vec![(1, 4), (2, 7), (5, 7)]);
+
+        let mut code = vec![call(-1), call(-1), exit];
+        let mut calls = pseudo_calls(&code, 2, 1, &rels, &symtab);
+        calls.sort_unstable();
+        assert_eq!(calls, vec![(0, 0), (1, 7)]);
+        text.link(&mut code, calls, &text_calls).unwrap();
+
+        // every function is appended once although f2 is called three times
+        assert_eq!(code.len(), 3 + 4 + 3 + 2);
+        let base = |id: i32| {
+            let bases = code
+                .iter()
+                .enumerate()
+                .filter(|(_, insn)| insn.code == 0xb7 && insn.imm == id)
+                .map(|(idx, _)| idx)
+                .collect::<Vec<usize>>();
+            assert_eq!(bases.len(), 1);
+            bases[0]
+        };
+        let (f0, f1, f2) = (base(100), base(101), base(102));
+        let target = |idx: usize| (idx as i32 + code[idx].imm + 1) as usize;
+        assert_eq!(code[0].imm, f0 as i32 - 1);
+        assert_eq!(code[1].imm, f2 as i32 - 2);
+        assert_eq!(target(f0 + 1), f1);
+        assert_eq!(target(f0 + 2), f2);
+        assert_eq!(target(f1 + 1), f2);
+    }
+
+    #[test]
+    fn test_global_variable() {
+        use crate::{read_global_variable, write_global_variable, GlobalVariable};
+
+        let var = GlobalVariable { offset: 4, size: 4 };
+        let mut data = [0u8; 8];
+        write_global_variable(&mut data, "pid", &var, 0x1234_5678u32).unwrap();
+        assert_eq!(data[4..], 0x1234_5678u32.to_ne_bytes());
+        assert_eq!(data[..4], [0; 4]);
+        assert_eq!(
+            read_global_variable::<u32>(&data, "pid", &var).unwrap(),
+            0x1234_5678
+        );
+
+        // size mismatch
+        assert!(write_global_variable(&mut data, "pid", &var, 0u64).is_err());
+        assert!(read_global_variable::<u16>(&data, "pid", &var).is_err());
+
+        // out of range
+        let var = GlobalVariable { offset: 6, size: 4 };
+        assert!(write_global_variable(&mut data, "pid", &var, 0u32).is_err());
+        assert!(read_global_variable::<u32>(&data, "pid", &var).is_err());
+        let var = GlobalVariable {
+            offset: usize::MAX,
+            size: 4,
+        };
+        assert!(read_global_variable::<u32>(&data, "pid", &var).is_err());
+        // failed writes leave the data intact
+        assert_eq!(data[4..], 0x1234_5678u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_unknown_global_variable() {
+        use crate::Map;
+        use libbpf_sys::{bpf_map_def, BPF_MAP_TYPE_ARRAY};
+        use std::collections::HashMap;
+
+        let map = Map {
+            name: ".data".to_string(),
+            kind: BPF_MAP_TYPE_ARRAY,
+            fd: -1,
+            config: bpf_map_def {
+                type_: BPF_MAP_TYPE_ARRAY,
+                key_size: 4,
+                value_size: 8,
+                max_entries: 1,
+                map_flags: 0,
+            },
+            section_data: true,
+            global_variables: HashMap::new(),
+            pin_file: None,
+        };
+        assert!(map.global_variable::<u32>("pid").is_err());
+        assert!(map.set_global_variable("pid", 0u32).is_err());
+    }
+}
@@ -0,0 +1,366 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*!
+BPF links.
+
+A [`Link`](struct.Link.html) represents an attachment of a BPF program to a
+hook of the kernel. Unlike the attachments made by the `attach_*` methods of
+the programs, the attachment is owned by the `Link` rather than by the
+program. So it is detached as soon as the `Link` is dropped, or the process
+holding it dies, unless the link is pinned to BPF FS. A pinned link keeps the
+program attached after the process exits and can be restored with
+[`Link::from_pin_file`](struct.Link.html#method.from_pin_file) later, e.g.
+when an agent restarts.
+
+Links are created by the `link_*` methods of the programs, e.g.
+[`KProbe::link_kprobe`](../struct.KProbe.html#method.link_kprobe). Attaching
+kprobes, uprobes, tracepoints and perf events with links requires Linux 5.15
+or later. Socket filters, `tc` actions and programs attached to sockmaps can
+not be attached with links.
+*/
+
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::raw::c_char;
+use std::os::unix::io::RawFd;
+use std::path::Path;
+use std::ptr;
+
+use libbpf_sys::{
+    bpf_attach_type, bpf_insn, bpf_link_create, bpf_load_program_attr, BPF_LINK_CREATE,
+    BPF_PERF_EVENT, BPF_PROG_TYPE_KPROBE,
+};
+use tracing::{debug, error};
+
+use crate::error::{Error, Result};
+use crate::{perf, pin_bpf_obj, unpin_bpf_obj};
+
+// kprobe_multi links are introduced by Linux 5.18 and libbpf-sys does not
+// define them yet
+const BPF_TRACE_KPROBE_MULTI: bpf_attach_type = 42;
+const BPF_F_KPROBE_MULTI_RETURN: u32 = 1;
+
+lazy_static! {
+    static ref KPROBE_MULTI_SUPPORTED: bool = probe_kprobe_multi();
+}
+
+/// `link_create` of `union bpf_attr` for `BPF_TRACE_KPROBE_MULTI` links
+#[repr(C)]
+#[derive(Default)]
+struct KprobeMultiLinkCreateAttr {
+    prog_fd: u32,
+    target_fd: u32,
+    attach_type: u32,
+    flags: u32,
+    kprobe_multi_flags: u32,
+    cnt: u32,
+    syms: u64,
+    addrs: u64,
+    cookies: u64,
+}
+
+/// An attachment of a BPF program that can be pinned to BPF FS
+pub struct Link {
+    fd: RawFd,
+    // the perf event that `BPF_PERF_EVENT` link is created on
+    pfd: Option<RawFd>,
+    pin_file: Option<Box<Path>>,
+}
+
+impl Link {
+    /// Create a link of `prog_fd` to `target_fd`. The meaning of `target_fd`
+    /// depends on `attach_type`, e.g. a cgroup or a network namespace.
+    pub(crate) fn create(
+        prog_fd: RawFd,
+        target_fd: RawFd,
+        attach_type: bpf_attach_type,
+        flags: u32,
+    ) -> Result<Link> {
+        let opts = libbpf_sys::bpf_link_create_opts {
+            sz: std::mem::size_of::<libbpf_sys::bpf_link_create_opts>() as _,
+            flags,
+            ..Default::default()
+        };
+        let fd = unsafe { bpf_link_create(prog_fd, target_fd, attach_type, &opts) };
+        if fd < 0 {
+            let err = io::Error::last_os_error();
+            error!("error on bpf_link_create: {}", err);
+            return Err(Error::IO(err));
+        }
+        Ok(Link::from_fd(fd))
+    }
+
+    /// Create a link of `prog_fd` to the perf event `pfd` and enable it.
+    ///
+    /// The link takes the ownership of `pfd`. So it is closed even on errors.
+    pub(crate) fn with_perf_event(prog_fd: RawFd, pfd: RawFd) -> Result<Link> {
+        let mut link = match Link::create(prog_fd, pfd, BPF_PERF_EVENT, 0) {
+            Ok(link) => link,
+            Err(e) => {
+                unsafe {
+                    let _ = libc::close(pfd);
+                }
+                return Err(e);
+            }
+        };
+        link.pfd = Some(pfd);
+        unsafe { perf::enable_perf_event(pfd)? };
+        Ok(link)
+    }
+
+    /// Create a `kprobe_multi` link of `prog_fd` to all of `fn_names`
+    ///
+    /// The program must be loaded by
+    /// [`load_kprobe_multi_program`](fn.load_kprobe_multi_program.html).
+    pub(crate) fn with_kprobe_multi(
+        prog_fd: RawFd,
+        fn_names: &[String],
+        retprobe: bool,
+    ) -> Result<Link> {
+        let cnames = fn_names
+            .iter()
+            .map(|name| CString::new(name.as_str()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let syms: Vec<*const c_char> = cnames.iter().map(|name| name.as_ptr()).collect();
+        let attr = KprobeMultiLinkCreateAttr {
+            prog_fd: prog_fd as u32,
+            attach_type: BPF_TRACE_KPROBE_MULTI,
+            kprobe_multi_flags: if retprobe {
+                BPF_F_KPROBE_MULTI_RETURN
+            } else {
+                0
+            },
+            cnt: syms.len() as u32,
+            syms: syms.as_ptr() as u64,
+            ..Default::default()
+        };
+        match unsafe { create_kprobe_multi_link(&attr) } {
+            Ok(fd) => Ok(Link::from_fd(fd)),
+            Err(err) => {
+                error!("error on creating kprobe_multi link: {}", err);
+                Err(Error::IO(err))
+            }
+        }
+    }
+
+    pub(crate) fn from_fd(fd: RawFd) -> Link {
+        Link {
+            fd,
+            pfd: None,
+            pin_file: None,
+        }
+    }
+
+    /// Restore `Link` from a file which represents a pinned link
+    ///
+    /// The program stays attached as long as the file exists, even after the
+    /// returned `Link` is dropped.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use redbpf::link::Link;
+    /// let link = Link::from_pin_file("/sys/fs/bpf/kprobe_link").expect("error on restoring link");
+    /// ```
+    pub fn from_pin_file(file: impl AsRef<Path>) -> Result<Link> {
+        let file = file.as_ref();
+        let fd = unsafe {
+            let cpathname = std::ffi::CString::new(file.to_str().unwrap())?;
+            libbpf_sys::bpf_obj_get(cpathname.as_ptr())
+        };
+        if fd < 0 {
+            error!("error on bpf_obj_get: {}", io::Error::last_os_error());
+            return Err(Error::IO(io::Error::last_os_error()));
+        }
+        Ok(Link {
+            fd,
+            pfd: None,
+            pin_file: Some(Box::from(file)),
+        })
+    }
+
+    /// Open the link of the given id
+    ///
+    /// The ids of the links are listed by
+    /// [`objects::links`](../objects/fn.links.html). Like a link restored
+    /// from a pinned file, the program stays attached after the returned
+    /// `Link` is dropped unless [`detach`](#method.detach) is called.
+    pub fn from_id(id: u32) -> Result<Link> {
+        let fd = unsafe { libbpf_sys::bpf_link_get_fd_by_id(id) };
+        if fd < 0 {
+            let err = io::Error::last_os_error();
+            error!("error on bpf_link_get_fd_by_id: {}", err);
+            return Err(Error::IO(err));
+        }
+        Ok(Link::from_fd(fd))
+    }
+
+    /// Pin link to BPF FS
+    ///
+    /// The program stays attached until the file is removed, e.g. by
+    /// [`unpin`](#method.unpin), or the link is detached explicitly by
+    /// [`detach`](#method.detach).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use redbpf::load::Loader;
+    /// let mut loaded = Loader::load_file("file.elf").expect("error loading probe");
+    /// let kprobe = loaded.kprobe_mut("vfs_read").expect("kprobe not found");
+    /// let mut link = kprobe.link_kprobe("vfs_read", 0).expect("error on linking kprobe");
+    /// link.pin("/sys/fs/bpf/kprobe_link").expect("error on pinning");
+    /// ```
+    pub fn pin(&mut self, file: impl AsRef<Path>) -> Result<()> {
+        let file = file.as_ref();
+        if self.pin_file.is_some() {
+            error!("already pinned");
+            return Err(Error::BPF);
+        }
+        pin_bpf_obj(self.fd, file)?;
+        self.pin_file = Some(Box::from(file));
+        Ok(())
+    }
+
+    /// Unpin link
+    ///
+    /// The program is detached when the `Link` is dropped after this.
+    pub fn unpin(&mut self) -> Result<()> {
+        if self.pin_file.is_none() {
+            error!("not pinned");
+            return Err(Error::BPF);
+        }
+        unpin_bpf_obj(self.pin_file.as_ref().unwrap())?;
+        self.pin_file = None;
+        Ok(())
+    }
+
+    /// Detach the program regardless of whether the link is pinned or not
+    ///
+    /// A pinned link remains in BPF FS but it does not refer to any hook any
+    /// more.
+    pub fn detach(&mut self) -> Result<()> {
+        if unsafe { libbpf_sys::bpf_link_detach(self.fd) } < 0 {
+            let err = io::Error::last_os_error();
+            error!("error on bpf_link_detach: {}", err);
+            return Err(Error::IO(err));
+        }
+        Ok(())
+    }
+
+    /// Replace the program of the link with `prog_fd` atomically
+    pub fn update_program(&self, prog_fd: RawFd) -> Result<()> {
+        if unsafe { libbpf_sys::bpf_link_update(self.fd, prog_fd, ptr::null()) } < 0 {
+            let err = io::Error::last_os_error();
+            error!("error on bpf_link_update: {}", err);
+            return Err(Error::IO(err));
+        }
+        Ok(())
+    }
+
+    /// The path that the link is pinned at
+    pub fn pin_file(&self) -> Option<&Path> {
+        self.pin_file.as_deref()
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for Link {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = libc::close(self.fd);
+            if let Some(pfd) = self.pfd.take() {
+                let _ = libc::close(pfd);
+            }
+        }
+    }
+}
+
+/// Whether the running kernel supports `kprobe_multi` links
+pub(crate) fn kprobe_multi_supported() -> bool {
+    *KPROBE_MULTI_SUPPORTED
+}
+
+/// Load `code` of a `kprobe` program to be attached with a `kprobe_multi`
+/// link
+///
+/// Such programs can not be attached with perf events, so a `kprobe` program
+/// that is loaded already is loaded once more for `kprobe_multi` links.
+pub(crate) fn load_kprobe_multi_program(
+    name: &str,
+    code: &[bpf_insn],
+    license: &str,
+) -> Result<RawFd> {
+    let cname = CString::new(name)?;
+    let clicense = CString::new(license)?;
+    let mut attr = unsafe { mem::zeroed::<bpf_load_program_attr>() };
+    attr.prog_type = BPF_PROG_TYPE_KPROBE;
+    attr.expected_attach_type = BPF_TRACE_KPROBE_MULTI;
+    attr.name = cname.as_ptr();
+    attr.insns = code.as_ptr();
+    attr.insns_cnt = code.len() as u64;
+    attr.license = clicense.as_ptr();
+    let fd = unsafe { libbpf_sys::bpf_load_program_xattr(&attr, ptr::null_mut(), 0) };
+    if fd < 0 {
+        let err = io::Error::last_os_error();
+        error!("error loading kprobe_multi program `{}': {}", name, err);
+        return Err(Error::IO(err));
+    }
+    Ok(fd)
+}
+
+unsafe fn create_kprobe_multi_link(attr: &KprobeMultiLinkCreateAttr) -> io::Result<RawFd> {
+    let fd = libc::syscall(
+        libc::SYS_bpf,
+        BPF_LINK_CREATE,
+        attr as *const KprobeMultiLinkCreateAttr,
+        mem::size_of::<KprobeMultiLinkCreateAttr>(),
+    );
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(fd as RawFd)
+}
+
+fn probe_kprobe_multi() -> bool {
+    // r0 = 0; exit
+    let mut code = unsafe { [mem::zeroed::<bpf_insn>(), mem::zeroed::<bpf_insn>()] };
+    code[0].code = 0xb7;
+    code[1].code = 0x95;
+    let prog_fd = match load_kprobe_multi_program("kprobe_multi", &code, "GPL") {
+        Ok(fd) => fd,
+        Err(_) => return false,
+    };
+    // Kernels that do not know the attach type fail with EINVAL before
+    // reading the addresses. Kernels that know it fail with EFAULT on reading
+    // the addresses from the invalid pointer, so nothing gets attached.
+    let attr = KprobeMultiLinkCreateAttr {
+        prog_fd: prog_fd as u32,
+        attach_type: BPF_TRACE_KPROBE_MULTI,
+        cnt: 1,
+        addrs: u64::MAX,
+        ..Default::default()
+    };
+    let result = unsafe { create_kprobe_multi_link(&attr) };
+    unsafe {
+        let _ = libc::close(prog_fd);
+    }
+    match result {
+        Ok(fd) => {
+            unsafe {
+                let _ = libc::close(fd);
+            }
+            true
+        }
+        Err(err) => {
+            debug!("kprobe_multi links are not supported: {}", err);
+            err.raw_os_error() == Some(libc::EFAULT)
+        }
+    }
+}
@@ -7,23 +7,96 @@
 
 use futures::channel::mpsc;
 use futures::prelude::*;
+use std::collections::{HashMap, HashSet};
 use std::convert::AsRef;
+use std::fmt;
 use std::fs;
 use std::io;
-use std::path::Path;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::{Arc, Mutex};
+use tracing::error;
 
 use crate::load::map_io::{PerfMessageStream, RingBufMessageStream};
+use crate::stats::ProgramStats;
+use crate::xdp::MapData;
 use crate::{cpus, Program, TracePoint};
 use crate::{
-    Error, KProbe, Map, Module, PerfMap, RingBufMap, SkLookup, SocketFilter, StreamParser,
-    StreamVerdict, TaskIter, UProbe, XDP,
+    BtfTracePoint, CGroup, Error, Iter, KProbe, Lsm, Map, Module, ModuleBuilder, PerfEvent,
+    PerfMap, RawTracePoint, RingBufMap, SkLookup, SkMsg, SocketFilter, StreamParser, StreamVerdict,
+    TaskIter, TcAction, Trampoline, UProbe, XDP,
 };
 
+/// Types of plain data that BPF programs send as events
+///
+/// Values of these types are made by copying the bytes of events, see
+/// [`Loaded::events_for`](struct.Loaded.html#method.events_for).
+///
+/// # Safety
+///
+/// Implement this only for `#[repr(C)]` or `#[repr(transparent)]` types that
+/// are valid for any bit pattern, e.g. integers and arrays or structs of
+/// them. Types such as `bool`, `char`, enums, references and `String` must
+/// not implement this. Wrap types defined in another crate, e.g. in the crate
+/// of the BPF programs, in a `#[repr(transparent)]` struct to implement this.
+pub unsafe trait PlainData {}
+
+macro_rules! impl_plain_data {
+    ($($t:ty),*) => {
+        $(unsafe impl PlainData for $t {})*
+    };
+}
+
+impl_plain_data!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: PlainData, const N: usize> PlainData for [T; N] {}
+
+unsafe impl<T: PlainData> PlainData for MapData<T> {}
+
+/// Read an event as `T` if its size is the size of `T` plus up to
+/// `padding` bytes
+fn read_event<T: PlainData>(event: &[u8], padding: usize) -> Option<T> {
+    let size = mem::size_of::<T>();
+    if event.len() < size || event.len() > size + padding {
+        return None;
+    }
+    Some(unsafe { ptr::read_unaligned(event.as_ptr() as *const T) })
+}
+
 #[derive(Debug)]
 pub enum LoaderError {
     FileError(io::Error),
     ParseError(Error),
     LoadError(String, Error),
+    /// A map could not be overridden, reused, pinned or bound to stream events
+    MapError(String, Error),
+}
+
+impl fmt::Display for LoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoaderError::FileError(e) => write!(f, "failed to read ELF file: {}", e),
+            LoaderError::ParseError(e) => write!(f, "failed to parse ELF file: {}", e),
+            // the name of the program is a part of the error already
+            LoaderError::LoadError(_, e @ Error::ProgramLoad { .. }) => write!(f, "{}", e),
+            LoaderError::LoadError(name, e) => {
+                write!(f, "failed to load program `{}': {}", name, e)
+            }
+            LoaderError::MapError(name, e) => write!(f, "failed to set up map `{}': {}", name, e),
+        }
+    }
+}
+
+impl std::error::Error for LoaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoaderError::FileError(e) => Some(e),
+            LoaderError::ParseError(e)
+            | LoaderError::LoadError(_, e)
+            | LoaderError::MapError(_, e) => Some(e),
+        }
+    }
 }
 
 /// High level API to load bpf programs.
@@ -33,27 +106,249 @@ impl Loader {
     /// Loads the programs included in `data`.
     ///
     /// This will parse `data` with `Module::parse()` and load all the programs
-    /// present in the module.
+    /// present in the module. Use [`LoaderBuilder`](struct.LoaderBuilder.html)
+    /// to change how the module is loaded.
     pub fn load(data: &[u8]) -> Result<Loaded, LoaderError> {
-        let mut module = Module::parse(&data).map_err(LoaderError::ParseError)?;
+        LoaderBuilder::new().load(data)
+    }
+
+    /// Loads the BPF programs included in `file`.
+    ///
+    /// See `load()`.
+    pub fn load_file<P: AsRef<Path>>(file: P) -> Result<Loaded, LoaderError> {
+        LoaderBuilder::new().load_file(file)
+    }
+}
+
+/// The number of pages of a perf buffer that a perf map is bound to by default
+const DEFAULT_PERF_PAGES: usize = 16;
+
+type EventSender = mpsc::UnboundedSender<(String, <PerfMessageStream as Stream>::Item)>;
+
+/// The senders of the maps whose events are taken by
+/// [`Loaded::events_for`](struct.Loaded.html#method.events_for)
+type EventRoutes = Arc<Mutex<HashMap<String, mpsc::UnboundedSender<Vec<Box<[u8]>>>>>>;
+
+/// A builder of [`Loaded`](struct.Loaded.html) with configurable options
+///
+/// [`Loader::load`](struct.Loader.html#method.load) is the same as loading
+/// with the default options.
+///
+/// # Example
+/// ```no_run
+/// use redbpf::load::LoaderBuilder;
+/// # async {
+/// let loaded = LoaderBuilder::new()
+///     .programs(&["trace_connect", "trace_accept"])
+///     .perf_buffer_pages("events", 64)
+///     .pin_dir("/sys/fs/bpf/myagent")
+///     .load_file("probe.elf")
+///     .expect("error loading probe");
+/// # };
+/// ```
+pub struct LoaderBuilder {
+    programs: Option<HashSet<String>>,
+    perf_buffer_pages: HashMap<String, usize>,
+    pin_dir: Option<PathBuf>,
+    map_overrides: Vec<(String, Map)>,
+    log_level: u32,
+    stream_events: bool,
+}
+
+impl Default for LoaderBuilder {
+    fn default() -> Self {
+        LoaderBuilder::new()
+    }
+}
+
+impl LoaderBuilder {
+    pub fn new() -> LoaderBuilder {
+        LoaderBuilder {
+            programs: None,
+            perf_buffer_pages: HashMap::new(),
+            pin_dir: None,
+            map_overrides: Vec::new(),
+            log_level: 1,
+            stream_events: true,
+        }
+    }
+
+    /// Load only the programs of the given names
+    ///
+    /// The other programs are left in the module without being loaded. All
+    /// programs are loaded by default. Loading fails if a name does not match
+    /// any program.
+    pub fn programs<I, S>(&mut self, names: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.programs = Some(names.into_iter().map(|n| n.as_ref().to_string()).collect());
+        self
+    }
+
+    /// Set the number of pages of the perf buffers of the perf map `map_name`
+    ///
+    /// `pages` must be a power of two, otherwise loading fails. Each perf
+    /// buffer of every online CPU has 16 pages by default.
+    pub fn perf_buffer_pages(&mut self, map_name: &str, pages: usize) -> &mut Self {
+        self.perf_buffer_pages.insert(map_name.to_string(), pages);
+        self
+    }
+
+    /// Share maps through BPF FS under `dir`
+    ///
+    /// A map is reused if a map of the same name is pinned at `dir` already,
+    /// e.g. by an earlier run of the process. Otherwise the map is created
+    /// and pinned there after loading. Maps of global variables are neither
+    /// reused nor pinned.
+    pub fn pin_dir(&mut self, dir: impl AsRef<Path>) -> &mut Self {
+        self.pin_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Use `map` instead of creating the map `map_name`
+    ///
+    /// See [`ModuleBuilder::replace_map`](../struct.ModuleBuilder.html#method.replace_map).
+    pub fn map_override(&mut self, map_name: &str, map: Map) -> &mut Self {
+        self.map_overrides.push((map_name.to_string(), map));
+        self
+    }
+
+    /// Set the level of the verifier log that is collected when loading a
+    /// program fails
+    ///
+    /// See [`Program::load_with_log_level`](../enum.Program.html#method.load_with_log_level).
+    pub fn verifier_log_level(&mut self, level: u32) -> &mut Self {
+        self.log_level = level;
+        self
+    }
+
+    /// Set whether events of perf maps and ring buffer maps are streamed to
+    /// [`Loaded::events`](struct.Loaded.html#structfield.events)
+    ///
+    /// Events are streamed by default, which requires a tokio runtime. If
+    /// this is turned off, the maps are not bound and the stream ends
+    /// without any events, so the maps can be consumed in other ways.
+    pub fn stream_events(&mut self, enable: bool) -> &mut Self {
+        self.stream_events = enable;
+        self
+    }
+
+    /// Loads the programs included in `data` with the options
+    ///
+    /// Maps given by [`map_override`](#method.map_override) are moved into
+    /// the loaded module. So they are not used by later calls.
+    pub fn load(&mut self, data: &[u8]) -> Result<Loaded, LoaderError> {
+        for (name, pages) in self.perf_buffer_pages.iter() {
+            if !pages.is_power_of_two() {
+                error!(
+                    "the number of perf buffer pages of `{}' is not a power of two: {}",
+                    name, pages
+                );
+                return Err(LoaderError::MapError(
+                    name.clone(),
+                    Error::IO(io::Error::from(io::ErrorKind::InvalidInput)),
+                ));
+            }
+        }
+        let mut builder = ModuleBuilder::parse(data).map_err(LoaderError::ParseError)?;
+        for (name, map) in self.map_overrides.drain(..) {
+            builder
+                .replace_map(&name, map)
+                .map_err(|e| LoaderError::MapError(name.clone(), e))?;
+        }
+        if let Some(dir) = self.pin_dir.as_ref() {
+            for name in builder.maps_to_create() {
+                let file = dir.join(&name);
+                if file.exists() {
+                    let map = Map::from_pin_file(&file)
+                        .map_err(|e| LoaderError::MapError(name.clone(), e))?;
+                    builder
+                        .replace_map(&name, map)
+                        .map_err(|e| LoaderError::MapError(name.clone(), e))?;
+                }
+            }
+        }
+        let mut module = builder.to_module().map_err(LoaderError::ParseError)?;
+        if let Some(names) = self.programs.as_ref() {
+            if let Some(name) = names.iter().find(|name| module.program(name).is_none()) {
+                error!("program of which name is `{}' not found", name);
+                return Err(LoaderError::LoadError(
+                    name.clone(),
+                    Error::SymbolNotFound(name.clone()),
+                ));
+            }
+        }
+
         for program in module.programs.iter_mut() {
+            if let Some(names) = self.programs.as_ref() {
+                if !names.contains(program.name()) {
+                    continue;
+                }
+            }
             program
-                .load(module.version, module.license.clone())
+                .load_with_log_level(module.version, module.license.clone(), self.log_level)
                 .map_err(|e| LoaderError::LoadError(program.name().to_string(), e))?;
         }
 
-        let online_cpus = cpus::get_online().unwrap();
+        if let Some(dir) = self.pin_dir.as_ref() {
+            for map in module
+                .maps
+                .iter_mut()
+                .filter(|m| !m.section_data && m.pin_file.is_none())
+            {
+                let file = dir.join(&map.name);
+                map.pin(&file)
+                    .map_err(|e| LoaderError::MapError(map.name.clone(), e))?;
+            }
+        }
+
         let (sender, receiver) = mpsc::unbounded();
+        let event_routes = EventRoutes::default();
+        if self.stream_events {
+            self.spawn_event_streams(&mut module, sender, &event_routes)?;
+        }
+
+        Ok(Loaded {
+            module,
+            events: receiver,
+            events_streamed: self.stream_events,
+            event_routes,
+        })
+    }
+
+    /// Loads the BPF programs included in `file` with the options
+    ///
+    /// See `load()`.
+    pub fn load_file<P: AsRef<Path>>(&mut self, file: P) -> Result<Loaded, LoaderError> {
+        self.load(&fs::read(file).map_err(LoaderError::FileError)?)
+    }
+
+    fn spawn_event_streams(
+        &self,
+        module: &mut Module,
+        sender: EventSender,
+        routes: &EventRoutes,
+    ) -> Result<(), LoaderError> {
+        let online_cpus = cpus::get_online().unwrap();
 
         // bpf_map_type_BPF_MAP_TYPE_PERF_EVENT_ARRAY = 4
         for m in module.maps.iter_mut().filter(|m| m.kind == 4) {
+            let pages = self
+                .perf_buffer_pages
+                .get(&m.name)
+                .copied()
+                .unwrap_or(DEFAULT_PERF_PAGES);
             for cpuid in online_cpus.iter() {
                 let name = m.name.clone();
-                let map = PerfMap::bind(m, -1, *cpuid, 16, -1, 0).unwrap();
+                let map = PerfMap::bind(m, -1, *cpuid, pages, -1, 0)
+                    .map_err(|e| LoaderError::MapError(name.clone(), e))?;
                 let stream = PerfMessageStream::new(name.clone(), map);
                 let mut s = sender.clone();
+                let routes = routes.clone();
                 let fut = stream.for_each(move |events| {
-                    s.start_send((name.clone(), events)).unwrap();
+                    route_events(&routes, &mut s, &name, events);
                     future::ready(())
                 });
                 tokio::spawn(fut);
@@ -63,28 +358,42 @@ impl Loader {
         // bpf_map_type_BPF_MAP_TYPE_RINGBUF
         for m in module.maps.iter_mut().filter(|m| m.kind == 27) {
             let name = m.name.clone();
-            let map = RingBufMap::bind(m).unwrap();
+            let map = RingBufMap::bind(m).map_err(|e| LoaderError::MapError(name.clone(), e))?;
             let stream = RingBufMessageStream::new(name.clone(), map);
             let mut s = sender.clone();
+            let routes = routes.clone();
             let fut = stream.for_each(move |events| {
-                s.start_send((name.clone(), events)).unwrap();
+                route_events(&routes, &mut s, &name, events);
                 future::ready(())
             });
             tokio::spawn(fut);
         }
-
-        Ok(Loaded {
-            module,
-            events: receiver,
-        })
+        Ok(())
     }
+}
 
-    /// Loads the BPF programs included in `file`.
-    ///
-    /// See `load()`.
-    pub fn load_file<P: AsRef<Path>>(file: P) -> Result<Loaded, LoaderError> {
-        Loader::load(&fs::read(file).map_err(LoaderError::FileError)?)
-    }
+/// Send `events` of the map `name` to the stream taken by
+/// [`Loaded::events_for`](struct.Loaded.html#method.events_for), or to
+/// [`Loaded::events`](struct.Loaded.html#structfield.events) if there is none
+fn route_events(
+    routes: &EventRoutes,
+    sender: &mut EventSender,
+    name: &str,
+    events: Vec<Box<[u8]>>,
+) {
+    let mut routes = routes.lock().unwrap();
+    let events = match routes.get(name) {
+        Some(map_sender) => match map_sender.unbounded_send(events) {
+            Ok(()) => return,
+            // the stream was dropped, so go back to `Loaded::events`
+            Err(e) => {
+                routes.remove(name);
+                e.into_inner()
+            }
+        },
+        None => events,
+    };
+    sender.start_send((name.to_string(), events)).unwrap();
 }
 
 /// The `Loaded` object returned by `load()`.
@@ -92,6 +401,9 @@ pub struct Loaded {
     pub module: Module,
     /// The stream of events emitted by the BPF programs.
     ///
+    /// The events of the maps taken by
+    /// [`events_for`](#method.events_for) are not included.
+    ///
     /// # Example
     ///
     /// ```no_run
@@ -108,6 +420,8 @@ pub struct Loaded {
     /// # };
     /// ```
     pub events: mpsc::UnboundedReceiver<(String, <PerfMessageStream as Stream>::Item)>,
+    events_streamed: bool,
+    event_routes: EventRoutes,
 }
 
 impl Loaded {
@@ -123,10 +437,104 @@ impl Loaded {
         self.module.program(name)
     }
 
+    /// Take the events of the perf map or ring buffer map `map_name` as a
+    /// stream of `T`
+    ///
+    /// `T` is the type of the events the BPF program sends, e.g. the `T` of
+    /// `redbpf_probes::maps::PerfMap<T>`, marked as
+    /// [`PlainData`](trait.PlainData.html). Events whose size does not match
+    /// `T` are dropped with an error logged. Perf buffers pad events to 8
+    /// bytes, so events of perf maps may be larger than `T` by the padding.
+    ///
+    /// The events of the map are no longer sent to
+    /// [`events`](#structfield.events) while the returned stream is alive.
+    /// But the events that arrive before this method is called, e.g. between
+    /// [`Loader::load`](struct.Loader.html#method.load) and this call, are
+    /// still sent to `events`. So call this method before attaching the
+    /// programs, or drain `events` too.
+    ///
+    /// Fails if the map is not a perf map or ring buffer map whose events are
+    /// streamed, see
+    /// [`LoaderBuilder::stream_events`](struct.LoaderBuilder.html#method.stream_events).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use futures::stream::StreamExt;
+    /// use redbpf::load::{Loader, PlainData};
+    ///
+    /// #[repr(C)]
+    /// struct Connection {
+    ///     pid: u32,
+    ///     port: u16,
+    /// }
+    ///
+    /// unsafe impl PlainData for Connection {}
+    ///
+    /// # async {
+    /// let mut loaded = Loader::load_file("probe.elf").expect("error loading probe");
+    /// let mut connections = loaded.events_for::<Connection>("connections").unwrap();
+    /// while let Some(conn) = connections.next().await {
+    ///     println!("pid {} connected to port {}", conn.pid, conn.port);
+    /// }
+    /// # };
+    /// ```
+    pub fn events_for<T: PlainData + Send + 'static>(
+        &mut self,
+        map_name: &str,
+    ) -> Result<impl Stream<Item = T> + Unpin, Error> {
+        let map = self.module.map(map_name).ok_or_else(|| {
+            error!("map of which name is `{}' not found", map_name);
+            Error::Map
+        })?;
+        // bpf_map_type_BPF_MAP_TYPE_PERF_EVENT_ARRAY = 4
+        // bpf_map_type_BPF_MAP_TYPE_RINGBUF = 27
+        let padding = match map.kind {
+            4 => 7,
+            27 => 0,
+            _ => return Err(map.mismatch("PerfMap or RingBufMap")),
+        };
+        if !self.events_streamed {
+            error!("events of map `{}' are not streamed", map_name);
+            return Err(Error::Map);
+        }
+        let mut routes = self.event_routes.lock().unwrap();
+        if matches!(routes.get(map_name), Some(sender) if !sender.is_closed()) {
+            error!("events of map `{}' are taken already", map_name);
+            return Err(Error::Map);
+        }
+        let (sender, receiver) = mpsc::unbounded();
+        routes.insert(map_name.to_string(), sender);
+
+        let name = map_name.to_string();
+        let size = mem::size_of::<T>();
+        Ok(receiver
+            .map(stream::iter)
+            .flatten()
+            .filter_map(move |event: Box<[u8]>| {
+                let value = read_event(&event, padding);
+                if value.is_none() {
+                    error!(
+                        "size of an event of map `{}' is {} bytes but expected {} bytes",
+                        name,
+                        event.len(),
+                        size
+                    );
+                }
+                future::ready(value)
+            }))
+    }
+
     pub fn program_mut(&mut self, name: &str) -> Option<&mut Program> {
         self.module.program_mut(name)
     }
 
+    /// Sum up the runtime statistics of all the programs
+    ///
+    /// See [`stats`](../stats/index.html) for enabling run time statistics.
+    pub fn stats(&self) -> Result<ProgramStats, Error> {
+        self.module.stats()
+    }
+
     pub fn kprobes_mut(&mut self) -> impl Iterator<Item = &mut KProbe> {
         self.module.kprobes_mut()
     }
@@ -159,6 +567,14 @@ impl Loaded {
         self.module.socket_filter_mut(name)
     }
 
+    pub fn tc_actions_mut(&mut self) -> impl Iterator<Item = &mut TcAction> {
+        self.module.tc_actions_mut()
+    }
+
+    pub fn tc_action_mut(&mut self, name: &str) -> Option<&mut TcAction> {
+        self.module.tc_action_mut(name)
+    }
+
     pub fn stream_parsers(&self) -> impl Iterator<Item = &StreamParser> {
         self.module.stream_parsers()
     }
@@ -183,6 +599,18 @@ impl Loaded {
         self.module.stream_verdict_mut(name)
     }
 
+    pub fn sk_msgs(&self) -> impl Iterator<Item = &SkMsg> {
+        self.module.sk_msgs()
+    }
+
+    pub fn sk_msgs_mut(&mut self) -> impl Iterator<Item = &mut SkMsg> {
+        self.module.sk_msgs_mut()
+    }
+
+    pub fn sk_msg_mut(&mut self, name: &str) -> Option<&mut SkMsg> {
+        self.module.sk_msg_mut(name)
+    }
+
     pub fn sk_lookups_mut(&mut self) -> impl Iterator<Item = &mut SkLookup> {
         self.module.sk_lookups_mut()
     }
@@ -195,7 +623,7 @@ impl Loaded {
         self.module.task_iters()
     }
 
-    pub fn bpf_iters_mut(&mut self) -> impl Iterator<Item = &mut TaskIter> {
+    pub fn task_iters_mut(&mut self) -> impl Iterator<Item = &mut TaskIter> {
         self.module.task_iters_mut()
     }
 
@@ -203,6 +631,18 @@ impl Loaded {
         self.module.task_iter_mut(name)
     }
 
+    pub fn bpf_iters(&self) -> impl Iterator<Item = &Iter> {
+        self.module.bpf_iters()
+    }
+
+    pub fn bpf_iters_mut(&mut self) -> impl Iterator<Item = &mut Iter> {
+        self.module.bpf_iters_mut()
+    }
+
+    pub fn bpf_iter_mut(&mut self, name: &str) -> Option<&mut Iter> {
+        self.module.bpf_iter_mut(name)
+    }
+
     pub fn tracepoints_mut(&mut self) -> impl Iterator<Item = &mut TracePoint> {
         self.module.trace_points_mut()
     }
@@ -210,4 +650,112 @@ impl Loaded {
     pub fn tracepoint_mut(&mut self, name: &str) -> Option<&mut TracePoint> {
         self.module.trace_point_mut(name)
     }
+
+    pub fn raw_tracepoints_mut(&mut self) -> impl Iterator<Item = &mut RawTracePoint> {
+        self.module.raw_trace_points_mut()
+    }
+
+    pub fn raw_tracepoint_mut(&mut self, name: &str) -> Option<&mut RawTracePoint> {
+        self.module.raw_trace_point_mut(name)
+    }
+
+    pub fn btf_tracepoints_mut(&mut self) -> impl Iterator<Item = &mut BtfTracePoint> {
+        self.module.btf_trace_points_mut()
+    }
+
+    pub fn btf_tracepoint_mut(&mut self, name: &str) -> Option<&mut BtfTracePoint> {
+        self.module.btf_trace_point_mut(name)
+    }
+
+    pub fn perf_events_mut(&mut self) -> impl Iterator<Item = &mut PerfEvent> {
+        self.module.perf_events_mut()
+    }
+
+    pub fn perf_event_mut(&mut self, name: &str) -> Option<&mut PerfEvent> {
+        self.module.perf_event_mut(name)
+    }
+
+    pub fn fentries_mut(&mut self) -> impl Iterator<Item = &mut Trampoline> {
+        self.module.fentries_mut()
+    }
+
+    pub fn fentry_mut(&mut self, name: &str) -> Option<&mut Trampoline> {
+        self.module.fentry_mut(name)
+    }
+
+    pub fn fexits_mut(&mut self) -> impl Iterator<Item = &mut Trampoline> {
+        self.module.fexits_mut()
+    }
+
+    pub fn fexit_mut(&mut self, name: &str) -> Option<&mut Trampoline> {
+        self.module.fexit_mut(name)
+    }
+
+    pub fn lsms_mut(&mut self) -> impl Iterator<Item = &mut Lsm> {
+        self.module.lsms_mut()
+    }
+
+    pub fn lsm_mut(&mut self, name: &str) -> Option<&mut Lsm> {
+        self.module.lsm_mut(name)
+    }
+
+    pub fn cgroup_skbs_mut(&mut self) -> impl Iterator<Item = &mut CGroup> {
+        self.module.cgroup_skbs_mut()
+    }
+
+    pub fn cgroup_skb_mut(&mut self, name: &str) -> Option<&mut CGroup> {
+        self.module.cgroup_skb_mut(name)
+    }
+
+    pub fn cgroup_socks_mut(&mut self) -> impl Iterator<Item = &mut CGroup> {
+        self.module.cgroup_socks_mut()
+    }
+
+    pub fn cgroup_sock_mut(&mut self, name: &str) -> Option<&mut CGroup> {
+        self.module.cgroup_sock_mut(name)
+    }
+
+    pub fn cgroup_sock_addrs_mut(&mut self) -> impl Iterator<Item = &mut CGroup> {
+        self.module.cgroup_sock_addrs_mut()
+    }
+
+    pub fn cgroup_sock_addr_mut(&mut self, name: &str) -> Option<&mut CGroup> {
+        self.module.cgroup_sock_addr_mut(name)
+    }
+
+    pub fn cgroup_sysctls_mut(&mut self) -> impl Iterator<Item = &mut CGroup> {
+        self.module.cgroup_sysctls_mut()
+    }
+
+    pub fn cgroup_sysctl_mut(&mut self, name: &str) -> Option<&mut CGroup> {
+        self.module.cgroup_sysctl_mut(name)
+    }
+}
+
+mod test {
+    #[test]
+    fn test_read_event() {
+        use crate::load::loader::read_event;
+
+        let event = [1u8, 0, 0, 0, 2, 0, 0, 0];
+        assert_eq!(
+            read_event::<[u32; 2]>(&event, 0),
+            Some([
+                u32::from_ne_bytes([1, 0, 0, 0]),
+                u32::from_ne_bytes([2, 0, 0, 0])
+            ])
+        );
+        // shorter than the type
+        assert_eq!(read_event::<u64>(&event[..4], 7), None);
+        // within the padding
+        assert_eq!(
+            read_event::<u32>(&event, 7),
+            Some(u32::from_ne_bytes([1, 0, 0, 0]))
+        );
+        assert_eq!(read_event::<u8>(&event, 7), Some(1));
+        // beyond the padding
+        assert_eq!(read_event::<u32>(&event, 3), None);
+        assert_eq!(read_event::<u16>(&event, 0), None);
+        assert_eq!(read_event::<u8>(&[], 7), None);
+    }
 }
@@ -0,0 +1,415 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*!
+Enumeration of the BPF objects of the system.
+
+[`programs`](fn.programs.html), [`maps`](fn.maps.html) and
+[`links`](fn.links.html) list all the BPF programs, maps and links that are
+loaded into the kernel, not only the ones loaded by this process. Each
+object is identified by its id, which can be used to open the object with
+[`Program::from_id`](../enum.Program.html#method.from_id),
+[`Map::from_id`](../struct.Map.html#method.from_id) or
+[`Link::from_id`](../link/struct.Link.html#method.from_id).
+
+An object stays loaded as long as it is referenced, e.g. by a file of BPF FS
+or by a link. The `pinned` fields list the files of BPF FS mounted at
+`/sys/fs/bpf` that refer to each object. Removing those files releases the
+objects left by processes that have died.
+
+Listing the objects requires `CAP_SYS_ADMIN`.
+
+# Example
+```no_run
+use redbpf::objects;
+
+for prog in objects::programs().expect("error on listing programs") {
+    if prog.name == "stale_probe" {
+        for path in prog.pinned.iter() {
+            std::fs::remove_file(path).expect("error on unpinning");
+        }
+    }
+}
+```
+*/
+
+use std::collections::HashMap as RSHashMap;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+
+use libbpf_sys::{
+    bpf_attach_type, bpf_link_info, bpf_link_type, bpf_map_type, bpf_prog_type,
+    BPF_LINK_TYPE_CGROUP, BPF_LINK_TYPE_ITER, BPF_LINK_TYPE_NETNS, BPF_LINK_TYPE_PERF_EVENT,
+    BPF_LINK_TYPE_RAW_TRACEPOINT, BPF_LINK_TYPE_TRACING, BPF_LINK_TYPE_XDP,
+};
+use tracing::error;
+
+use crate::error::{Error, Result};
+use crate::{get_map_info, get_prog_info};
+
+/// The mount point of BPF FS that is searched for pinned objects
+const BPF_FS: &str = "/sys/fs/bpf";
+
+/// A BPF program loaded into the kernel
+#[derive(Debug, Clone)]
+pub struct ProgramInfo {
+    pub id: u32,
+    pub prog_type: bpf_prog_type,
+    pub name: String,
+    /// The hash of the instructions of the program
+    pub tag: [u8; 8],
+    /// The time the program was loaded at, in nanoseconds since boot
+    pub load_time: u64,
+    pub created_by_uid: u32,
+    /// The ids of the maps the program uses
+    pub map_ids: Vec<u32>,
+    /// The id of the BTF of the program, or zero if it has no BTF
+    pub btf_id: u32,
+    /// The files of BPF FS the program is pinned at
+    pub pinned: Vec<PathBuf>,
+}
+
+/// A BPF map created in the kernel
+#[derive(Debug, Clone)]
+pub struct MapInfo {
+    pub id: u32,
+    pub map_type: bpf_map_type,
+    pub name: String,
+    pub key_size: u32,
+    pub value_size: u32,
+    pub max_entries: u32,
+    pub map_flags: u32,
+    /// The id of the BTF of the map, or zero if it has no BTF
+    pub btf_id: u32,
+    /// The files of BPF FS the map is pinned at
+    pub pinned: Vec<PathBuf>,
+}
+
+/// A BPF link that attaches a program to a hook
+#[derive(Debug, Clone)]
+pub struct LinkInfo {
+    pub id: u32,
+    pub link_type: bpf_link_type,
+    /// The id of the attached program
+    pub prog_id: u32,
+    pub target: LinkTarget,
+    /// The files of BPF FS the link is pinned at
+    pub pinned: Vec<PathBuf>,
+}
+
+/// The hook a BPF link attaches its program to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkTarget {
+    RawTracePoint {
+        name: String,
+    },
+    /// `fentry`, `fexit`, `tp_btf` and LSM programs
+    Tracing {
+        attach_type: bpf_attach_type,
+        /// The id of the BTF object of the target, or zero for `vmlinux`
+        target_obj_id: u32,
+        target_btf_id: u32,
+    },
+    CGroup {
+        cgroup_id: u64,
+        attach_type: bpf_attach_type,
+    },
+    Iter,
+    NetNs {
+        netns_ino: u32,
+        attach_type: bpf_attach_type,
+    },
+    Xdp {
+        ifindex: u32,
+    },
+    PerfEvent,
+    /// A link type unknown to redbpf
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ObjectKind {
+    Program,
+    Map,
+    Link,
+}
+
+/// List all the BPF programs of the system
+pub fn programs() -> Result<Vec<ProgramInfo>> {
+    let mut pinned = pinned_objects(ObjectKind::Program);
+    let mut infos = vec![];
+    for_each_id(
+        libbpf_sys::bpf_prog_get_next_id,
+        libbpf_sys::bpf_prog_get_fd_by_id,
+        |id, fd| {
+            let info = get_prog_info(fd)?;
+            let map_ids = get_prog_map_ids(fd, info.nr_map_ids)?;
+            infos.push(ProgramInfo {
+                id,
+                prog_type: info.type_,
+                name: c_chars_to_string(&info.name),
+                tag: info.tag,
+                load_time: info.load_time,
+                created_by_uid: info.created_by_uid,
+                map_ids,
+                btf_id: info.btf_id,
+                pinned: pinned.remove(&id).unwrap_or_default(),
+            });
+            Ok(())
+        },
+    )?;
+    Ok(infos)
+}
+
+/// List all the BPF maps of the system
+pub fn maps() -> Result<Vec<MapInfo>> {
+    let mut pinned = pinned_objects(ObjectKind::Map);
+    let mut infos = vec![];
+    for_each_id(
+        libbpf_sys::bpf_map_get_next_id,
+        libbpf_sys::bpf_map_get_fd_by_id,
+        |id, fd| {
+            let info = get_map_info(fd)?;
+            infos.push(MapInfo {
+                id,
+                map_type: info.type_,
+                name: c_chars_to_string(&info.name),
+                key_size: info.key_size,
+                value_size: info.value_size,
+                max_entries: info.max_entries,
+                map_flags: info.map_flags,
+                btf_id: info.btf_id,
+                pinned: pinned.remove(&id).unwrap_or_default(),
+            });
+            Ok(())
+        },
+    )?;
+    Ok(infos)
+}
+
+/// List all the BPF links of the system
+pub fn links() -> Result<Vec<LinkInfo>> {
+    let mut pinned = pinned_objects(ObjectKind::Link);
+    let mut infos = vec![];
+    for_each_id(
+        libbpf_sys::bpf_link_get_next_id,
+        libbpf_sys::bpf_link_get_fd_by_id,
+        |id, fd| {
+            let info = get_link_info(fd)?;
+            let target = link_target(fd, &info)?;
+            infos.push(LinkInfo {
+                id,
+                link_type: info.type_,
+                prog_id: info.prog_id,
+                target,
+                pinned: pinned.remove(&id).unwrap_or_default(),
+            });
+            Ok(())
+        },
+    )?;
+    Ok(infos)
+}
+
+/// Call `f` with the id and a fd of every object
+///
+/// The objects that are released while iterating are skipped.
+fn for_each_id(
+    get_next_id: unsafe extern "C" fn(u32, *mut u32) -> i32,
+    get_fd_by_id: unsafe extern "C" fn(u32) -> i32,
+    mut f: impl FnMut(u32, RawFd) -> Result<()>,
+) -> Result<()> {
+    let mut id = 0;
+    loop {
+        if unsafe { get_next_id(id, &mut id) } < 0 {
+            let err = io::Error::last_os_error();
+            if let Some(libc::ENOENT) = err.raw_os_error() {
+                return Ok(());
+            }
+            error!("error on getting next id: {}", err);
+            return Err(Error::IO(err));
+        }
+        let fd = unsafe { get_fd_by_id(id) };
+        if fd < 0 {
+            let err = io::Error::last_os_error();
+            if let Some(libc::ENOENT) = err.raw_os_error() {
+                continue;
+            }
+            error!("error on getting fd of id {}: {}", id, err);
+            return Err(Error::IO(err));
+        }
+        let ret = f(id, fd);
+        unsafe {
+            let _ = libc::close(fd);
+        }
+        ret?;
+    }
+}
+
+fn get_prog_map_ids(fd: RawFd, nr_map_ids: u32) -> Result<Vec<u32>> {
+    let mut map_ids = vec![0u32; nr_map_ids as usize];
+    if map_ids.is_empty() {
+        return Ok(map_ids);
+    }
+    unsafe {
+        let mut info = mem::zeroed::<libbpf_sys::bpf_prog_info>();
+        info.nr_map_ids = nr_map_ids;
+        info.map_ids = map_ids.as_mut_ptr() as u64;
+        let mut info_len = mem::size_of_val(&info) as u32;
+        if libbpf_sys::bpf_obj_get_info_by_fd(fd, &mut info as *mut _ as *mut _, &mut info_len) != 0
+        {
+            let err = io::Error::last_os_error();
+            error!("error on bpf_obj_get_info_by_fd: {}", err);
+            return Err(Error::IO(err));
+        }
+        // maps can not be added to a loaded program but be conservative
+        map_ids.truncate(info.nr_map_ids.min(nr_map_ids) as usize);
+    }
+    Ok(map_ids)
+}
+
+fn get_link_info(fd: RawFd) -> Result<bpf_link_info> {
+    unsafe {
+        let mut info = mem::zeroed::<bpf_link_info>();
+        let mut info_len = mem::size_of_val(&info) as u32;
+        if libbpf_sys::bpf_obj_get_info_by_fd(fd, &mut info as *mut _ as *mut _, &mut info_len) != 0
+        {
+            let err = io::Error::last_os_error();
+            error!("error on bpf_obj_get_info_by_fd: {}", err);
+            return Err(Error::IO(err));
+        }
+        Ok(info)
+    }
+}
+
+fn link_target(fd: RawFd, info: &bpf_link_info) -> Result<LinkTarget> {
+    let target = unsafe {
+        let u = &info.__bindgen_anon_1;
+        match info.type_ {
+            BPF_LINK_TYPE_RAW_TRACEPOINT => LinkTarget::RawTracePoint {
+                name: get_raw_tracepoint_name(fd, u.raw_tracepoint.tp_name_len)?,
+            },
+            BPF_LINK_TYPE_TRACING => LinkTarget::Tracing {
+                attach_type: u.tracing.attach_type,
+                target_obj_id: u.tracing.target_obj_id,
+                target_btf_id: u.tracing.target_btf_id,
+            },
+            BPF_LINK_TYPE_CGROUP => LinkTarget::CGroup {
+                cgroup_id: u.cgroup.cgroup_id,
+                attach_type: u.cgroup.attach_type,
+            },
+            BPF_LINK_TYPE_ITER => LinkTarget::Iter,
+            BPF_LINK_TYPE_NETNS => LinkTarget::NetNs {
+                netns_ino: u.netns.netns_ino,
+                attach_type: u.netns.attach_type,
+            },
+            BPF_LINK_TYPE_XDP => LinkTarget::Xdp {
+                ifindex: u.xdp.ifindex,
+            },
+            BPF_LINK_TYPE_PERF_EVENT => LinkTarget::PerfEvent,
+            _ => LinkTarget::Unknown,
+        }
+    };
+    Ok(target)
+}
+
+/// Read the name of the tracepoint of a raw tracepoint link
+///
+/// The kernel copies the name only if a buffer is passed.
+fn get_raw_tracepoint_name(fd: RawFd, tp_name_len: u32) -> Result<String> {
+    if tp_name_len == 0 {
+        return Ok(String::new());
+    }
+    let mut buf = vec![0u8; tp_name_len as usize];
+    unsafe {
+        let mut info = mem::zeroed::<bpf_link_info>();
+        info.__bindgen_anon_1.raw_tracepoint.tp_name = buf.as_mut_ptr() as u64;
+        info.__bindgen_anon_1.raw_tracepoint.tp_name_len = tp_name_len;
+        let mut info_len = mem::size_of_val(&info) as u32;
+        if libbpf_sys::bpf_obj_get_info_by_fd(fd, &mut info as *mut _ as *mut _, &mut info_len) != 0
+        {
+            let err = io::Error::last_os_error();
+            error!("error on bpf_obj_get_info_by_fd: {}", err);
+            return Err(Error::IO(err));
+        }
+    }
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    buf.truncate(len);
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// Find the files of BPF FS that refer to objects of `kind`
+///
+/// Returns the paths grouped by the ids of the objects. Files that can not
+/// be opened are ignored.
+fn pinned_objects(kind: ObjectKind) -> RSHashMap<u32, Vec<PathBuf>> {
+    let mut pinned = RSHashMap::new();
+    let mut dirs = vec![PathBuf::from(BPF_FS)];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => dirs.push(path),
+                Ok(file_type) if file_type.is_file() => {
+                    if let Some(id) = pinned_object_id(&path, kind) {
+                        pinned.entry(id).or_insert_with(Vec::new).push(path);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    pinned
+}
+
+/// Get the id of the object pinned at `path` if it is an object of `kind`
+fn pinned_object_id(path: &Path, kind: ObjectKind) -> Option<u32> {
+    let cpath = CString::new(path.to_str()?).ok()?;
+    let fd = unsafe { libbpf_sys::bpf_obj_get(cpath.as_ptr()) };
+    if fd < 0 {
+        return None;
+    }
+    let id = match object_kind(fd) {
+        Some(k) if k == kind => match kind {
+            ObjectKind::Program => get_prog_info(fd).ok().map(|info| info.id),
+            ObjectKind::Map => get_map_info(fd).ok().map(|info| info.id),
+            ObjectKind::Link => get_link_info(fd).ok().map(|info| info.id),
+        },
+        _ => None,
+    };
+    unsafe {
+        let _ = libc::close(fd);
+    }
+    id
+}
+
+/// Tell the kind of the BPF object of `fd` from the name of its anonymous
+/// inode
+fn object_kind(fd: RawFd) -> Option<ObjectKind> {
+    let target = fs::read_link(format!("/proc/self/fd/{}", fd)).ok()?;
+    match target.to_str()? {
+        "anon_inode:bpf-prog" => Some(ObjectKind::Program),
+        "anon_inode:bpf-map" => Some(ObjectKind::Map),
+        "anon_inode:bpf_link" => Some(ObjectKind::Link),
+        _ => None,
+    }
+}
+
+fn c_chars_to_string(chars: &[libc::c_char]) -> String {
+    unsafe {
+        CStr::from_ptr(chars.as_ptr())
+            .to_string_lossy()
+            .into_owned()
+    }
+}
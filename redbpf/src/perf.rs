@@ -100,6 +100,10 @@ pub(crate) unsafe fn attach_perf_event(prog_fd: RawFd, pfd: RawFd) -> Result<()>
         return Err(Error::IO(io::Error::last_os_error()));
     }
 
+    enable_perf_event(pfd)
+}
+
+pub(crate) unsafe fn enable_perf_event(pfd: RawFd) -> Result<()> {
     if ioctl(pfd, PERF_EVENT_IOC_ENABLE, 0) < 0 {
         return Err(Error::IO(io::Error::last_os_error()));
     }
@@ -114,6 +118,41 @@ pub(crate) unsafe fn detach_perf_event(pfd: RawFd) -> Result<()> {
     Ok(())
 }
 
+/// Open a sampling perf event that fires `sample_freq` times per second.
+///
+/// `type_` and `config` are passed through to `perf_event_open(2)` as is, so
+/// any software or hardware event supported by the running kernel can be
+/// used.
+pub(crate) unsafe fn open_sampling_perf_event(
+    type_: u32,
+    config: u64,
+    sample_freq: u64,
+    pid: i32,
+    cpu: i32,
+) -> Result<RawFd> {
+    let mut attr = mem::zeroed::<perf_event_attr>();
+
+    attr.size = mem::size_of::<perf_event_attr>() as u32;
+    attr.type_ = type_;
+    attr.config = config;
+    attr.set_freq(1);
+    attr.__bindgen_anon_1.sample_freq = sample_freq;
+
+    let pfd = syscall(
+        SYS_perf_event_open,
+        &attr as *const perf_event_attr,
+        pid,
+        cpu,
+        -1, // group_fd
+        PERF_FLAG_FD_CLOEXEC,
+    );
+    if pfd < 0 {
+        Err(Error::IO(io::Error::last_os_error()))
+    } else {
+        Ok(pfd as RawFd)
+    }
+}
+
 unsafe fn perf_event_open_kprobe(name: &str, offset: u64, retprobe: bool) -> Result<RawFd> {
     let mut attr = mem::zeroed::<perf_event_attr>();
     let type_ = fs::read_to_string("/sys/bus/event_source/devices/kprobe/type")
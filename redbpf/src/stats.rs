@@ -0,0 +1,138 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*!
+Runtime statistics of BPF programs.
+
+The kernel counts how many times each BPF program has run and how long it
+took, but only while run time statistics are enabled because measuring them
+adds overhead to every run. They are enabled as long as a
+[`StatsEnabled`](struct.StatsEnabled.html) returned by
+[`enable_stats`](fn.enable_stats.html) is alive, or while the
+`kernel.bpf_stats_enabled` sysctl is set.
+
+The statistics of a program are read by
+[`Program::stats`](../enum.Program.html#method.stats) and summed up across
+all the programs of a module by
+[`Module::stats`](../struct.Module.html#method.stats).
+
+# Example
+```no_run
+use redbpf::load::Loader;
+use redbpf::stats;
+
+let _enabled = stats::enable_stats().expect("error on enabling stats");
+let loaded = Loader::load_file("probe.elf").expect("error loading probe");
+// attach the programs and let them run for a while
+for prog in loaded.module.programs.iter() {
+    let stats = prog.stats().expect("error on reading stats");
+    println!("{}: {} runs, {} ns", prog.name(), stats.run_cnt, stats.run_time_ns);
+}
+let total = loaded.stats().expect("error on reading stats");
+println!("total: {:?} ns per run", total.avg_run_time_ns());
+```
+*/
+
+use std::io;
+use std::ops::{Add, AddAssign};
+use std::os::unix::io::RawFd;
+
+use libbpf_sys::{bpf_prog_info, BPF_STATS_RUN_TIME};
+use tracing::error;
+
+use crate::error::{Error, Result};
+
+/// Runtime statistics of a BPF program
+///
+/// `run_cnt`, `run_time_ns` and `recursion_misses` are counted only while
+/// run time statistics are enabled. `verified_insns` is reported by Linux
+/// 5.16 or later and is zero on older kernels.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramStats {
+    /// The number of times the program has run
+    pub run_cnt: u64,
+    /// The total time the program has run for, in nanoseconds
+    pub run_time_ns: u64,
+    /// The number of times the program was skipped because another BPF
+    /// program was running on the same CPU
+    pub recursion_misses: u64,
+    /// The number of instructions the verifier processed to verify the
+    /// program
+    pub verified_insns: u32,
+    /// The size of the JIT-compiled program in bytes
+    pub jited_prog_len: u32,
+}
+
+impl ProgramStats {
+    /// The average run time of the program in nanoseconds
+    ///
+    /// Returns `None` if the program has not run.
+    pub fn avg_run_time_ns(&self) -> Option<u64> {
+        self.run_time_ns.checked_div(self.run_cnt)
+    }
+}
+
+impl From<&bpf_prog_info> for ProgramStats {
+    fn from(info: &bpf_prog_info) -> ProgramStats {
+        ProgramStats {
+            run_cnt: info.run_cnt,
+            run_time_ns: info.run_time_ns,
+            recursion_misses: info.recursion_misses,
+            verified_insns: info.verified_insns,
+            jited_prog_len: info.jited_prog_len,
+        }
+    }
+}
+
+impl Add for ProgramStats {
+    type Output = ProgramStats;
+
+    fn add(self, other: ProgramStats) -> ProgramStats {
+        ProgramStats {
+            run_cnt: self.run_cnt + other.run_cnt,
+            run_time_ns: self.run_time_ns + other.run_time_ns,
+            recursion_misses: self.recursion_misses + other.recursion_misses,
+            verified_insns: self.verified_insns + other.verified_insns,
+            jited_prog_len: self.jited_prog_len + other.jited_prog_len,
+        }
+    }
+}
+
+impl AddAssign for ProgramStats {
+    fn add_assign(&mut self, other: ProgramStats) {
+        *self = *self + other;
+    }
+}
+
+/// A handle that keeps run time statistics of BPF programs enabled
+///
+/// The statistics are disabled when it is dropped unless other processes
+/// still keep them enabled.
+pub struct StatsEnabled {
+    fd: RawFd,
+}
+
+impl Drop for StatsEnabled {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = libc::close(self.fd);
+        }
+    }
+}
+
+/// Enable run time statistics of all BPF programs
+///
+/// This requires Linux 5.8 or later and `CAP_SYS_ADMIN`.
+pub fn enable_stats() -> Result<StatsEnabled> {
+    let fd = unsafe { libbpf_sys::bpf_enable_stats(BPF_STATS_RUN_TIME) };
+    if fd < 0 {
+        let err = io::Error::last_os_error();
+        error!("error on bpf_enable_stats: {}", err);
+        return Err(Error::IO(err));
+    }
+    Ok(StatsEnabled { fd })
+}
@@ -0,0 +1,403 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*!
+Symbolization of the addresses of stack traces.
+
+The addresses of [`BpfStackFrames`](../struct.BpfStackFrames.html) returned
+by [`StackTrace::get`](../struct.StackTrace.html#method.get) are translated to
+the names of the functions they belong to. Kernel stacks are symbolized by
+[`KernelSymbolizer`](struct.KernelSymbolizer.html) and user stacks by
+[`UserSymbolizer`](struct.UserSymbolizer.html).
+
+# Example
+```no_run
+use redbpf::load::Loader;
+use redbpf::symbolizer::KernelSymbolizer;
+use redbpf::StackTrace;
+
+let loaded = Loader::load_file("profile.elf").expect("error loading probe");
+let symbolizer = KernelSymbolizer::new().expect("error on reading kallsyms");
+let mut stack_trace = StackTrace::new(loaded.map("stack_trace").unwrap());
+# let stack_id = 0;
+if let Some(frames) = stack_trace.get(stack_id) {
+    for frame in symbolizer.symbolize_frames(&frames) {
+        println!("    {}", frame);
+    }
+}
+```
+*/
+
+use goblin::elf::program_header::ProgramHeader;
+use libc::pid_t;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use tracing::{debug, error};
+
+use crate::error::{Error, Result};
+use crate::kallsyms::parse_kallsyms_line;
+use crate::symbols::{proc_maps, ElfSymbols, ProcMap};
+use crate::BpfStackFrames;
+
+const DEBUG_DIR: &str = "/usr/lib/debug";
+
+/// A function of the kernel or of a kernel module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelSymbol {
+    pub addr: u64,
+    pub name: String,
+    /// The kernel module the function belongs to. `None` for the functions of
+    /// the kernel itself.
+    pub module: Option<String>,
+}
+
+/// An address resolved to a function and the offset into it
+///
+/// It is displayed as `symbol+offset [module]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedAddr<'a> {
+    pub symbol: &'a KernelSymbol,
+    pub offset: u64,
+}
+
+impl fmt::Display for ResolvedAddr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}+{:#x}", self.symbol.name, self.offset)?;
+        if let Some(module) = self.symbol.module.as_ref() {
+            write!(f, " [{}]", module)?;
+        }
+        Ok(())
+    }
+}
+
+/// Symbolizer of kernel addresses backed by `/proc/kallsyms`
+///
+/// The symbol table is read once when the symbolizer is created and is
+/// reused by all lookups. Call [`reload`](#method.reload) to pick up kernel
+/// modules loaded after that.
+///
+/// Reading the addresses of kallsyms requires `CAP_SYSLOG` when
+/// `kernel.kptr_restrict` is 1.
+pub struct KernelSymbolizer {
+    // sorted by address
+    symbols: Vec<KernelSymbol>,
+}
+
+impl KernelSymbolizer {
+    /// Read the symbol table from `/proc/kallsyms`
+    pub fn new() -> Result<KernelSymbolizer> {
+        Ok(KernelSymbolizer {
+            symbols: read_kernel_functions()?,
+        })
+    }
+
+    /// Read the symbol table again
+    pub fn reload(&mut self) -> Result<()> {
+        self.symbols = read_kernel_functions()?;
+        Ok(())
+    }
+
+    /// Find the function that `addr` belongs to
+    ///
+    /// Returns `None` if `addr` is below every function of the kernel.
+    pub fn resolve(&self, addr: u64) -> Option<ResolvedAddr<'_>> {
+        // the index of the first symbol after `addr`
+        let idx = self.symbols.partition_point(|sym| sym.addr <= addr);
+        let symbol = self.symbols.get(idx.checked_sub(1)?)?;
+        Some(ResolvedAddr {
+            symbol,
+            offset: addr - symbol.addr,
+        })
+    }
+
+    /// Symbolize `addr` as `symbol+offset [module]`
+    ///
+    /// The address itself is returned in hexadecimal if it can not be
+    /// resolved.
+    pub fn symbolize(&self, addr: u64) -> String {
+        match self.resolve(addr) {
+            Some(resolved) => resolved.to_string(),
+            None => format!("{:#x}", addr),
+        }
+    }
+
+    /// Symbolize the addresses of a kernel stack trace from the innermost
+    /// frame
+    pub fn symbolize_frames(&self, frames: &BpfStackFrames) -> Vec<String> {
+        frames
+            .ip
+            .iter()
+            .take_while(|&&ip| ip != 0)
+            .map(|&ip| self.symbolize(ip))
+            .collect()
+    }
+}
+
+fn read_kernel_functions() -> Result<Vec<KernelSymbol>> {
+    let content = fs::read_to_string("/proc/kallsyms").map_err(|e| {
+        error!("error on reading /proc/kallsyms: {}", e);
+        Error::IO(e)
+    })?;
+    let mut symbols: Vec<KernelSymbol> = content
+        .lines()
+        .filter_map(parse_kallsyms_line)
+        .filter(|sym| sym.is_function() && sym.addr != 0)
+        .map(|sym| KernelSymbol {
+            addr: sym.addr,
+            name: sym.name.to_string(),
+            module: sym.module.map(String::from),
+        })
+        .collect();
+    if symbols.is_empty() {
+        // kptr_restrict hides all the addresses as zero
+        error!("addresses of /proc/kallsyms are not readable");
+        return Err(Error::IO(io::Error::from(io::ErrorKind::PermissionDenied)));
+    }
+    symbols.sort_by_key(|sym| sym.addr);
+    Ok(symbols)
+}
+
+/// A user space address resolved to the file it is mapped from and the
+/// function it belongs to
+///
+/// It is displayed like the frames of `perf script`, i.e. as
+/// `<address> <function>+<offset> (<module>)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserSymbol {
+    pub addr: u64,
+    /// The path of the file the address is mapped from
+    pub module: String,
+    /// The offset of the address into `module`
+    pub module_offset: u64,
+    /// The demangled name of the function and the offset into it. `None` if
+    /// no symbol of `module` covers the address.
+    pub function: Option<(String, u64)>,
+}
+
+impl fmt::Display for UserSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.function.as_ref() {
+            Some((name, offset)) => write!(
+                f,
+                "{:16x} {}+{:#x} ({})",
+                self.addr, name, offset, self.module
+            ),
+            None => write!(f, "{:16x} [unknown] ({})", self.addr, self.module),
+        }
+    }
+}
+
+/// Symbolizer of the user space addresses of a process
+///
+/// Addresses are resolved to the files mapped to the memory of the process
+/// by `/proc/<pid>/maps`, and then to functions by the symbol tables of the
+/// files. If a file is stripped, the symbols are read from its debug file
+/// found by the build ID or by `.gnu_debuglink` under `/usr/lib/debug`, like
+/// gdb does. Rust and C++ symbols are demangled.
+///
+/// The symbols of a file are read when an address of it is resolved for the
+/// first time and are reused by later lookups. Call
+/// [`reload`](#method.reload) when the process maps new files, e.g. by
+/// `dlopen(3)`.
+pub struct UserSymbolizer {
+    pid: pid_t,
+    maps: Vec<ProcMap>,
+    // `None` if the symbols of the file can not be read
+    modules: HashMap<String, Option<ModuleSymbols>>,
+}
+
+impl UserSymbolizer {
+    /// Read the memory mappings of the process `pid`
+    pub fn new(pid: pid_t) -> Result<UserSymbolizer> {
+        let mut symbolizer = UserSymbolizer {
+            pid,
+            maps: Vec::new(),
+            modules: HashMap::new(),
+        };
+        symbolizer.reload()?;
+        Ok(symbolizer)
+    }
+
+    /// Read the memory mappings of the process again
+    ///
+    /// The symbols of the files that were already read are kept.
+    pub fn reload(&mut self) -> Result<()> {
+        self.maps = proc_maps(self.pid).map_err(|e| {
+            error!("error on reading /proc/{}/maps: {}", self.pid, e);
+            Error::IO(e)
+        })?;
+        Ok(())
+    }
+
+    /// Find the file `addr` is mapped from and the offset into the file
+    pub fn module_offset(&self, addr: u64) -> Option<(&str, u64)> {
+        let map = self
+            .maps
+            .iter()
+            .find(|map| map.start <= addr && addr < map.end)?;
+        Some((map.path.as_str(), addr - map.start + map.offset))
+    }
+
+    /// Find the file and the function that `addr` belongs to
+    ///
+    /// Returns `None` if `addr` is not in an executable mapping of a file.
+    pub fn resolve(&mut self, addr: u64) -> Option<UserSymbol> {
+        let (module, module_offset) = self.module_offset(addr)?;
+        let module = module.to_string();
+        let pid = self.pid;
+        let function = self
+            .modules
+            .entry(module.clone())
+            .or_insert_with(|| ModuleSymbols::load(pid, &module))
+            .as_ref()
+            .and_then(|symbols| symbols.resolve(module_offset))
+            .map(|(name, offset)| (demangle(name), offset));
+        Some(UserSymbol {
+            addr,
+            module,
+            module_offset,
+            function,
+        })
+    }
+
+    /// Symbolize `addr` like `perf script` does
+    pub fn symbolize(&mut self, addr: u64) -> String {
+        match self.resolve(addr) {
+            Some(symbol) => symbol.to_string(),
+            None => format!("{:16x} [unknown] ([unknown])", addr),
+        }
+    }
+
+    /// Symbolize the addresses of a user stack trace from the innermost
+    /// frame
+    pub fn symbolize_frames(&mut self, frames: &BpfStackFrames) -> Vec<String> {
+        frames
+            .ip
+            .iter()
+            .take_while(|&&ip| ip != 0)
+            .map(|&ip| self.symbolize(ip))
+            .collect()
+    }
+}
+
+/// The functions of an ELF file mapped to the memory of a process
+struct ModuleSymbols {
+    segments: Vec<ProgramHeader>,
+    // `(address, size, name)` sorted by address
+    functions: Vec<(u64, u64, String)>,
+}
+
+impl ModuleSymbols {
+    fn load(pid: pid_t, path: &str) -> Option<ModuleSymbols> {
+        let data = read_proc_file(pid, path)
+            .map_err(|e| debug!("error on reading {}: {}", path, e))
+            .ok()?;
+        let elf = ElfSymbols::parse(&data)
+            .map_err(|e| debug!("error on parsing {}: {}", path, e))
+            .ok()?;
+        let mut functions = elf.functions();
+        if !elf.has_symtab() {
+            if let Some(debug_data) = find_debug_file(pid, path, &elf) {
+                if let Ok(debug_elf) = ElfSymbols::parse(&debug_data) {
+                    functions.extend(debug_elf.functions());
+                }
+            }
+        }
+        // prefer public names like `malloc` to their aliases like
+        // `__libc_malloc` at the same address
+        functions.sort_by_key(|func| (func.0, func.2.starts_with('_')));
+        functions.dedup_by_key(|func| func.0);
+        Some(ModuleSymbols {
+            segments: elf.load_segments(),
+            functions,
+        })
+    }
+
+    /// Find the function at `file_offset` of the file and the offset into it
+    fn resolve(&self, file_offset: u64) -> Option<(&str, u64)> {
+        // PIE executables and shared libraries are mapped at a random base
+        // address, so go through the file offset to the virtual address of
+        // the file the symbols are defined with
+        let segment = self.segments.iter().find(|phdr| {
+            phdr.p_offset <= file_offset && file_offset < phdr.p_offset + phdr.p_filesz
+        })?;
+        let vaddr = file_offset - segment.p_offset + segment.p_vaddr;
+        let idx = self.functions.partition_point(|func| func.0 <= vaddr);
+        let (start, size, name) = self.functions.get(idx.checked_sub(1)?)?;
+        // symbols without size extend to the next symbol
+        if *size != 0 && vaddr >= start + size {
+            return None;
+        }
+        Some((name.as_str(), vaddr - start))
+    }
+}
+
+/// Read `path` through the root directory of the process so that the files
+/// of processes in other mount namespaces are found
+fn read_proc_file(pid: pid_t, path: &str) -> io::Result<Vec<u8>> {
+    fs::read(format!("/proc/{}/root{}", pid, path)).or_else(|_| fs::read(path))
+}
+
+/// Find the separate debug file of `path` by its build ID or by its
+/// `.gnu_debuglink`
+fn find_debug_file(pid: pid_t, path: &str, elf: &ElfSymbols) -> Option<Vec<u8>> {
+    if let Some(build_id) = elf.build_id().filter(|id| id.len() > 1) {
+        let hex: String = build_id.iter().map(|b| format!("{:02x}", b)).collect();
+        let debug_path = format!("{}/.build-id/{}/{}.debug", DEBUG_DIR, &hex[..2], &hex[2..]);
+        if let Ok(data) = read_proc_file(pid, &debug_path) {
+            return Some(data);
+        }
+    }
+
+    let (name, crc) = elf.debuglink()?;
+    let dir = Path::new(path).parent()?.to_string_lossy();
+    let candidates = [
+        format!("{}/{}", dir, name),
+        format!("{}/.debug/{}", dir, name),
+        format!("{}{}/{}", DEBUG_DIR, dir, name),
+    ];
+    candidates
+        .iter()
+        .filter_map(|debug_path| read_proc_file(pid, debug_path).ok())
+        .find(|data| crc32fast::hash(data) == crc)
+}
+
+/// Demangle Rust and C++ symbols
+fn demangle(name: &str) -> String {
+    if let Ok(demangled) = rustc_demangle::try_demangle(name) {
+        // without the hash suffix
+        return format!("{:#}", demangled);
+    }
+    if name.starts_with("_Z") {
+        if let Ok(symbol) = cpp_demangle::Symbol::new(name) {
+            if let Ok(demangled) = symbol.demangle(&Default::default()) {
+                return demangled;
+            }
+        }
+    }
+    name.to_string()
+}
+
+mod test {
+    #[test]
+    fn test_demangle() {
+        use crate::symbolizer::demangle;
+        assert_eq!(
+            demangle("_ZN4core3ptr13drop_in_place17h0123456789abcdefE"),
+            "core::ptr::drop_in_place"
+        );
+        assert_eq!(
+            demangle("_ZNSt6vectorIiSaIiEE9push_backERKi"),
+            "std::vector<int, std::allocator<int> >::push_back(int const&)"
+        );
+        assert_eq!(demangle("malloc"), "malloc");
+    }
+}
@@ -6,6 +6,9 @@
 // copied, modified, or distributed except according to those terms.
 
 use byteorder::{NativeEndian, ReadBytesExt};
+use goblin::elf::note::NT_GNU_BUILD_ID;
+use goblin::elf::program_header::{ProgramHeader, PT_LOAD};
+use goblin::elf::sym::STT_GNU_IFUNC;
 use goblin::elf::{Elf, Sym};
 use libc::pid_t;
 use std::ffi::CStr;
@@ -24,13 +27,14 @@ lazy_static! {
 const CACHE_HEADER: &str = "glibc-ld.so.cache1.1";
 
 pub(crate) struct ElfSymbols<'a> {
+    data: &'a [u8],
     elf: Elf<'a>,
 }
 
 impl<'a> ElfSymbols<'a> {
-    pub fn parse(data: &[u8]) -> goblin::error::Result<ElfSymbols> {
+    pub fn parse(data: &'a [u8]) -> goblin::error::Result<ElfSymbols<'a>> {
         let elf = Elf::parse(&data)?;
-        Ok(ElfSymbols { elf })
+        Ok(ElfSymbols { data, elf })
     }
 
     fn resolve_dyn_syms(&self, sym_name: &str) -> Option<Sym> {
@@ -89,6 +93,67 @@ impl<'a> ElfSymbols<'a> {
         self.resolve_dyn_syms(sym_name)
             .or_else(|| self.resolve_syms(sym_name))
     }
+
+    /// Whether the ELF file has `.symtab`, i.e. it is not stripped
+    pub fn has_symtab(&self) -> bool {
+        !self.elf.syms.is_empty()
+    }
+
+    /// The functions of `.symtab` and `.dynsym` as `(address, size, name)`
+    ///
+    /// The addresses are the virtual addresses of the ELF file.
+    pub fn functions(&self) -> Vec<(u64, u64, String)> {
+        let syms = self.elf.syms.iter().map(|sym| (sym, &self.elf.strtab));
+        let dynsyms = self
+            .elf
+            .dynsyms
+            .iter()
+            .map(|sym| (sym, &self.elf.dynstrtab));
+        syms.chain(dynsyms)
+            .filter(|(sym, _)| {
+                (sym.is_function() || sym.st_type() == STT_GNU_IFUNC) && sym.st_value != 0
+            })
+            .filter_map(|(sym, strtab)| {
+                let name = strtab.get_at(sym.st_name)?;
+                Some((sym.st_value, sym.st_size, name.to_string()))
+            })
+            .collect()
+    }
+
+    /// The `PT_LOAD` program headers that map the ELF file to memory
+    pub fn load_segments(&self) -> Vec<ProgramHeader> {
+        self.elf
+            .program_headers
+            .iter()
+            .filter(|phdr| phdr.p_type == PT_LOAD)
+            .cloned()
+            .collect()
+    }
+
+    /// The build ID of the `NT_GNU_BUILD_ID` note
+    pub fn build_id(&self) -> Option<&'a [u8]> {
+        self.elf
+            .iter_note_headers(self.data)?
+            .filter_map(|note| note.ok())
+            .find(|note| note.n_type == NT_GNU_BUILD_ID)
+            .map(|note| note.desc)
+    }
+
+    /// The file name and the CRC32 of the debug file of `.gnu_debuglink`
+    pub fn debuglink(&self) -> Option<(&'a str, u32)> {
+        let shdr = self
+            .elf
+            .section_headers
+            .iter()
+            .find(|shdr| self.elf.shdr_strtab.get_at(shdr.sh_name) == Some(".gnu_debuglink"))?;
+        let section = self.data.get(shdr.file_range()?)?;
+        let name_len = section.iter().position(|&c| c == 0)?;
+        let name = str::from_utf8(&section[..name_len]).ok()?;
+        // the CRC follows the file name padded to 4 bytes
+        let crc_offset = (name_len + 4) & !3;
+        let mut crc = section.get(crc_offset..crc_offset + 4)?;
+        Some((name, crc.read_u32::<NativeEndian>().ok()?))
+    }
 }
 
 #[derive(Debug)]
@@ -192,6 +257,47 @@ fn proc_maps_libs(pid: pid_t) -> io::Result<Vec<(String, String)>> {
         .collect())
 }
 
+/// A file mapped to the executable memory of a process
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ProcMap {
+    pub start: u64,
+    pub end: u64,
+    pub offset: u64,
+    pub path: String,
+}
+
+/// Parse a line of `/proc/<pid>/maps`, which is
+/// `<start>-<end> <perms> <offset> <dev> <inode> <path>`
+///
+/// Returns `None` if the line is not an executable mapping of a file.
+pub(crate) fn parse_proc_maps_line(line: &str) -> Option<ProcMap> {
+    let mut fields = line.splitn(6, ' ');
+    let mut range = fields.next()?.split('-');
+    let start = u64::from_str_radix(range.next()?, 16).ok()?;
+    let end = u64::from_str_radix(range.next()?, 16).ok()?;
+    if !fields.next()?.contains('x') {
+        return None;
+    }
+    let offset = u64::from_str_radix(fields.next()?, 16).ok()?;
+    let path = fields.nth(2)?.trim_start();
+    if !path.starts_with('/') {
+        return None;
+    }
+    Some(ProcMap {
+        start,
+        end,
+        offset,
+        path: path.to_string(),
+    })
+}
+
+pub(crate) fn proc_maps(pid: pid_t) -> io::Result<Vec<ProcMap>> {
+    Ok(fs::read_to_string(format!("/proc/{}/maps", pid))?
+        .lines()
+        .filter_map(parse_proc_maps_line)
+        .collect())
+}
+
 pub(crate) fn resolve_proc_maps_lib(pid: pid_t, lib: &str) -> Option<String> {
     let libs = proc_maps_libs(pid).ok()?;
 
@@ -207,3 +313,33 @@ pub(crate) fn resolve_proc_maps_lib(pid: pid_t, lib: &str) -> Option<String> {
 
     ret.map(|(_, v)| v.clone())
 }
+
+mod test {
+    #[test]
+    fn test_parse_proc_maps_line() {
+        use crate::symbols::{parse_proc_maps_line, ProcMap};
+        assert_eq!(
+            parse_proc_maps_line(
+                "7f2c5a828000-7f2c5a9bd000 r-xp 00028000 103:02 1836107                   /usr/lib/x86_64-linux-gnu/libc.so.6"
+            ),
+            Some(ProcMap {
+                start: 0x7f2c5a828000,
+                end: 0x7f2c5a9bd000,
+                offset: 0x28000,
+                path: "/usr/lib/x86_64-linux-gnu/libc.so.6".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_proc_maps_line(
+                "7f2c5a800000-7f2c5a828000 r--p 00000000 103:02 1836107                   /usr/lib/x86_64-linux-gnu/libc.so.6"
+            ),
+            None
+        );
+        assert_eq!(
+            parse_proc_maps_line(
+                "7ffd3c1f5000-7ffd3c1f7000 r-xp 00000000 00:00 0                          [vdso]"
+            ),
+            None
+        );
+    }
+}
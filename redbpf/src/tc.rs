@@ -0,0 +1,679 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*!
+Traffic control (tc) support.
+
+`tc_action` BPF programs are attached as direct-action `bpf` filters of the
+`clsact` qdisc of a network interface. This module talks to the kernel over
+rtnetlink to manage the `clsact` qdisc and the filters, so the `tc` utility is
+not needed. Programs are attached with
+[`TcAction::attach_tc`](../struct.TcAction.html#method.attach_tc).
+
+# Example
+```no_run
+# static PROBE_CODE: &[u8] = &[];
+use redbpf::load::Loader;
+use redbpf::tc;
+
+let mut loaded = Loader::load(PROBE_CODE).unwrap();
+loaded
+    .tc_action_mut("block_ports")
+    .expect("block_ports tc_action not found")
+    .attach_tc("eth0", tc::AttachPoint::Ingress, 1, 1)
+    .unwrap();
+
+for filter in tc::list_filters("eth0", tc::AttachPoint::Ingress).unwrap() {
+    println!("{:?}", filter);
+}
+```
+*/
+use std::ffi::CStr;
+use std::io;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::ptr;
+
+use libc::{
+    nlattr, nlmsgerr, nlmsghdr, sockaddr_nl, AF_NETLINK, AF_UNSPEC, NETLINK_ROUTE, NLMSG_DONE,
+    NLMSG_ERROR, NLM_F_ACK, NLM_F_CREATE, NLM_F_DUMP, NLM_F_EXCL, NLM_F_REQUEST, RTM_DELQDISC,
+    RTM_DELTFILTER, RTM_GETTFILTER, RTM_NEWQDISC, RTM_NEWTFILTER, SOCK_CLOEXEC, SOCK_RAW,
+};
+
+use crate::error::{Error, Result};
+use crate::if_nametoindex;
+
+const TCA_KIND: u16 = 1;
+const TCA_OPTIONS: u16 = 2;
+const TCA_BPF_FD: u16 = 6;
+const TCA_BPF_NAME: u16 = 7;
+const TCA_BPF_FLAGS: u16 = 8;
+const TCA_BPF_ID: u16 = 11;
+const TCA_BPF_FLAG_ACT_DIRECT: u32 = 1;
+
+const TC_H_MAJ_MASK: u32 = 0xFFFF_0000;
+const TC_H_MIN_MASK: u32 = 0x0000_FFFF;
+const TC_H_CLSACT: u32 = 0xFFFF_FFF1;
+const TC_H_MIN_INGRESS: u32 = 0xFFF2;
+const TC_H_MIN_EGRESS: u32 = 0xFFF3;
+
+const ETH_P_ALL: u16 = 0x0003;
+const NLA_ALIGNTO: usize = 4;
+
+/// The hook of the `clsact` qdisc a filter is attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachPoint {
+    /// Packets received by the interface.
+    Ingress,
+    /// Packets transmitted by the interface.
+    Egress,
+}
+
+impl AttachPoint {
+    fn parent(&self) -> u32 {
+        match self {
+            AttachPoint::Ingress => tc_h_make(TC_H_CLSACT, TC_H_MIN_INGRESS),
+            AttachPoint::Egress => tc_h_make(TC_H_CLSACT, TC_H_MIN_EGRESS),
+        }
+    }
+}
+
+/// A BPF filter attached to the `clsact` qdisc of an interface.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    /// Priority of the filter. Filters with lower priority run first.
+    pub priority: u16,
+    /// Handle of the filter within its priority.
+    pub handle: u32,
+    /// Name of the BPF program, as reported by the kernel.
+    pub name: Option<String>,
+    /// Id of the BPF program.
+    pub prog_id: Option<u32>,
+    /// Whether the filter runs in direct-action mode.
+    pub direct_action: bool,
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct tcmsg {
+    tcm_family: u8,
+    tcm_pad1: u8,
+    tcm_pad2: u16,
+    tcm_ifindex: i32,
+    tcm_handle: u32,
+    tcm_parent: u32,
+    tcm_info: u32,
+}
+
+#[inline]
+fn tc_h_make(maj: u32, min: u32) -> u32 {
+    (maj & TC_H_MAJ_MASK) | (min & TC_H_MIN_MASK)
+}
+
+#[inline]
+fn nla_align(len: usize) -> usize {
+    (len + NLA_ALIGNTO - 1) & !(NLA_ALIGNTO - 1)
+}
+
+/// Creates the `clsact` qdisc on `interface`.
+///
+/// Returns `false` if the interface already has a `clsact` qdisc.
+pub fn create_clsact(interface: &str) -> Result<bool> {
+    create_clsact_by_index(if_nametoindex(interface)?)
+}
+
+pub(crate) fn create_clsact_by_index(ifindex: u32) -> Result<bool> {
+    let mut req = Request::new(
+        RTM_NEWQDISC,
+        NLM_F_CREATE | NLM_F_EXCL,
+        tcmsg {
+            tcm_family: AF_UNSPEC as u8,
+            tcm_ifindex: ifindex as i32,
+            tcm_handle: tc_h_make(TC_H_CLSACT, 0),
+            tcm_parent: TC_H_CLSACT,
+            ..Default::default()
+        },
+    );
+    req.put_str(TCA_KIND, "clsact");
+    match NetlinkSocket::open()?.execute(req) {
+        Ok(_) => Ok(true),
+        Err(Error::IO(e)) if e.raw_os_error() == Some(libc::EEXIST) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Removes the `clsact` qdisc from `interface`.
+///
+/// All the filters attached to the qdisc are removed along with it.
+pub fn destroy_clsact(interface: &str) -> Result<()> {
+    let ifindex = if_nametoindex(interface)?;
+    let req = Request::new(
+        RTM_DELQDISC,
+        0,
+        tcmsg {
+            tcm_family: AF_UNSPEC as u8,
+            tcm_ifindex: ifindex as i32,
+            tcm_handle: tc_h_make(TC_H_CLSACT, 0),
+            tcm_parent: TC_H_CLSACT,
+            ..Default::default()
+        },
+    );
+    NetlinkSocket::open()?.execute(req).map(|_| ())
+}
+
+/// Lists the BPF filters attached to `attach_point` of `interface`.
+pub fn list_filters(interface: &str, attach_point: AttachPoint) -> Result<Vec<Filter>> {
+    let ifindex = if_nametoindex(interface)?;
+    let req = Request::new(
+        RTM_GETTFILTER,
+        NLM_F_DUMP,
+        tcmsg {
+            tcm_family: AF_UNSPEC as u8,
+            tcm_ifindex: ifindex as i32,
+            tcm_parent: attach_point.parent(),
+            ..Default::default()
+        },
+    );
+    Ok(NetlinkSocket::open()?
+        .execute(req)?
+        .iter()
+        .filter_map(|payload| parse_filter(payload))
+        .collect())
+}
+
+/// Parses the payload of a `RTM_NEWTFILTER` message of a filter dump.
+///
+/// Returns `None` if the message is not about a BPF filter.
+fn parse_filter(payload: &[u8]) -> Option<Filter> {
+    if payload.len() < mem::size_of::<tcmsg>() {
+        return None;
+    }
+    let tcm = unsafe { ptr::read_unaligned(payload.as_ptr() as *const tcmsg) };
+    // the first message of each priority describes the classifier itself,
+    // not a filter
+    if tcm.tcm_handle == 0 {
+        return None;
+    }
+    let mut filter = Filter {
+        priority: (tcm.tcm_info >> 16) as u16,
+        handle: tcm.tcm_handle,
+        name: None,
+        prog_id: None,
+        direct_action: false,
+    };
+    let mut is_bpf = false;
+    let attrs = payload
+        .get(nla_align(mem::size_of::<tcmsg>())..)
+        .unwrap_or(&[]);
+    for (type_, data) in Attrs::new(attrs) {
+        match type_ {
+            TCA_KIND => is_bpf = attr_str(data).as_deref() == Some("bpf"),
+            TCA_OPTIONS => {
+                for (type_, data) in Attrs::new(data) {
+                    match type_ {
+                        TCA_BPF_NAME => filter.name = attr_str(data),
+                        TCA_BPF_ID => filter.prog_id = attr_u32(data),
+                        TCA_BPF_FLAGS => {
+                            filter.direct_action = attr_u32(data)
+                                .map(|flags| flags & TCA_BPF_FLAG_ACT_DIRECT != 0)
+                                .unwrap_or(false)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if is_bpf {
+        Some(filter)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn attach_filter(
+    ifindex: u32,
+    attach_point: AttachPoint,
+    priority: u16,
+    handle: u32,
+    prog_fd: RawFd,
+    name: &str,
+) -> Result<()> {
+    let mut req = Request::new(
+        RTM_NEWTFILTER,
+        NLM_F_CREATE | NLM_F_EXCL,
+        tcmsg {
+            tcm_family: AF_UNSPEC as u8,
+            tcm_ifindex: ifindex as i32,
+            tcm_handle: handle,
+            tcm_parent: attach_point.parent(),
+            tcm_info: tc_h_make((priority as u32) << 16, ETH_P_ALL.to_be() as u32),
+            ..Default::default()
+        },
+    );
+    req.put_str(TCA_KIND, "bpf");
+    let options = req.begin_nested(TCA_OPTIONS);
+    req.put(TCA_BPF_FD, &(prog_fd as u32).to_ne_bytes());
+    req.put_str(TCA_BPF_NAME, name);
+    req.put(TCA_BPF_FLAGS, &TCA_BPF_FLAG_ACT_DIRECT.to_ne_bytes());
+    req.end_nested(options);
+    NetlinkSocket::open()?.execute(req).map(|_| ())
+}
+
+pub(crate) fn detach_filter(
+    ifindex: u32,
+    attach_point: AttachPoint,
+    priority: u16,
+    handle: u32,
+) -> Result<()> {
+    let mut req = Request::new(
+        RTM_DELTFILTER,
+        0,
+        tcmsg {
+            tcm_family: AF_UNSPEC as u8,
+            tcm_ifindex: ifindex as i32,
+            tcm_handle: handle,
+            tcm_parent: attach_point.parent(),
+            tcm_info: tc_h_make((priority as u32) << 16, ETH_P_ALL.to_be() as u32),
+            ..Default::default()
+        },
+    );
+    req.put_str(TCA_KIND, "bpf");
+    NetlinkSocket::open()?.execute(req).map(|_| ())
+}
+
+fn attr_str(data: &[u8]) -> Option<String> {
+    CStr::from_bytes_with_nul(data)
+        .ok()
+        .and_then(|s| s.to_str().ok())
+        .map(|s| s.to_string())
+}
+
+fn attr_u32(data: &[u8]) -> Option<u32> {
+    if data.len() < mem::size_of::<u32>() {
+        return None;
+    }
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&data[..4]);
+    Some(u32::from_ne_bytes(bytes))
+}
+
+/// An rtnetlink request that consists of a `tcmsg` followed by attributes.
+struct Request {
+    buf: Vec<u8>,
+}
+
+impl Request {
+    fn new(type_: u16, flags: libc::c_int, tcm: tcmsg) -> Self {
+        let mut buf = vec![0u8; mem::size_of::<nlmsghdr>()];
+        let hdr = nlmsghdr {
+            nlmsg_len: 0,
+            nlmsg_type: type_,
+            nlmsg_flags: (NLM_F_REQUEST | NLM_F_ACK | flags) as u16,
+            nlmsg_seq: 0,
+            nlmsg_pid: 0,
+        };
+        unsafe { ptr::write_unaligned(buf.as_mut_ptr() as *mut nlmsghdr, hdr) };
+        let tcm_bytes = unsafe {
+            std::slice::from_raw_parts(&tcm as *const _ as *const u8, mem::size_of::<tcmsg>())
+        };
+        buf.extend_from_slice(tcm_bytes);
+        buf.resize(nla_align(buf.len()), 0);
+        Request { buf }
+    }
+
+    fn put(&mut self, type_: u16, data: &[u8]) {
+        let attr = nlattr {
+            nla_len: (mem::size_of::<nlattr>() + data.len()) as u16,
+            nla_type: type_,
+        };
+        let offset = self.buf.len();
+        self.buf.resize(offset + mem::size_of::<nlattr>(), 0);
+        unsafe { ptr::write_unaligned(self.buf[offset..].as_mut_ptr() as *mut nlattr, attr) };
+        self.buf.extend_from_slice(data);
+        self.buf.resize(nla_align(self.buf.len()), 0);
+    }
+
+    fn put_str(&mut self, type_: u16, s: &str) {
+        let mut data = s.as_bytes().to_vec();
+        data.push(0);
+        self.put(type_, &data);
+    }
+
+    fn begin_nested(&mut self, type_: u16) -> usize {
+        let offset = self.buf.len();
+        self.put(type_ | libc::NLA_F_NESTED as u16, &[]);
+        offset
+    }
+
+    fn end_nested(&mut self, offset: usize) {
+        let len = (self.buf.len() - offset) as u16;
+        self.buf[offset..offset + 2].copy_from_slice(&len.to_ne_bytes());
+    }
+
+    fn finish(mut self, seq: u32) -> Vec<u8> {
+        let len = self.buf.len() as u32;
+        let hdr = self.buf.as_mut_ptr() as *mut nlmsghdr;
+        unsafe {
+            let mut h = ptr::read_unaligned(hdr);
+            h.nlmsg_len = len;
+            h.nlmsg_seq = seq;
+            ptr::write_unaligned(hdr, h);
+        }
+        self.buf
+    }
+}
+
+/// Iterator over the netlink attributes contained in a buffer.
+struct Attrs<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Attrs<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Attrs { buf }
+    }
+}
+
+impl<'a> Iterator for Attrs<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.len() < mem::size_of::<nlattr>() {
+            return None;
+        }
+        let attr = unsafe { ptr::read_unaligned(self.buf.as_ptr() as *const nlattr) };
+        let len = attr.nla_len as usize;
+        if len < mem::size_of::<nlattr>() || len > self.buf.len() {
+            return None;
+        }
+        let data = &self.buf[mem::size_of::<nlattr>()..len];
+        self.buf = &self.buf[nla_align(len).min(self.buf.len())..];
+        Some((attr.nla_type & !(libc::NLA_F_NESTED as u16), data))
+    }
+}
+
+struct NetlinkSocket {
+    fd: RawFd,
+    seq: u32,
+}
+
+impl NetlinkSocket {
+    fn open() -> Result<Self> {
+        unsafe {
+            let fd = libc::socket(AF_NETLINK, SOCK_RAW | SOCK_CLOEXEC, NETLINK_ROUTE);
+            if fd < 0 {
+                return Err(Error::IO(io::Error::last_os_error()));
+            }
+            let mut addr = mem::zeroed::<sockaddr_nl>();
+            addr.nl_family = AF_NETLINK as u16;
+            if libc::bind(
+                fd,
+                &addr as *const _ as *const libc::sockaddr,
+                mem::size_of::<sockaddr_nl>() as u32,
+            ) < 0
+            {
+                let err = io::Error::last_os_error();
+                libc::close(fd);
+                return Err(Error::IO(err));
+            }
+            Ok(NetlinkSocket { fd, seq: 0 })
+        }
+    }
+
+    /// Sends `req` and collects the payloads of the replies until the kernel
+    /// acknowledges the request or finishes dumping.
+    fn execute(&mut self, req: Request) -> Result<Vec<Vec<u8>>> {
+        self.seq += 1;
+        let seq = self.seq;
+        let msg = req.finish(seq);
+        if unsafe { libc::send(self.fd, msg.as_ptr() as *const _, msg.len(), 0) } < 0 {
+            return Err(Error::IO(io::Error::last_os_error()));
+        }
+
+        let mut payloads = vec![];
+        let mut buf = vec![0u8; 32 * 1024];
+        loop {
+            let len = unsafe { libc::recv(self.fd, buf.as_mut_ptr() as *mut _, buf.len(), 0) };
+            if len < 0 {
+                return Err(Error::IO(io::Error::last_os_error()));
+            }
+            if let Some(result) = parse_replies(&buf[..len as usize], seq, &mut payloads) {
+                return result.map(|_| payloads);
+            }
+        }
+    }
+}
+
+/// Collects the payloads of the replies to the request of `seq` in `msgs`
+/// into `payloads`.
+///
+/// Returns `None` if more replies are expected, or the result of the request
+/// if the kernel acknowledged it or finished dumping.
+fn parse_replies(msgs: &[u8], seq: u32, payloads: &mut Vec<Vec<u8>>) -> Option<Result<()>> {
+    let mut msgs = msgs;
+    while msgs.len() >= mem::size_of::<nlmsghdr>() {
+        let hdr = unsafe { ptr::read_unaligned(msgs.as_ptr() as *const nlmsghdr) };
+        let msg_len = hdr.nlmsg_len as usize;
+        if msg_len < mem::size_of::<nlmsghdr>() || msg_len > msgs.len() {
+            break;
+        }
+        let payload = &msgs[mem::size_of::<nlmsghdr>()..msg_len];
+        msgs = &msgs[nla_align(msg_len).min(msgs.len())..];
+        if hdr.nlmsg_seq != seq {
+            continue;
+        }
+        match hdr.nlmsg_type as libc::c_int {
+            NLMSG_DONE => return Some(Ok(())),
+            NLMSG_ERROR => {
+                if payload.len() < mem::size_of::<nlmsgerr>() {
+                    return Some(Err(Error::IO(io::Error::from(io::ErrorKind::InvalidData))));
+                }
+                let err = unsafe { ptr::read_unaligned(payload.as_ptr() as *const nlmsgerr) };
+                if err.error == 0 {
+                    return Some(Ok(()));
+                }
+                return Some(Err(Error::IO(io::Error::from_raw_os_error(-err.error))));
+            }
+            _ => payloads.push(payload.to_vec()),
+        }
+    }
+    None
+}
+
+impl Drop for NetlinkSocket {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tc::{nla_align, tcmsg, Request};
+    use libc::{nlattr, nlmsgerr, nlmsghdr, NLMSG_DONE, NLMSG_ERROR};
+    use std::{mem, ptr};
+
+    const HDR_LEN: usize = mem::size_of::<nlmsghdr>();
+    const TCM_LEN: usize = mem::size_of::<tcmsg>();
+
+    fn message(type_: libc::c_int, seq: u32, payload: &[u8]) -> Vec<u8> {
+        let hdr = nlmsghdr {
+            nlmsg_len: (HDR_LEN + payload.len()) as u32,
+            nlmsg_type: type_ as u16,
+            nlmsg_flags: 0,
+            nlmsg_seq: seq,
+            nlmsg_pid: 0,
+        };
+        let mut buf = vec![0u8; HDR_LEN];
+        unsafe { ptr::write_unaligned(buf.as_mut_ptr() as *mut nlmsghdr, hdr) };
+        buf.extend_from_slice(payload);
+        buf.resize(nla_align(buf.len()), 0);
+        buf
+    }
+
+    fn error_message(seq: u32, error: i32) -> Vec<u8> {
+        let mut err = unsafe { mem::zeroed::<nlmsgerr>() };
+        err.error = error;
+        let payload = unsafe {
+            std::slice::from_raw_parts(&err as *const _ as *const u8, mem::size_of::<nlmsgerr>())
+        };
+        message(NLMSG_ERROR, seq, payload)
+    }
+
+    fn filter_request() -> Request {
+        let mut req = Request::new(
+            libc::RTM_NEWTFILTER,
+            0,
+            tcmsg {
+                tcm_ifindex: 2,
+                tcm_handle: 1,
+                tcm_info: 3 << 16,
+                ..Default::default()
+            },
+        );
+        req.put_str(crate::tc::TCA_KIND, "bpf");
+        let options = req.begin_nested(crate::tc::TCA_OPTIONS);
+        req.put(crate::tc::TCA_BPF_ID, &42u32.to_ne_bytes());
+        req.put_str(crate::tc::TCA_BPF_NAME, "block");
+        req.put(
+            crate::tc::TCA_BPF_FLAGS,
+            &crate::tc::TCA_BPF_FLAG_ACT_DIRECT.to_ne_bytes(),
+        );
+        req.end_nested(options);
+        req
+    }
+
+    #[test]
+    fn test_request_encode() {
+        use crate::tc::{TCA_BPF_ID, TCA_KIND, TCA_OPTIONS};
+
+        let msg = filter_request().finish(7);
+        let hdr = unsafe { ptr::read_unaligned(msg.as_ptr() as *const nlmsghdr) };
+        assert_eq!(hdr.nlmsg_len as usize, msg.len());
+        assert_eq!(hdr.nlmsg_seq, 7);
+        assert_eq!(hdr.nlmsg_type, libc::RTM_NEWTFILTER);
+        assert_ne!(hdr.nlmsg_flags & libc::NLM_F_REQUEST as u16, 0);
+        assert_ne!(hdr.nlmsg_flags & libc::NLM_F_ACK as u16, 0);
+        assert_eq!(msg.len() % 4, 0);
+
+        let tcm = unsafe { ptr::read_unaligned(msg[HDR_LEN..].as_ptr() as *const tcmsg) };
+        assert_eq!(tcm.tcm_ifindex, 2);
+        assert_eq!(tcm.tcm_handle, 1);
+
+        // "bpf\0" fits in 4 bytes so the kind takes 8 bytes
+        let attrs = &msg[HDR_LEN + nla_align(TCM_LEN)..];
+        let kind = unsafe { ptr::read_unaligned(attrs.as_ptr() as *const nlattr) };
+        assert_eq!(kind.nla_type, TCA_KIND);
+        assert_eq!(kind.nla_len, 8);
+        assert_eq!(&attrs[4..8], b"bpf\0");
+
+        // the nested attribute covers the id (8), the padded name (12) and
+        // the flags (8)
+        let options = unsafe { ptr::read_unaligned(attrs[8..].as_ptr() as *const nlattr) };
+        assert_eq!(options.nla_type, TCA_OPTIONS | libc::NLA_F_NESTED as u16);
+        assert_eq!(options.nla_len, 4 + 8 + 12 + 8);
+        assert_eq!(attrs.len(), 8 + options.nla_len as usize);
+        let id = unsafe { ptr::read_unaligned(attrs[12..].as_ptr() as *const nlattr) };
+        assert_eq!(id.nla_type, TCA_BPF_ID);
+        assert_eq!(id.nla_len, 8);
+        assert_eq!(attrs[16..20], 42u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_attrs_decode() {
+        use crate::tc::{attr_str, attr_u32, Attrs, TCA_BPF_FLAGS, TCA_BPF_ID, TCA_BPF_NAME};
+        use crate::tc::{TCA_KIND, TCA_OPTIONS};
+
+        let msg = filter_request().finish(1);
+        let attrs = Attrs::new(&msg[HDR_LEN + nla_align(TCM_LEN)..]).collect::<Vec<_>>();
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(attrs[0].0, TCA_KIND);
+        assert_eq!(attr_str(attrs[0].1).as_deref(), Some("bpf"));
+        // the nested flag is masked out
+        assert_eq!(attrs[1].0, TCA_OPTIONS);
+        let options = Attrs::new(attrs[1].1).collect::<Vec<_>>();
+        assert_eq!(
+            options.iter().map(|(type_, _)| *type_).collect::<Vec<_>>(),
+            vec![TCA_BPF_ID, TCA_BPF_NAME, TCA_BPF_FLAGS]
+        );
+        assert_eq!(attr_u32(options[0].1), Some(42));
+        assert_eq!(attr_str(options[1].1).as_deref(), Some("block"));
+
+        // truncated attributes end the iteration
+        assert_eq!(Attrs::new(&[8, 0, 1]).count(), 0);
+        assert_eq!(Attrs::new(&[16, 0, 1, 0, 0, 0, 0, 0]).count(), 0);
+        assert_eq!(Attrs::new(&[2, 0, 1, 0]).count(), 0);
+        assert_eq!(attr_u32(&[1, 2]), None);
+        assert_eq!(attr_str(b"bpf"), None);
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        use crate::tc::parse_filter;
+
+        let msg = filter_request().finish(1);
+        let filter = parse_filter(&msg[HDR_LEN..]).unwrap();
+        assert_eq!(filter.priority, 3);
+        assert_eq!(filter.handle, 1);
+        assert_eq!(filter.prog_id, Some(42));
+        assert_eq!(filter.name.as_deref(), Some("block"));
+        assert!(filter.direct_action);
+
+        // the classifier itself and truncated messages are not filters
+        let msg = Request::new(libc::RTM_NEWTFILTER, 0, tcmsg::default()).finish(1);
+        assert!(parse_filter(&msg[HDR_LEN..]).is_none());
+        assert!(parse_filter(&[0; 4]).is_none());
+    }
+
+    #[test]
+    fn test_parse_replies() {
+        use crate::error::Error;
+        use crate::tc::parse_replies;
+
+        // payloads are collected until the dump is done
+        let mut msgs = message(libc::RTM_NEWTFILTER as libc::c_int, 5, &[1, 2, 3, 4]);
+        let mut payloads = vec![];
+        assert!(parse_replies(&msgs, 5, &mut payloads).is_none());
+        assert_eq!(payloads, vec![vec![1, 2, 3, 4]]);
+        msgs.extend(message(NLMSG_DONE, 5, &[]));
+        payloads.clear();
+        assert!(matches!(
+            parse_replies(&msgs, 5, &mut payloads),
+            Some(Ok(()))
+        ));
+        assert_eq!(payloads.len(), 1);
+
+        // replies to other requests are skipped
+        let mut payloads = vec![];
+        assert!(parse_replies(&message(NLMSG_DONE, 4, &[]), 5, &mut payloads).is_none());
+
+        // acknowledgement and error
+        assert!(matches!(
+            parse_replies(&error_message(5, 0), 5, &mut payloads),
+            Some(Ok(()))
+        ));
+        match parse_replies(&error_message(5, -libc::EEXIST), 5, &mut payloads) {
+            Some(Err(Error::IO(e))) => assert_eq!(e.raw_os_error(), Some(libc::EEXIST)),
+            _ => panic!("expected EEXIST"),
+        }
+
+        // an error message too short to hold `nlmsgerr`
+        match parse_replies(&message(NLMSG_ERROR, 5, &[0; 4]), 5, &mut payloads) {
+            Some(Err(Error::IO(e))) => assert_eq!(e.kind(), std::io::ErrorKind::InvalidData),
+            _ => panic!("expected InvalidData"),
+        }
+
+        // a header whose length exceeds the buffer
+        let mut msg = message(NLMSG_DONE, 5, &[]);
+        msg[0] = 64;
+        assert!(parse_replies(&msg, 5, &mut payloads).is_none());
+    }
+}
@@ -0,0 +1,406 @@
+// Copyright 2021 Authors of Red Sift
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*!
+USDT (user statically-defined tracing) probes.
+
+Programs like postgres, JVMs and language runtimes define static tracepoints
+with the `DTRACE_PROBE` macros of systemtap's `<sys/sdt.h>`. Each probe site
+is a `nop` instruction recorded in the `.note.stapsdt` section of the binary
+together with the locations of the arguments of the probe, e.g. `-4@%edi` for
+a signed 32-bit integer in `edi`.
+
+[`UProbe::attach_usdt`](../struct.UProbe.html#method.attach_usdt) attaches a
+uprobe to all the sites of a USDT probe. The argument specs it returns are
+stored in a map so that the BPF program can read the arguments with
+`redbpf_probes::usdt::UsdtSpec`.
+
+# Example
+```no_run
+use redbpf::load::Loader;
+use redbpf::usdt::UsdtSpec;
+use redbpf::HashMap;
+
+let mut loaded = Loader::load_file("postgres.elf").expect("error loading probe");
+let specs = loaded
+    .uprobe_mut("query_start")
+    .expect("uprobe not found")
+    .attach_usdt("postgresql", "query__start", "/usr/bin/postgres", Some(1234))
+    .expect("error attaching USDT probe");
+let spec_map = HashMap::<u64, UsdtSpec>::new(loaded.map("USDT_SPECS").unwrap()).unwrap();
+for (addr, spec) in specs {
+    spec_map.set(addr, spec);
+}
+```
+*/
+
+use byteorder::{NativeEndian, ReadBytesExt};
+use goblin::elf::program_header::PT_LOAD;
+use goblin::elf::Elf;
+use std::fs;
+use std::io::Cursor;
+use std::str;
+
+use crate::error::Result;
+
+/// The maximum number of arguments of a USDT probe
+pub const USDT_MAX_ARGS: usize = 12;
+
+/// The argument is the constant `val_off`
+pub const USDT_ARG_CONST: u32 = 0;
+/// The argument is the value of the register at `reg_off`
+pub const USDT_ARG_REG: u32 = 1;
+/// The argument is the value at the address of the register at `reg_off`
+/// plus `val_off`
+pub const USDT_ARG_REG_DEREF: u32 = 2;
+
+const NT_STAPSDT: u32 = 3;
+
+/// The location of an argument of a USDT probe
+///
+/// This must have the same layout as `redbpf_probes::usdt::UsdtArgSpec`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UsdtArgSpec {
+    /// The constant value, or the offset added to the register to dereference
+    pub val_off: u64,
+    /// One of `USDT_ARG_CONST`, `USDT_ARG_REG` and `USDT_ARG_REG_DEREF`
+    pub arg_type: u32,
+    /// The offset of the register in `struct pt_regs`
+    pub reg_off: u16,
+    pub arg_signed: bool,
+    /// The number of bits to shift the 64-bit value by to get the argument,
+    /// i.e. 64 minus the size of the argument in bits
+    pub arg_bitshift: u8,
+}
+
+/// The locations of the arguments of a USDT probe site
+///
+/// This must have the same layout as `redbpf_probes::usdt::UsdtSpec`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UsdtSpec {
+    pub args: [UsdtArgSpec; USDT_MAX_ARGS],
+    pub nr_args: u16,
+}
+
+/// A site of a USDT probe in an ELF file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsdtProbe {
+    pub provider: String,
+    pub name: String,
+    /// The virtual address of the probe site in the ELF file
+    pub addr: u64,
+    /// The file offset of the probe site
+    pub offset: u64,
+    /// The file offset of the semaphore of the probe, or 0 if the probe has
+    /// no semaphore
+    pub semaphore: u64,
+    /// The argument specs, e.g. `-4@%edi 8@-16(%rbp)`
+    pub args: String,
+}
+
+impl UsdtProbe {
+    /// Parse the argument specs of the probe site
+    ///
+    /// Returns `None` if the probe has more than
+    /// [`USDT_MAX_ARGS`](constant.USDT_MAX_ARGS.html) arguments or if an
+    /// argument is in a form that can not be read by BPF programs, e.g.
+    /// relative to the instruction pointer or indexed by another register.
+    pub fn spec(&self) -> Option<UsdtSpec> {
+        let args = split_args(&self.args);
+        if args.len() > USDT_MAX_ARGS {
+            return None;
+        }
+        let mut spec = UsdtSpec {
+            nr_args: args.len() as u16,
+            ..Default::default()
+        };
+        for (arg_spec, arg) in spec.args.iter_mut().zip(args) {
+            *arg_spec = parse_arg(arg)?;
+        }
+        Some(spec)
+    }
+}
+
+/// List the sites of all the USDT probes of the ELF file at `path`
+pub fn probes(path: &str) -> Result<Vec<UsdtProbe>> {
+    let data = fs::read(path)?;
+    let elf = Elf::parse(&data)?;
+    Ok(parse_probes(&elf, &data))
+}
+
+pub(crate) fn parse_probes(elf: &Elf, data: &[u8]) -> Vec<UsdtProbe> {
+    // prelink moves `.stapsdt.base` without updating the notes, so the
+    // addresses of the notes are adjusted by how far it moved
+    let base_addr = elf
+        .section_headers
+        .iter()
+        .find(|shdr| elf.shdr_strtab.get_at(shdr.sh_name) == Some(".stapsdt.base"))
+        .map(|shdr| shdr.sh_addr);
+    let notes = match elf.iter_note_sections(data, Some(".note.stapsdt")) {
+        Some(notes) => notes,
+        None => return Vec::new(),
+    };
+    notes
+        .filter_map(|note| note.ok())
+        .filter(|note| note.n_type == NT_STAPSDT && note.name == "stapsdt")
+        .filter_map(|note| {
+            let (mut addr, base, mut semaphore, strings) = parse_note_desc(note.desc, elf.is_64)?;
+            if let Some(base_addr) = base_addr {
+                addr = addr.wrapping_sub(base).wrapping_add(base_addr);
+                if semaphore != 0 {
+                    semaphore = semaphore.wrapping_sub(base).wrapping_add(base_addr);
+                }
+            }
+            let mut strings = strings.split(|&c| c == 0);
+            let provider = str::from_utf8(strings.next()?).ok()?;
+            let name = str::from_utf8(strings.next()?).ok()?;
+            let args = str::from_utf8(strings.next().unwrap_or_default()).ok()?;
+            let semaphore = if semaphore != 0 {
+                vaddr_to_offset(elf, semaphore)?
+            } else {
+                0
+            };
+            Some(UsdtProbe {
+                provider: provider.to_string(),
+                name: name.to_string(),
+                addr,
+                offset: vaddr_to_offset(elf, addr)?,
+                semaphore,
+                args: args.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parse the description of a `stapsdt` note, which is the address of the
+/// probe site, the link-time address of `.stapsdt.base`, the address of the
+/// semaphore and then `provider\0name\0args\0`
+fn parse_note_desc(desc: &[u8], is_64: bool) -> Option<(u64, u64, u64, &[u8])> {
+    let mut cursor = Cursor::new(desc);
+    let mut read_addr = || {
+        if is_64 {
+            cursor.read_u64::<NativeEndian>().ok()
+        } else {
+            cursor.read_u32::<NativeEndian>().ok().map(u64::from)
+        }
+    };
+    let addr = read_addr()?;
+    let base = read_addr()?;
+    let semaphore = read_addr()?;
+    let strings_off = if is_64 { 24 } else { 12 };
+    Some((addr, base, semaphore, &desc[strings_off..]))
+}
+
+fn vaddr_to_offset(elf: &Elf, vaddr: u64) -> Option<u64> {
+    elf.program_headers
+        .iter()
+        .find(|phdr| {
+            phdr.p_type == PT_LOAD && phdr.p_vaddr <= vaddr && vaddr < phdr.p_vaddr + phdr.p_filesz
+        })
+        .map(|phdr| vaddr - phdr.p_vaddr + phdr.p_offset)
+}
+
+/// Split the argument specs separated by spaces. The arguments of aarch64
+/// like `[sp, 8]` contain spaces themselves.
+fn split_args(args: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ' ' if depth == 0 => {
+                if start < i {
+                    result.push(&args[start..i]);
+                }
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < args.len() {
+        result.push(&args[start..]);
+    }
+    result
+}
+
+/// Parse an argument spec of the form `<size>@<location>`
+///
+/// The size is the size of the argument in bytes, negative if the argument is
+/// signed.
+pub(crate) fn parse_arg(arg: &str) -> Option<UsdtArgSpec> {
+    let mut parts = arg.splitn(2, '@');
+    let size = parts.next()?.parse::<i32>().ok()?;
+    let location = parts.next()?;
+    let bits = match size.abs() {
+        1 | 2 | 4 | 8 => size.unsigned_abs() as u8 * 8,
+        _ => return None,
+    };
+    let (arg_type, reg_off, val_off) = parse_location(location)?;
+    Some(UsdtArgSpec {
+        val_off,
+        arg_type,
+        reg_off,
+        arg_signed: size < 0,
+        arg_bitshift: 64 - bits,
+    })
+}
+
+/// Parse `$<imm>`, `%<reg>` and `<off>(%<reg>)`
+#[cfg(target_arch = "x86_64")]
+fn parse_location(location: &str) -> Option<(u32, u16, u64)> {
+    if let Some(imm) = location.strip_prefix('$') {
+        return Some((USDT_ARG_CONST, 0, parse_int(imm)? as u64));
+    }
+    if let Some(reg) = location.strip_prefix('%') {
+        return Some((USDT_ARG_REG, reg_offset(reg)?, 0));
+    }
+    let (off, reg) = location.strip_suffix(')')?.split_once("(%")?;
+    let off = if off.is_empty() { 0 } else { parse_int(off)? };
+    Some((USDT_ARG_REG_DEREF, reg_offset(reg)?, off as u64))
+}
+
+/// Parse `<imm>`, `<reg>`, `[<reg>]` and `[<reg>, <off>]`
+#[cfg(target_arch = "aarch64")]
+fn parse_location(location: &str) -> Option<(u32, u16, u64)> {
+    if let Some(deref) = location.strip_prefix('[') {
+        let deref = deref.strip_suffix(']')?;
+        let mut parts = deref.splitn(2, ',');
+        let reg = parts.next()?.trim();
+        let off = match parts.next() {
+            Some(off) => parse_int(off.trim())?,
+            None => 0,
+        };
+        return Some((USDT_ARG_REG_DEREF, reg_offset(reg)?, off as u64));
+    }
+    if let Some(imm) = parse_int(location) {
+        return Some((USDT_ARG_CONST, 0, imm as u64));
+    }
+    Some((USDT_ARG_REG, reg_offset(location)?, 0))
+}
+
+fn parse_int(s: &str) -> Option<i64> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let value = match s.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => s.parse::<i64>().ok()?,
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// The offset of the register in `struct pt_regs` of x86_64
+#[cfg(target_arch = "x86_64")]
+fn reg_offset(reg: &str) -> Option<u16> {
+    let off = match reg {
+        "r15" | "r15d" | "r15w" | "r15b" => 0,
+        "r14" | "r14d" | "r14w" | "r14b" => 8,
+        "r13" | "r13d" | "r13w" | "r13b" => 16,
+        "r12" | "r12d" | "r12w" | "r12b" => 24,
+        "rbp" | "ebp" | "bp" | "bpl" => 32,
+        "rbx" | "ebx" | "bx" | "bl" => 40,
+        "r11" | "r11d" | "r11w" | "r11b" => 48,
+        "r10" | "r10d" | "r10w" | "r10b" => 56,
+        "r9" | "r9d" | "r9w" | "r9b" => 64,
+        "r8" | "r8d" | "r8w" | "r8b" => 72,
+        "rax" | "eax" | "ax" | "al" => 80,
+        "rcx" | "ecx" | "cx" | "cl" => 88,
+        "rdx" | "edx" | "dx" | "dl" => 96,
+        "rsi" | "esi" | "si" | "sil" => 104,
+        "rdi" | "edi" | "di" | "dil" => 112,
+        "rip" => 128,
+        "rsp" | "esp" | "sp" | "spl" => 152,
+        _ => return None,
+    };
+    Some(off)
+}
+
+/// The offset of the register in `struct user_pt_regs` of aarch64
+#[cfg(target_arch = "aarch64")]
+fn reg_offset(reg: &str) -> Option<u16> {
+    if reg == "sp" {
+        return Some(31 * 8);
+    }
+    let n = reg
+        .strip_prefix('x')
+        .or_else(|| reg.strip_prefix('w'))?
+        .parse::<u16>()
+        .ok()?;
+    if n > 30 {
+        return None;
+    }
+    Some(n * 8)
+}
+
+mod test {
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_parse_arg() {
+        use crate::usdt::{
+            parse_arg, UsdtArgSpec, USDT_ARG_CONST, USDT_ARG_REG, USDT_ARG_REG_DEREF,
+        };
+        assert_eq!(
+            parse_arg("-4@%edi"),
+            Some(UsdtArgSpec {
+                val_off: 0,
+                arg_type: USDT_ARG_REG,
+                reg_off: 112,
+                arg_signed: true,
+                arg_bitshift: 32,
+            })
+        );
+        assert_eq!(
+            parse_arg("8@-16(%rbp)"),
+            Some(UsdtArgSpec {
+                val_off: -16i64 as u64,
+                arg_type: USDT_ARG_REG_DEREF,
+                reg_off: 32,
+                arg_signed: false,
+                arg_bitshift: 0,
+            })
+        );
+        assert_eq!(
+            parse_arg("2@(%rax)"),
+            Some(UsdtArgSpec {
+                val_off: 0,
+                arg_type: USDT_ARG_REG_DEREF,
+                reg_off: 80,
+                arg_signed: false,
+                arg_bitshift: 48,
+            })
+        );
+        assert_eq!(
+            parse_arg("-1@$-5"),
+            Some(UsdtArgSpec {
+                val_off: -5i64 as u64,
+                arg_type: USDT_ARG_CONST,
+                reg_off: 0,
+                arg_signed: true,
+                arg_bitshift: 56,
+            })
+        );
+        assert_eq!(parse_arg("8@counter(%rip)"), None);
+        assert_eq!(parse_arg("4@-8(%rbp,%rax,4)"), None);
+        assert_eq!(parse_arg("3@%eax"), None);
+    }
+
+    #[test]
+    fn test_split_args() {
+        use crate::usdt::split_args;
+        assert_eq!(
+            split_args("-4@%edi 8@-16(%rbp)"),
+            vec!["-4@%edi", "8@-16(%rbp)"]
+        );
+        assert_eq!(split_args("8@x0 -4@[sp, 8]"), vec!["8@x0", "-4@[sp, 8]"]);
+        assert!(split_args("").is_empty());
+    }
+}